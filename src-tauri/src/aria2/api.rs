@@ -1,19 +1,58 @@
+use crate::aria2::logs::{spawn_log_reader, Aria2Logs};
 use crate::aria2::types::*;
+use crate::error::AppError;
 use reqwest::Client;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use std::time::Duration;
 
 const DEFAULT_RPC_URL: &str = "http://localhost:6800/jsonrpc";
-const ARIA2_START_UP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Number of speed samples per GID averaged into `DownloadInfo::average_speed`
+const SPEED_SAMPLE_WINDOW: usize = 5;
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_START_UP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Left out by `validate_config`: they'd load or overwrite the real session
+const VALIDATION_SKIPPED_ARGS: &[&str] = &["--input-file", "--save-session", "--save-session-interval"];
+/// Trailing log lines quoted in a startup failure message
+const START_UP_ERROR_LOG_LINES: usize = 10;
+/// Entries asked for per call when reading a whole waiting or stopped list
+const LIST_PAGE_SIZE: usize = 1000;
+/// Published alongside app releases; maps `<os>-<arch>` to a raw aria2c binary and its SHA-256
+const ARIA2_BINARY_MANIFEST_URL: &str =
+    "https://github.com/basstimam/trauso/releases/latest/download/aria2-binaries.json";
 
 pub struct Aria2Client {
     client: Client,
-    rpc_url: String,
+    rpc_url: Mutex<String>,
+    rpc_secret: Mutex<Option<String>>,
     aria2_process: Mutex<Option<Child>>,
+    /// Held while the daemon is started, stopped or restarted, so concurrent callers
+    /// can't spawn two daemons or restart one that's mid-start
+    lifecycle: tokio::sync::Mutex<()>,
+    keep_on_exit: Mutex<bool>,
     max_overall_download_limit_kb_per_sec: Mutex<u64>,
     max_download_limit_kb_per_sec: Mutex<u64>,
+    extra_args: Mutex<Vec<String>>,
+    seed_ratio: Mutex<f64>,
+    max_concurrent_downloads: Mutex<u32>,
+    file_allocation: Mutex<FileAllocation>,
+    overwrite_policy: Mutex<OverwritePolicy>,
+    start_up_timeout: Mutex<Duration>,
+    disable_ipv6: Mutex<bool>,
+    async_dns_server: Mutex<Option<String>>,
+    connect_timeout_secs: Mutex<u32>,
+    lowest_speed_limit: Mutex<String>,
+    logs: Arc<Aria2Logs>,
+    log_readers: Mutex<Vec<JoinHandle<()>>>,
+    origins: Mutex<HashMap<String, DownloadOrigin>>,
+    speed_samples: Mutex<HashMap<String, VecDeque<u64>>>,
+    /// Percent of the overall limit given to a download by `set_download_share`
+    download_shares: Mutex<HashMap<String, u8>>,
 }
 
 impl Default for Aria2Client {
@@ -31,13 +70,62 @@ impl Aria2Client {
 
         Self {
             client,
-            rpc_url: rpc_url.to_string(),
+            rpc_url: Mutex::new(rpc_url.to_string()),
+            rpc_secret: Mutex::new(None),
             aria2_process: Mutex::new(None),
+            lifecycle: tokio::sync::Mutex::new(()),
+            keep_on_exit: Mutex::new(false),
             max_overall_download_limit_kb_per_sec: Mutex::new(max_overall_limit_kb_per_sec),
             max_download_limit_kb_per_sec: Mutex::new(max_download_limit_kb_per_sec),
+            extra_args: Mutex::new(Vec::new()),
+            seed_ratio: Mutex::new(1.0),
+            max_concurrent_downloads: Mutex::new(5),
+            file_allocation: Mutex::new(FileAllocation::None),
+            overwrite_policy: Mutex::new(OverwritePolicy::Rename),
+            start_up_timeout: Mutex::new(DEFAULT_START_UP_TIMEOUT),
+            disable_ipv6: Mutex::new(false),
+            async_dns_server: Mutex::new(None),
+            connect_timeout_secs: Mutex::new(60),
+            lowest_speed_limit: Mutex::new("0".to_string()),
+            logs: Arc::new(Aria2Logs::default()),
+            log_readers: Mutex::new(Vec::new()),
+            origins: Mutex::new(Self::load_origins()),
+            speed_samples: Mutex::new(HashMap::new()),
+            download_shares: Mutex::new(HashMap::new()),
         }
     }
 
+    pub fn set_rpc_endpoint(&self, rpc_url: &str, secret: Option<String>) {
+        *self.rpc_url.lock().unwrap() = rpc_url.to_string();
+        *self.rpc_secret.lock().unwrap() = secret.filter(|s| !s.is_empty());
+    }
+
+    pub fn get_rpc_url(&self) -> String {
+        self.rpc_url.lock().unwrap().clone()
+    }
+
+    /// True when the endpoint points at another host, i.e. a daemon we never spawn ourselves.
+    pub fn is_remote(&self) -> bool {
+        !Self::is_local_endpoint(&self.get_rpc_url())
+    }
+
+    fn is_local_endpoint(rpc_url: &str) -> bool {
+        match reqwest::Url::parse(rpc_url) {
+            Ok(url) => matches!(
+                url.host_str(),
+                Some("localhost") | Some("127.0.0.1") | Some("[::1]") | Some("::1")
+            ),
+            Err(_) => false,
+        }
+    }
+
+    fn rpc_listen_port(&self) -> u16 {
+        reqwest::Url::parse(&self.get_rpc_url())
+            .ok()
+            .and_then(|url| url.port_or_known_default())
+            .unwrap_or(6800)
+    }
+
     pub fn set_bandwidth_limit(&self, max_overall_limit_kb_per_sec: u64, max_download_limit_kb_per_sec: u64) {
         *self.max_overall_download_limit_kb_per_sec.lock().unwrap() = max_overall_limit_kb_per_sec;
         *self.max_download_limit_kb_per_sec.lock().unwrap() = max_download_limit_kb_per_sec;
@@ -49,8 +137,223 @@ impl Aria2Client {
         (overall, per_download)
     }
 
-    fn get_aria2_path() -> Option<PathBuf> {
+    /// KB/s for a download given `percent` of the overall limit, no more than the
+    /// per-download limit. A share of unlimited is unlimited, so only the per-download
+    /// limit applies then; 0 is unlimited throughout.
+    pub fn share_limit_kb(overall_kb_per_sec: u64, per_download_kb_per_sec: u64, percent: u8) -> u64 {
+        if overall_kb_per_sec == 0 {
+            return per_download_kb_per_sec;
+        }
+        let share = (overall_kb_per_sec * percent as u64 / 100).max(1);
+        match per_download_kb_per_sec {
+            0 => share,
+            cap => share.min(cap),
+        }
+    }
+
+    /// Limits `gid` to `percent` of the overall limit, or with `None` puts it back on the
+    /// per-download limit. Returns the KB/s applied; `apply_download_shares` keeps it in
+    /// step with the overall limit.
+    pub async fn set_download_share(&self, gid: &str, percent: Option<u8>) -> Result<u64, AppError> {
+        let (overall, per_download) = self.get_bandwidth_limit();
+        let limit = match percent {
+            Some(percent) => Self::share_limit_kb(overall, per_download, percent),
+            None => per_download,
+        };
+        self.change_option(gid, "max-download-limit", &format!("{}K", limit)).await?;
+
+        let mut shares = self.download_shares.lock().unwrap();
+        match percent {
+            Some(percent) => shares.insert(gid.to_string(), percent),
+            None => shares.remove(gid),
+        };
+        Ok(limit)
+    }
+
+    /// Recomputes every download's share after the overall limit changed. Downloads
+    /// aria2 no longer takes options for, like finished ones, are forgotten.
+    pub async fn apply_download_shares(&self) {
+        let (overall, per_download) = self.get_bandwidth_limit();
+        let shares: Vec<(String, u8)> = self
+            .download_shares
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(gid, percent)| (gid.clone(), *percent))
+            .collect();
+        for (gid, percent) in shares {
+            let limit = Self::share_limit_kb(overall, per_download, percent);
+            if let Err(e) = self.change_option(&gid, "max-download-limit", &format!("{}K", limit)).await {
+                log::debug!("Dropping the bandwidth share of {}: {}", gid, e);
+                self.download_shares.lock().unwrap().remove(&gid);
+            }
+        }
+    }
+
+    /// Extra user-supplied flags appended verbatim on the next daemon start
+    pub fn set_extra_args(&self, args: Vec<String>) {
+        *self.extra_args.lock().unwrap() = args;
+    }
+
+    pub fn set_seed_ratio(&self, ratio: f64) {
+        *self.seed_ratio.lock().unwrap() = ratio;
+    }
+
+    pub fn set_max_concurrent_downloads(&self, max: u32) {
+        *self.max_concurrent_downloads.lock().unwrap() = max;
+    }
+
+    pub fn set_file_allocation(&self, allocation: FileAllocation) {
+        *self.file_allocation.lock().unwrap() = allocation;
+    }
+
+    pub fn get_file_allocation(&self) -> FileAllocation {
+        *self.file_allocation.lock().unwrap()
+    }
+
+    pub fn set_overwrite_policy(&self, policy: OverwritePolicy) {
+        *self.overwrite_policy.lock().unwrap() = policy;
+    }
+
+    pub fn set_start_up_timeout(&self, timeout: Duration) {
+        *self.start_up_timeout.lock().unwrap() = timeout;
+    }
+
+    pub fn set_disable_ipv6(&self, disabled: bool) {
+        *self.disable_ipv6.lock().unwrap() = disabled;
+    }
+
+    /// Nameservers aria2 resolves with instead of the system's, comma-separated
+    pub fn set_async_dns_server(&self, servers: Option<String>) {
+        *self.async_dns_server.lock().unwrap() = servers.filter(|s| !s.trim().is_empty());
+    }
+
+    pub fn set_connect_timeout(&self, secs: u32) {
+        *self.connect_timeout_secs.lock().unwrap() = secs;
+    }
+
+    /// Speed below which aria2 aborts a download, in aria2's syntax (`10K`, `1M`)
+    pub fn set_lowest_speed_limit(&self, limit: String) {
+        *self.lowest_speed_limit.lock().unwrap() = limit;
+    }
+
+    /// Leave the daemon running when the app quits. Only daemons started after this is
+    /// set are detached enough to survive it.
+    pub fn set_keep_on_exit(&self, keep: bool) {
+        *self.keep_on_exit.lock().unwrap() = keep;
+    }
+
+    pub fn set_log_to_file(&self, enabled: bool) {
+        self.logs.set_log_to_file(enabled);
+    }
+
+    pub fn get_logs(&self, max_lines: usize) -> Vec<String> {
+        self.logs.tail(max_lines)
+    }
+
+    /// Remembers where a GID came from, since aria2 only knows the resolved direct link.
+    /// Persisted so the mapping survives restarts alongside the aria2 session.
+    pub fn record_origin(&self, gid: &str, origin: DownloadOrigin) {
+        let mut origins = self.origins.lock().unwrap();
+        origins.insert(gid.to_string(), origin);
+        Self::save_origins(&origins);
+    }
+
+    pub fn get_origin(&self, gid: &str) -> Option<DownloadOrigin> {
+        self.origins.lock().unwrap().get(gid).cloned()
+    }
+
+    /// Stamps and returns the completion time; later calls keep the first one
+    pub fn mark_completed(&self, gid: &str) -> Option<String> {
+        let mut origins = self.origins.lock().unwrap();
+        let origin = origins.entry(gid.to_string()).or_default();
+        if origin.completed_at.is_some() {
+            return origin.completed_at.clone();
+        }
+
+        let completed_at = chrono::Local::now().to_rfc3339();
+        origin.completed_at = Some(completed_at.clone());
+        Self::save_origins(&origins);
+        Some(completed_at)
+    }
+
+    /// Notes that `gid` is in the history, so it's never recorded again
+    pub fn mark_recorded(&self, gid: &str) {
+        let mut origins = self.origins.lock().unwrap();
+        let origin = origins.entry(gid.to_string()).or_default();
+        if !origin.recorded {
+            origin.recorded = true;
+            Self::save_origins(&origins);
+        }
+    }
+
+    pub fn forget_origin(&self, gid: &str) {
+        let mut origins = self.origins.lock().unwrap();
+        if origins.remove(gid).is_some() {
+            Self::save_origins(&origins);
+        }
+    }
+
+    /// GIDs recorded with this `group_id`
+    pub fn group_gids(&self, group_id: &str) -> Vec<String> {
+        self.origins
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, origin)| origin.group_id.as_deref() == Some(group_id))
+            .map(|(gid, _)| gid.clone())
+            .collect()
+    }
+
+    fn origins_path() -> PathBuf {
+        crate::settings::api::get_config_dir().join("download_origins.json")
+    }
+
+    fn load_origins() -> HashMap<String, DownloadOrigin> {
+        fs::read_to_string(Self::origins_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_origins(origins: &HashMap<String, DownloadOrigin>) {
+        let path = Self::origins_path();
+        let result = serde_json::to_string(origins)
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&path, content).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::warn!("Failed to save download origins to {}: {}", path.display(), e);
+        }
+    }
+
+    /// Pushes a speed sample into the GID's window and returns the window average.
+    /// Anything but an active download drops the window, so after a resume the
+    /// average isn't dragged down by the zero samples recorded while paused.
+    fn record_speed_sample(&self, gid: &str, status: &DownloadStatus, speed: u64) -> u64 {
+        let mut samples = self.speed_samples.lock().unwrap();
+
+        if *status != DownloadStatus::Active {
+            samples.remove(gid);
+            return speed;
+        }
+
+        let window = samples.entry(gid.to_string()).or_default();
+        if window.len() == SPEED_SAMPLE_WINDOW {
+            window.pop_front();
+        }
+        window.push_back(speed);
+
+        window.iter().sum::<u64>() / window.len() as u64
+    }
+
+    fn installed_binary_path() -> PathBuf {
+        let name = if cfg!(target_os = "windows") { "aria2c.exe" } else { "aria2c" };
+        crate::settings::api::get_config_dir().join("aria2").join(name)
+    }
+
+    fn get_aria2_path() -> Result<PathBuf, AppError> {
         let possible_paths = [
+            Self::installed_binary_path(),
             PathBuf::from("aria2/aria2c.exe"),
             PathBuf::from("../aria2/aria2c.exe"),
             PathBuf::from("../../aria2/aria2c.exe"),
@@ -59,85 +362,438 @@ impl Aria2Client {
 
         for path in &possible_paths {
             if path.exists() {
-                return Some(path.clone());
+                return Ok(path.clone());
             }
         }
 
         if let Ok(output) = Command::new("aria2c").arg("--version").output() {
             if output.status.success() {
-                return Some(PathBuf::from("aria2c"));
+                return Ok(PathBuf::from("aria2c"));
             }
         }
 
-        None
+        let mut searched_paths = possible_paths.to_vec();
+        searched_paths.push(PathBuf::from("aria2c"));
+        Err(AppError::BinaryNotFound { searched_paths })
     }
 
-    pub async fn start_daemon(&self) -> Result<(), String> {
-        if self.is_running().await {
-            return Ok(());
+    /// Downloads the prebuilt aria2c for this OS/arch into the config dir, where
+    /// `get_aria2_path` looks first. The binary is only written once its checksum matches.
+    pub async fn install_binary() -> Result<PathBuf, AppError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(300))
+            .build()
+            .map_err(|e| AppError::network(format!("Failed to create HTTP client: {}", e)))?;
+
+        let target = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+
+        let manifest: HashMap<String, Aria2BinaryAsset> = client
+            .get(ARIA2_BINARY_MANIFEST_URL)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::network(format!("Failed to fetch aria2 manifest: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::invalid_response(format!("Failed to parse aria2 manifest: {}", e)))?;
+
+        let asset = manifest
+            .get(&target)
+            .ok_or_else(|| AppError::not_found(format!("No prebuilt aria2c available for {}", target)))?;
+
+        let bytes = client
+            .get(&asset.url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::network(format!("Failed to download aria2c: {}", e)))?
+            .bytes()
+            .await
+            .map_err(|e| AppError::network(format!("Failed to download aria2c: {}", e)))?;
+
+        let checksum = hex::encode(Sha256::digest(&bytes));
+        if !checksum.eq_ignore_ascii_case(asset.sha256.trim()) {
+            return Err(AppError::invalid_response(format!(
+                "aria2c checksum mismatch (expected {}, got {})",
+                asset.sha256, checksum
+            )));
+        }
+
+        let path = Self::installed_binary_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| AppError::io(format!("Failed to create {}: {}", parent.display(), e)))?;
         }
 
-        let aria2_path = Self::get_aria2_path().ok_or("aria2c not found")?;
+        let tmp_path = path.with_extension("download");
+        fs::write(&tmp_path, &bytes)
+            .map_err(|e| AppError::io(format!("Failed to write aria2c: {}", e)))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o755))
+                .map_err(|e| AppError::io(format!("Failed to mark aria2c executable: {}", e)))?;
+        }
 
+        fs::rename(&tmp_path, &path)
+            .map_err(|e| AppError::io(format!("Failed to install aria2c: {}", e)))?;
+
+        Ok(path)
+    }
+
+    /// The command line `start_daemon` runs aria2c with
+    fn daemon_args(&self, listen_port: u16) -> Vec<String> {
         let overall_limit = *self.max_overall_download_limit_kb_per_sec.lock().unwrap();
         let download_limit = *self.max_download_limit_kb_per_sec.lock().unwrap();
 
         let overall_limit_arg = format!("{}K", overall_limit);
         let download_limit_arg = format!("{}K", download_limit);
+        let listen_port_arg = format!("--rpc-listen-port={}", listen_port);
+        let seed_ratio = *self.seed_ratio.lock().unwrap();
+        let max_concurrent = *self.max_concurrent_downloads.lock().unwrap();
+        let overwrite_policy = *self.overwrite_policy.lock().unwrap();
 
-        let args = [
-            "--enable-rpc",
-            "--rpc-listen-all=false",
-            "--rpc-listen-port=6800",
-            "--max-concurrent-downloads=5",
-            "--max-connection-per-server=16",
-            "--split=16",
-            "--min-split-size=1M",
-            &format!("--max-overall-download-limit={}", overall_limit_arg),
-            &format!("--max-download-limit={}", download_limit_arg),
-            "--file-allocation=none",
-            "--continue=true",
-            "--auto-file-renaming=true",
-            "--allow-overwrite=false",
+        let mut args = vec![
+            "--enable-rpc".to_string(),
+            "--rpc-listen-all=false".to_string(),
+            listen_port_arg,
+            format!("--max-concurrent-downloads={}", max_concurrent),
+            "--max-connection-per-server=16".to_string(),
+            "--split=16".to_string(),
+            "--min-split-size=1M".to_string(),
+            format!("--max-overall-download-limit={}", overall_limit_arg),
+            format!("--max-download-limit={}", download_limit_arg),
+            format!("--file-allocation={}", self.get_file_allocation().as_arg()),
+            "--continue=true".to_string(),
+            format!("--auto-file-renaming={}", overwrite_policy.auto_file_renaming()),
+            format!("--allow-overwrite={}", overwrite_policy.allow_overwrite()),
+            format!("--seed-ratio={}", seed_ratio),
+            format!("--connect-timeout={}", self.connect_timeout_secs.lock().unwrap()),
+            format!("--lowest-speed-limit={}", self.lowest_speed_limit.lock().unwrap()),
         ];
 
-        let mut cmd = Command::new(&aria2_path);
-        cmd.args(&args)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null());
+        if *self.disable_ipv6.lock().unwrap() {
+            args.push("--disable-ipv6=true".to_string());
+        }
+        if let Some(servers) = self.async_dns_server.lock().unwrap().as_ref() {
+            args.push(format!("--async-dns-server={}", servers));
+        }
+        if let Some(secret) = self.rpc_secret.lock().unwrap().as_ref() {
+            args.push(format!("--rpc-secret={}", secret));
+        }
+        args.extend(self.extra_args.lock().unwrap().iter().cloned());
+        args
+    }
+
+    /// Runs aria2c with the arguments `start_daemon` would use, on a spare port and with
+    /// nothing downloading, then tries `options` as global options on it. aria2 applies
+    /// those per download, so a bad value would otherwise only fail once a download is
+    /// added. Session arguments are left out so the real session file isn't touched.
+    pub async fn validate_config(&self, options: &[(&str, String)]) -> Result<Aria2ConfigCheck, AppError> {
+        if self.is_remote() {
+            return Err(AppError::invalid_input(
+                "aria2 runs on another machine, so its command line can't be checked from here",
+            ));
+        }
+        let aria2_path = Self::get_aria2_path()?;
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .map_err(|e| AppError::io(format!("No free port to check aria2 on: {}", e)))?
+            .port();
+
+        let mut args: Vec<String> = self
+            .daemon_args(port)
+            .into_iter()
+            .filter(|arg| {
+                let flag = arg.split('=').next().unwrap_or(arg);
+                !VALIDATION_SKIPPED_ARGS.contains(&flag)
+            })
+            .collect();
+        args.push("--pause=true".to_string());
+        args.push("--dry-run=true".to_string());
 
+        let mut cmd = Command::new(&aria2_path);
+        cmd.args(&args).stdout(Stdio::piped()).stderr(Stdio::piped());
         #[cfg(target_os = "windows")]
         {
             use std::os::windows::process::CommandExt;
             cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
         }
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| AppError::io(format!("Failed to start aria2c: {}", e)))?;
+
+        let probe = Aria2Client::new(&format!("http://127.0.0.1:{}/jsonrpc", port), 0, 0);
+        probe.set_rpc_endpoint(&probe.get_rpc_url(), self.rpc_secret.lock().unwrap().clone());
+
+        let timeout = *self.start_up_timeout.lock().unwrap();
+        let start = std::time::Instant::now();
+        let mut check = Aria2ConfigCheck {
+            args: args
+                .iter()
+                .map(|arg| match arg.starts_with("--rpc-secret=") {
+                    true => "--rpc-secret=***".to_string(),
+                    false => arg.clone(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+        loop {
+            if let Ok(version) = probe.get_version().await {
+                check.version = Some(version.version);
+                break;
+            }
+            if let Ok(Some(status)) = child.try_wait() {
+                let output = child.wait_with_output()?;
+                check.problems = Self::startup_problems(&output.stdout, &output.stderr);
+                if check.problems.is_empty() {
+                    check.problems.push(format!("aria2c exited during startup ({})", status));
+                }
+                return Ok(check);
+            }
+            if start.elapsed() >= timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                check.problems.push(format!("aria2c didn't start within {} seconds", timeout.as_secs()));
+                return Ok(check);
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+
+        for (key, value) in options {
+            if let Err(e) = probe.change_global_option(key, value).await {
+                check.problems.push(format!("{}={}: {}", key, value, e));
+            }
+        }
+
+        let _ = probe.call::<String>("forceShutdown", vec![]).await;
+        let _ = child.kill();
+        let _ = child.wait();
+        check.valid = check.problems.is_empty();
+        Ok(check)
+    }
+
+    /// What aria2c printed before giving up, minus blank lines
+    fn startup_problems(stdout: &[u8], stderr: &[u8]) -> Vec<String> {
+        [stdout, stderr]
+            .iter()
+            .flat_map(|output| String::from_utf8_lossy(output).lines().map(str::to_string).collect::<Vec<_>>())
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    pub async fn start_daemon(&self) -> Result<(), AppError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        self.start_daemon_locked().await
+    }
+
+    async fn start_daemon_locked(&self) -> Result<(), AppError> {
+        if self.is_running().await {
+            return Ok(());
+        }
+
+        if self.is_remote() {
+            return Err(AppError::network(format!(
+                "Remote aria2 at {} is not reachable",
+                self.get_rpc_url()
+            )));
+        }
+
+        let aria2_path = Self::get_aria2_path()?;
+
+        let args = self.daemon_args(self.rpc_listen_port());
+
+        // A daemon that outlives the app can't write to our pipes once we're gone, so its
+        // output isn't captured and it gets a process group of its own
+        let detach = *self.keep_on_exit.lock().unwrap();
+        let output = || if detach { Stdio::null() } else { Stdio::piped() };
+        let mut cmd = Command::new(&aria2_path);
+        cmd.args(&args).stdout(output()).stderr(output());
+
+        #[cfg(unix)]
+        if detach {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
 
-        let child = cmd.spawn()
-            .map_err(|e| format!("Failed to start aria2c: {}", e))?;
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            if detach {
+                cmd.creation_flags(0x00000008 | 0x00000200); // DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP
+            } else {
+                cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            }
+        }
 
+        let mut child = cmd.spawn()
+            .map_err(|e| AppError::io(format!("Failed to start aria2c: {}", e)))?;
+
+        self.logs.clear();
+        {
+            let mut readers = self.log_readers.lock().unwrap();
+            if let Some(stdout) = child.stdout.take() {
+                readers.push(spawn_log_reader(stdout, Arc::clone(&self.logs)));
+            }
+            if let Some(stderr) = child.stderr.take() {
+                readers.push(spawn_log_reader(stderr, Arc::clone(&self.logs)));
+            }
+        }
         *self.aria2_process.lock().unwrap() = Some(child);
 
+        let timeout = *self.start_up_timeout.lock().unwrap();
         let start = std::time::Instant::now();
-        while start.elapsed() < ARIA2_START_UP_TIMEOUT {
+        while start.elapsed() < timeout {
             if self.is_running().await {
                 return Ok(());
             }
+
+            // Don't sit out the whole timeout if aria2c already gave up (bad flag, port in use...)
+            let exited = {
+                let mut process = self.aria2_process.lock().unwrap();
+                match process.as_mut().map(|child| child.try_wait()) {
+                    Some(Ok(Some(status))) => {
+                        process.take();
+                        Some(status)
+                    }
+                    _ => None,
+                }
+            };
+            if let Some(status) = exited {
+                self.join_log_readers();
+                return Err(self.start_up_error(format!("aria2c exited during startup ({})", status)));
+            }
+
             tokio::time::sleep(Duration::from_millis(200)).await;
         }
 
-        Err("aria2c failed to start within timeout".to_string())
+        self.kill_process();
+
+        Err(self.start_up_error(format!(
+            "aria2c failed to start within {} seconds",
+            timeout.as_secs()
+        )))
+    }
+
+    fn start_up_error(&self, message: String) -> AppError {
+        let output = self.logs.tail(START_UP_ERROR_LOG_LINES).join("\n");
+        let output = output.trim();
+        if output.is_empty() {
+            message.into()
+        } else {
+            format!("{}: {}", message, output).into()
+        }
     }
 
-    pub async fn stop_daemon(&self) -> Result<(), String> {
+    /// Kills the spawned aria2c, if any, and waits for its log readers to finish
+    fn kill_process(&self) {
         if let Some(mut child) = self.aria2_process.lock().unwrap().take() {
             let _ = child.kill();
             let _ = child.wait();
         }
+        self.join_log_readers();
+    }
+
+    /// Stops a daemon we spawned as the app quits, unless it's to keep running; the next
+    /// launch then finds it on the RPC port and uses it instead of spawning another
+    pub fn release_on_exit(&self) {
+        if *self.keep_on_exit.lock().unwrap() {
+            self.aria2_process.lock().unwrap().take();
+            return;
+        }
+        self.kill_process();
+    }
+
+    fn join_log_readers(&self) {
+        let readers: Vec<_> = self.log_readers.lock().unwrap().drain(..).collect();
+        for reader in readers {
+            let _ = reader.join();
+        }
+    }
+
+    pub async fn stop_daemon(&self) -> Result<(), AppError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        self.stop_daemon_locked().await
+    }
+
+    async fn stop_daemon_locked(&self) -> Result<(), AppError> {
+        if self.is_remote() {
+            // Never shut down a daemon we don't own
+            return Ok(());
+        }
+
+        self.kill_process();
 
         let _ = self.shutdown().await;
         Ok(())
     }
 
+    /// Restarts a local daemon and adds back what was queued under the same GIDs, so
+    /// downloads the user paused stay paused and the rest carry on. Prefer changing
+    /// options live; this is for settings aria2 only reads at startup.
+    pub async fn restart_daemon(&self) -> Result<(), AppError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        let queue = if self.is_running().await {
+            self.snapshot_queue().await?
+        } else {
+            Vec::new()
+        };
+
+        self.stop_daemon_locked().await?;
+        self.start_daemon_locked().await?;
+        self.restore_queue(queue).await;
+        Ok(())
+    }
+
+    async fn snapshot_queue(&self) -> Result<Vec<QueuedDownload>, AppError> {
+        let mut statuses = self.tell_active().await?;
+        statuses.extend(self.tell_all_waiting().await?);
+
+        let mut queue = Vec::new();
+        for status in statuses {
+            let options = match self.call("getOption", vec![serde_json::json!(status.gid)]).await {
+                Ok(serde_json::Value::Object(options)) => options,
+                _ => serde_json::Map::new(),
+            };
+            queue.extend(QueuedDownload::from_status(&status, options));
+        }
+        Ok(queue)
+    }
+
+    /// A download the new daemon already knows (from its session file) only gets its
+    /// paused state put back; the others are added again
+    async fn restore_queue(&self, queue: Vec<QueuedDownload>) {
+        for download in queue {
+            let result = match self.get_status(&download.gid).await {
+                Ok(status) => {
+                    let paused = DownloadStatus::from(status.status.as_str()) == DownloadStatus::Paused;
+                    if download.paused && !paused {
+                        self.pause(&download.gid).await.map(|_| ())
+                    } else {
+                        Ok(())
+                    }
+                }
+                Err(_) => self
+                    .call::<String>(
+                        "addUri",
+                        vec![serde_json::json!(download.uris), download.add_options()],
+                    )
+                    .await
+                    .map(|_| ()),
+            };
+            if let Err(e) = result {
+                log::warn!("Failed to restore download {} after restart: {}", download.gid, e);
+            }
+        }
+    }
+
+    /// True while a daemon spawned by `start_daemon` is still ours to manage
+    pub fn owns_daemon(&self) -> bool {
+        self.aria2_process.lock().unwrap().is_some()
+    }
+
     pub async fn is_running(&self) -> bool {
         self.get_version().await.is_ok()
     }
@@ -146,52 +802,215 @@ impl Aria2Client {
         &self,
         method: &str,
         params: Vec<serde_json::Value>,
-    ) -> Result<T, String> {
-        let request = Aria2RpcRequest::new(method, params);
+    ) -> Result<T, AppError> {
+        self.call_with_timeout(method, params, None).await
+    }
+
+    /// Like `call`, but `timeout` overrides the client's 30s default for this request
+    async fn call_with_timeout<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Vec<serde_json::Value>,
+        timeout: Option<Duration>,
+    ) -> Result<T, AppError> {
+        let request = Aria2RpcRequest::new(method, self.with_secret(params));
+        self.send(request, timeout).await
+    }
+
+    fn with_secret(&self, mut params: Vec<serde_json::Value>) -> Vec<serde_json::Value> {
+        if let Some(secret) = self.rpc_secret.lock().unwrap().as_ref() {
+            params.insert(0, serde_json::json!(format!("token:{}", secret)));
+        }
+        params
+    }
+
+    #[tracing::instrument(skip(self, request, timeout), fields(method = %request.method))]
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: Aria2RpcRequest,
+        timeout: Option<Duration>,
+    ) -> Result<T, AppError> {
+        let rpc_url = self.get_rpc_url();
 
-        let response = self
-            .client
-            .post(&self.rpc_url)
-            .json(&request)
+        let mut http_request = self.client.post(&rpc_url).json(&request);
+        if let Some(timeout) = timeout {
+            http_request = http_request.timeout(timeout);
+        }
+
+        let response = http_request
             .send()
             .await
-            .map_err(|e| format!("RPC request failed: {}", e))?;
+            .map_err(|e| AppError::network(format!("RPC request failed: {}", e)))?;
 
         let rpc_response: Aria2RpcResponse<T> = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse RPC response: {}", e))?;
+            .map_err(|e| AppError::invalid_response(format!("Failed to parse RPC response: {}", e)))?;
 
         if let Some(error) = rpc_response.error {
-            return Err(format!("aria2 error: {} (code: {})", error.message, error.code));
+            tracing::debug!("aria2 returned error {}: {}", error.code, error.message);
+            return Err(AppError::Aria2Rpc {
+                code: error.code,
+                message: error.message,
+            });
         }
 
-        rpc_response.result.ok_or("Empty response from aria2".to_string())
+        rpc_response
+            .result
+            .ok_or_else(|| AppError::invalid_response("Empty response from aria2"))
+    }
+
+    /// Runs each `(method, params)` of `calls` in a single `system.multicall` round trip,
+    /// returning each call's result in order
+    pub async fn multicall(
+        &self,
+        calls: Vec<(&str, Vec<serde_json::Value>)>,
+    ) -> Result<Vec<Result<serde_json::Value, AppError>>, AppError> {
+        let calls: Vec<_> = calls
+            .into_iter()
+            .map(|(method, params)| {
+                serde_json::json!({
+                    "methodName": format!("aria2.{}", method),
+                    "params": self.with_secret(params),
+                })
+            })
+            .collect();
+        let request = Aria2RpcRequest::system("multicall", vec![serde_json::json!(calls)]);
+
+        // Each entry is `[result]` on success or a `{code, message}` fault
+        let results: Vec<serde_json::Value> = self.send(request, None).await?;
+        Ok(results
+            .into_iter()
+            .map(|result| match result {
+                serde_json::Value::Array(mut values) if !values.is_empty() => Ok(values.swap_remove(0)),
+                fault => Err(serde_json::from_value::<Aria2RpcError>(fault)
+                    .map(|error| AppError::Aria2Rpc {
+                        code: error.code,
+                        message: error.message,
+                    })
+                    .unwrap_or_else(|_| AppError::invalid_response("Malformed multicall result"))),
+            })
+            .collect())
     }
 
-    pub async fn get_version(&self) -> Result<serde_json::Value, String> {
+    pub async fn get_version(&self) -> Result<Aria2Version, AppError> {
         self.call("getVersion", vec![]).await
     }
 
+    /// Times a `getVersion` round trip, giving up quickly on a dead daemon
+    pub async fn ping(&self) -> Aria2Ping {
+        let start = std::time::Instant::now();
+        let result: Result<Aria2Version, AppError> = self
+            .call_with_timeout("getVersion", vec![], Some(PING_TIMEOUT))
+            .await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(version) => Aria2Ping {
+                reachable: true,
+                latency_ms,
+                version: Some(version.version),
+            },
+            Err(_) => Aria2Ping {
+                reachable: false,
+                latency_ms,
+                version: None,
+            },
+        }
+    }
+
+    /// Adds one download; extra `uris` are mirrors of the same file that aria2 fails over between
     pub async fn add_uri(
         &self,
-        url: &str,
+        uris: &[String],
         options: Option<Aria2Options>,
-    ) -> Result<String, String> {
-        let uris = serde_json::json!([url]);
+    ) -> Result<String, AppError> {
+        let uris = serde_json::json!(uris);
         let opts = options.unwrap_or_default();
         let opts_json = serde_json::to_value(&opts).unwrap_or(serde_json::json!({}));
 
         self.call("addUri", vec![uris, opts_json]).await
     }
 
-    pub async fn get_status(&self, gid: &str) -> Result<Aria2Status, String> {
+    pub async fn get_status(&self, gid: &str) -> Result<Aria2Status, AppError> {
         self.call("tellStatus", vec![serde_json::json!(gid)]).await
     }
 
-    pub async fn get_download_info(&self, gid: &str) -> Result<DownloadInfo, String> {
+    pub async fn get_download_files(&self, gid: &str) -> Result<Vec<DownloadFile>, AppError> {
+        let status = self.get_status(gid).await?;
+        Ok(status
+            .files
+            .unwrap_or_default()
+            .into_iter()
+            .map(DownloadFile::from)
+            .collect())
+    }
+
+    /// The servers each file is coming from. aria2 only reports live servers for active
+    /// downloads; for the rest this lists the URIs it last used, with no speed.
+    pub async fn get_download_servers(&self, gid: &str) -> Result<Vec<DownloadServer>, AppError> {
+        let status = self.get_status(gid).await?;
+        if status.status == "active" {
+            let files: Vec<Aria2FileServers> =
+                self.call("getServers", vec![serde_json::json!(gid)]).await?;
+            return Ok(files
+                .into_iter()
+                .flat_map(|file| {
+                    let index = file.index.parse().unwrap_or(0);
+                    file.servers.into_iter().map(move |server| {
+                        let speed = server.download_speed.parse().unwrap_or(0);
+                        DownloadServer::new(index, server.uri, server.current_uri, speed)
+                    })
+                })
+                .collect());
+        }
+
+        Ok(status
+            .files
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|file| {
+                let index = file.index.parse().unwrap_or(0);
+                file.uris
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|uri| uri.status == "used")
+                    .map(move |uri| DownloadServer::new(index, uri.uri.clone(), uri.uri, 0))
+            })
+            .collect())
+    }
+
+    /// Limits a torrent to the files at these 1-based `indices`; aria2 ignores
+    /// `select-file` for plain HTTP downloads
+    pub async fn select_files(&self, gid: &str, indices: &[u32]) -> Result<String, AppError> {
+        if indices.is_empty() {
+            return Err(AppError::invalid_input("Select at least one file"));
+        }
+
+        let files = self.get_download_files(gid).await?;
+        if let Some(missing) = indices.iter().find(|i| !files.iter().any(|f| f.index == **i)) {
+            return Err(AppError::invalid_input(format!(
+                "Download {} has no file with index {}",
+                gid, missing
+            )));
+        }
+
+        let selection = indices
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        self.change_option(gid, "select-file", &selection).await
+    }
+
+    pub async fn get_download_info(&self, gid: &str) -> Result<DownloadInfo, AppError> {
         let status = self.get_status(gid).await?;
+        Ok(self.download_info(status))
+    }
 
+    /// `tellActive`/`tellWaiting`/`tellStopped` return full statuses, so lists can be
+    /// converted directly instead of asking for each GID again
+    fn download_info(&self, status: Aria2Status) -> DownloadInfo {
         let total_size: u64 = status.total_length.as_ref()
             .and_then(|s| s.parse().ok())
             .unwrap_or(0);
@@ -202,12 +1021,47 @@ impl Aria2Client {
             .and_then(|s| s.parse().ok())
             .unwrap_or(0);
 
+        let download_status = DownloadStatus::from(status.status.as_str());
+        let average_speed = self.record_speed_sample(&status.gid, &download_status, speed);
+
+        let connections: u32 = status.connections.as_ref()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+
         let progress = if total_size > 0 {
             (downloaded as f64 / total_size as f64) * 100.0
         } else {
             0.0
         };
 
+        let eta_seconds = if speed > 0 && total_size > 0 {
+            Some(total_size.saturating_sub(downloaded) / speed)
+        } else {
+            None
+        };
+
+        // Upload stats only mean something for BitTorrent downloads
+        let (uploaded, upload_speed, seeders) = if status.bittorrent.is_some() {
+            (
+                status.upload_length.as_ref().and_then(|s| s.parse().ok()).unwrap_or(0),
+                status.upload_speed.as_ref().and_then(|s| s.parse().ok()).unwrap_or(0),
+                status.num_seeders.as_ref().and_then(|s| s.parse().ok()).unwrap_or(0),
+            )
+        } else {
+            (0, 0, 0)
+        };
+
+        let source_uri = status.files.as_ref()
+            .and_then(|files| files.first())
+            .and_then(|f| f.uris.as_ref())
+            .and_then(|uris| uris.first())
+            .map(|u| u.uri.clone());
+
+        let path = status.files.as_ref()
+            .and_then(|files| files.first())
+            .map(|f| f.path.clone())
+            .filter(|p| !p.is_empty());
+
         let filename = status.files
             .and_then(|files| files.first().cloned())
             .map(|f| {
@@ -218,73 +1072,212 @@ impl Aria2Client {
             })
             .unwrap_or_else(|| "unknown".to_string());
 
-        Ok(DownloadInfo {
+        let origin = self.get_origin(&status.gid).unwrap_or_default();
+
+        DownloadInfo {
             gid: status.gid,
             filename,
+            path,
             total_size,
             downloaded,
             speed,
+            average_speed,
             progress,
-            status: DownloadStatus::from(status.status.as_str()),
+            status: download_status,
             error_message: status.error_message,
-        })
+            eta_seconds,
+            connections,
+            uploaded,
+            upload_speed,
+            seeders,
+            source_uri,
+            created_time: origin.added_at,
+            completed_time: origin.completed_at,
+        }
     }
 
-    pub async fn pause(&self, gid: &str) -> Result<String, String> {
+    pub async fn pause(&self, gid: &str) -> Result<String, AppError> {
         self.call("pause", vec![serde_json::json!(gid)]).await
     }
 
-    pub async fn unpause(&self, gid: &str) -> Result<String, String> {
+    pub async fn unpause(&self, gid: &str) -> Result<String, AppError> {
         self.call("unpause", vec![serde_json::json!(gid)]).await
     }
 
-    pub async fn remove(&self, gid: &str) -> Result<String, String> {
+    pub async fn remove(&self, gid: &str) -> Result<String, AppError> {
         self.call("remove", vec![serde_json::json!(gid)]).await
     }
 
-    pub async fn force_remove(&self, gid: &str) -> Result<String, String> {
+    pub async fn force_remove(&self, gid: &str) -> Result<String, AppError> {
         self.call("forceRemove", vec![serde_json::json!(gid)]).await
     }
 
-    pub async fn pause_all(&self) -> Result<String, String> {
+    /// Runs `method` (`pause`, `unpause`, `forceRemove`, ...) on every member of a group
+    /// at once. Returns how many it succeeded for; a member it doesn't apply to, like a
+    /// finished one being paused, is skipped.
+    pub async fn group_action(&self, group_id: &str, method: &str) -> Result<usize, AppError> {
+        let gids = self.group_gids(group_id);
+        if gids.is_empty() {
+            return Err(AppError::not_found("No downloads in this group"));
+        }
+        let calls = gids.iter().map(|gid| (method, vec![serde_json::json!(gid)])).collect();
+        let results = self.multicall(calls).await?;
+        Ok(results.iter().filter(|result| result.is_ok()).count())
+    }
+
+    pub async fn get_group_progress(&self, group_id: &str) -> Result<GroupProgress, AppError> {
+        let gids = self.group_gids(group_id);
+        if gids.is_empty() {
+            return Err(AppError::not_found("No downloads in this group"));
+        }
+        let calls = gids.iter().map(|gid| ("tellStatus", vec![serde_json::json!(gid)])).collect();
+
+        // Members whose results were purged from aria2 come back as faults and are left out
+        let mut downloads: Vec<DownloadInfo> = self
+            .multicall(calls)
+            .await?
+            .into_iter()
+            .filter_map(|result| result.ok())
+            .filter_map(|status| serde_json::from_value::<Aria2Status>(status).ok())
+            .map(|status| self.download_info(status))
+            .collect();
+        downloads.sort_by(|a, b| a.filename.cmp(&b.filename));
+
+        let downloaded = downloads.iter().map(|d| d.downloaded).sum();
+        let total_size: u64 = downloads.iter().map(|d| d.total_size).sum();
+        let progress = if total_size > 0 {
+            (downloaded as f64 / total_size as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        Ok(GroupProgress {
+            group_id: group_id.to_string(),
+            downloaded,
+            total_size,
+            progress,
+            downloads,
+            current_gid: None,
+        })
+    }
+
+    pub async fn pause_all(&self) -> Result<String, AppError> {
         self.call("pauseAll", vec![]).await
     }
 
-    pub async fn unpause_all(&self) -> Result<String, String> {
+    pub async fn unpause_all(&self) -> Result<String, AppError> {
         self.call("unpauseAll", vec![]).await
     }
 
-    pub async fn get_global_stat(&self) -> Result<Aria2GlobalStat, String> {
+    /// Writes aria2's session file now; fails unless aria2 runs with `--save-session`
+    pub async fn save_session(&self) -> Result<String, AppError> {
+        self.call("saveSession", vec![]).await
+    }
+
+    pub async fn get_global_stat(&self) -> Result<Aria2GlobalStat, AppError> {
         self.call("getGlobalStat", vec![]).await
     }
 
-    pub async fn tell_active(&self) -> Result<Vec<Aria2Status>, String> {
+    pub async fn get_overall_progress(&self) -> Result<OverallProgress, AppError> {
+        let stats = GlobalStats::from(self.get_global_stat().await?);
+        let active = self.tell_active().await?;
+        Ok(OverallProgress::from_active(&active, stats.download_speed))
+    }
+
+    pub async fn tell_active(&self) -> Result<Vec<Aria2Status>, AppError> {
         self.call("tellActive", vec![]).await
     }
 
-    pub async fn tell_waiting(&self, offset: i32, num: i32) -> Result<Vec<Aria2Status>, String> {
+    pub async fn tell_waiting(&self, offset: i32, num: i32) -> Result<Vec<Aria2Status>, AppError> {
         self.call("tellWaiting", vec![
             serde_json::json!(offset),
             serde_json::json!(num),
         ]).await
     }
 
-    pub async fn tell_stopped(&self, offset: i32, num: i32) -> Result<Vec<Aria2Status>, String> {
+    pub async fn tell_stopped(&self, offset: i32, num: i32) -> Result<Vec<Aria2Status>, AppError> {
         self.call("tellStopped", vec![
             serde_json::json!(offset),
             serde_json::json!(num),
         ]).await
     }
 
-    pub async fn purge_download_result(&self) -> Result<String, String> {
+    /// The whole waiting list, however long, in queue order
+    pub async fn tell_all_waiting(&self) -> Result<Vec<Aria2Status>, AppError> {
+        self.tell_all("tellWaiting").await
+    }
+
+    /// The whole stopped list, however long, oldest first
+    pub async fn tell_all_stopped(&self) -> Result<Vec<Aria2Status>, AppError> {
+        self.tell_all("tellStopped").await
+    }
+
+    /// Pages through `method` (`tellWaiting` or `tellStopped`) until a short page
+    async fn tell_all(&self, method: &str) -> Result<Vec<Aria2Status>, AppError> {
+        let mut all = Vec::new();
+        loop {
+            let page: Vec<Aria2Status> = self
+                .call(method, vec![serde_json::json!(all.len()), serde_json::json!(LIST_PAGE_SIZE)])
+                .await?;
+            let last = page.len() < LIST_PAGE_SIZE;
+            all.extend(page);
+            if last {
+                return Ok(all);
+            }
+        }
+    }
+
+    pub async fn purge_download_result(&self) -> Result<String, AppError> {
         self.call("purgeDownloadResult", vec![]).await
     }
 
-    pub async fn shutdown(&self) -> Result<String, String> {
+    pub async fn remove_download_result(&self, gid: &str) -> Result<String, AppError> {
+        let result = self.call("removeDownloadResult", vec![serde_json::json!(gid)]).await?;
+        self.forget_origin(gid);
+        Ok(result)
+    }
+
+    /// Removes finished and removed results from the stopped list, keeping errored ones
+    /// visible. Returns how many were removed.
+    pub async fn clear_completed_results(&self) -> Result<usize, AppError> {
+        self.clear_results(&[DownloadStatus::Complete, DownloadStatus::Removed]).await
+    }
+
+    /// Clears only failed downloads, leaving completed ones in the list
+    pub async fn clear_errored_results(&self) -> Result<usize, AppError> {
+        self.clear_results(&[DownloadStatus::Error]).await
+    }
+
+    /// GIDs of the stopped downloads that failed
+    pub async fn errored_gids(&self) -> Result<Vec<String>, AppError> {
+        let stopped = self.tell_stopped(0, 1000).await?;
+        Ok(stopped
+            .into_iter()
+            .filter(|entry| DownloadStatus::from(entry.status.as_str()) == DownloadStatus::Error)
+            .map(|entry| entry.gid)
+            .collect())
+    }
+
+    async fn clear_results(&self, statuses: &[DownloadStatus]) -> Result<usize, AppError> {
+        let stopped = self.tell_stopped(0, 1000).await?;
+        let mut cleared = 0;
+
+        for entry in stopped {
+            let status = DownloadStatus::from(entry.status.as_str());
+            if statuses.contains(&status) {
+                self.remove_download_result(&entry.gid).await?;
+                cleared += 1;
+            }
+        }
+
+        Ok(cleared)
+    }
+
+    pub async fn shutdown(&self) -> Result<String, AppError> {
         self.call("shutdown", vec![]).await
     }
 
-    pub async fn change_global_option(&self, key: &str, value: &str) -> Result<String, String> {
+    pub async fn change_global_option(&self, key: &str, value: &str) -> Result<String, AppError> {
         self.call(
             "changeGlobalOption",
             vec![
@@ -293,50 +1286,394 @@ impl Aria2Client {
         ).await
     }
 
-    pub async fn get_global_option(&self, key: &str) -> Result<String, String> {
+    pub async fn change_option(&self, gid: &str, key: &str, value: &str) -> Result<String, AppError> {
+        self.call(
+            "changeOption",
+            vec![
+                serde_json::json!(gid),
+                serde_json::json!({ key: value }),
+            ],
+        ).await
+    }
+
+    /// Moves a waiting download within aria2's queue; `how` is `POS_SET`, `POS_CUR` or
+    /// `POS_END`. Returns the new position.
+    pub async fn change_position(&self, gid: &str, pos: i64, how: &str) -> Result<i64, AppError> {
+        self.call(
+            "changePosition",
+            vec![
+                serde_json::json!(gid),
+                serde_json::json!(pos),
+                serde_json::json!(how),
+            ],
+        ).await
+    }
+
+    pub async fn get_global_option(&self, key: &str) -> Result<String, AppError> {
         let result: serde_json::Value = self.call("getGlobalOption", vec![]).await?;
         result.get(key)
             .and_then(|v| v.as_str())
             .map(|s| s.to_string())
-            .ok_or(format!("Option {} not found", key))
+            .ok_or_else(|| AppError::not_found(format!("Option {} not found", key)))
     }
 
-    pub async fn get_all_downloads(&self) -> Result<Vec<DownloadInfo>, String> {
-        let mut all_downloads = Vec::new();
-
-        if let Ok(active) = self.tell_active().await {
-            for status in active {
-                if let Ok(info) = self.get_download_info(&status.gid).await {
-                    all_downloads.push(info);
-                }
+    /// Every download in `status`, however many there are
+    pub async fn get_downloads_by_status(&self, status: DownloadStatus) -> Result<Vec<DownloadInfo>, AppError> {
+        // tellWaiting also returns paused entries and tellStopped mixes complete/error/removed,
+        // so only the matching list is fetched and then narrowed by its reported status
+        let statuses = match status {
+            DownloadStatus::Active => self.tell_active().await?,
+            DownloadStatus::Waiting | DownloadStatus::Paused => self.tell_all_waiting().await?,
+            DownloadStatus::Complete | DownloadStatus::Error | DownloadStatus::Removed => {
+                self.tell_all_stopped().await?
             }
-        }
+        };
 
-        if let Ok(waiting) = self.tell_waiting(0, 100).await {
-            for status in waiting {
-                if let Ok(info) = self.get_download_info(&status.gid).await {
-                    all_downloads.push(info);
-                }
-            }
+        Ok(statuses
+            .into_iter()
+            .filter(|entry| DownloadStatus::from(entry.status.as_str()) == status)
+            .map(|entry| self.download_info(entry))
+            .collect())
+    }
+
+    /// Active, then waiting, then stopped downloads. Without a `limit`, at most 100 waiting
+    /// and 100 stopped ones are included; with one, at most `limit` in total. All three lists
+    /// come back in one multicall, so waiting and stopped are each fetched up to the full
+    /// `limit` and trimmed here. Fails if aria2 can't be reached or any list fails, so an
+    /// empty result really means there are no downloads.
+    pub async fn get_all_downloads(&self, limit: Option<usize>) -> Result<Vec<DownloadInfo>, AppError> {
+        let num = limit.unwrap_or(100).min(i32::MAX as usize) as i32;
+        if num == 0 {
+            return Ok(Vec::new());
         }
 
-        if let Ok(stopped) = self.tell_stopped(0, 100).await {
-            for status in stopped {
-                if let Ok(info) = self.get_download_info(&status.gid).await {
-                    all_downloads.push(info);
-                }
-            }
+        let results = self
+            .multicall(vec![
+                ("tellActive", vec![]),
+                ("tellWaiting", vec![serde_json::json!(0), serde_json::json!(num)]),
+                ("tellStopped", vec![serde_json::json!(0), serde_json::json!(num)]),
+            ])
+            .await?;
+        let mut statuses: Vec<Aria2Status> = Vec::new();
+        for result in results {
+            let list = serde_json::from_value::<Vec<Aria2Status>>(result?)
+                .map_err(|e| AppError::invalid_response(format!("Failed to parse download list: {}", e)))?;
+            statuses.extend(list);
+        }
+        if let Some(limit) = limit {
+            statuses.truncate(limit);
         }
 
-        Ok(all_downloads)
+        Ok(statuses.into_iter().map(|status| self.download_info(status)).collect())
+    }
+
+    /// One page of the waiting (queued or paused) list, in queue order
+    pub async fn get_waiting_page(&self, offset: u32, num: u32) -> Result<DownloadPage, AppError> {
+        let statuses = self.tell_waiting(offset as i32, num as i32).await?;
+        let total = GlobalStats::from(self.get_global_stat().await?).num_waiting;
+        Ok(self.download_page(statuses, offset, total))
+    }
+
+    /// One page of the stopped (complete, failed or removed) list, oldest first
+    pub async fn get_stopped_page(&self, offset: u32, num: u32) -> Result<DownloadPage, AppError> {
+        let statuses = self.tell_stopped(offset as i32, num as i32).await?;
+        let total = GlobalStats::from(self.get_global_stat().await?).num_stopped;
+        Ok(self.download_page(statuses, offset, total))
+    }
+
+    fn download_page(&self, statuses: Vec<Aria2Status>, offset: u32, total: u32) -> DownloadPage {
+        DownloadPage {
+            downloads: statuses.into_iter().map(|status| self.download_info(status)).collect(),
+            offset,
+            total,
+        }
     }
 }
 
 impl Drop for Aria2Client {
     fn drop(&mut self) {
-        if let Some(mut child) = self.aria2_process.lock().unwrap().take() {
-            let _ = child.kill();
-            let _ = child.wait();
+        self.release_on_exit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_speed_resets_after_pause() {
+        let client = Aria2Client::default();
+        let active = DownloadStatus::Active;
+
+        assert_eq!(client.record_speed_sample("a", &active, 100), 100);
+        assert_eq!(client.record_speed_sample("a", &active, 300), 200);
+
+        client.record_speed_sample("a", &DownloadStatus::Paused, 0);
+        assert_eq!(client.record_speed_sample("a", &active, 500), 500);
+
+        for _ in 0..SPEED_SAMPLE_WINDOW {
+            client.record_speed_sample("a", &active, 10);
+        }
+        assert_eq!(client.record_speed_sample("a", &active, 10), 10);
+    }
+
+    #[test]
+    fn test_share_limit_follows_overall_limit() {
+        assert_eq!(Aria2Client::share_limit_kb(1000, 0, 50), 500);
+        assert_eq!(Aria2Client::share_limit_kb(1000, 0, 100), 1000);
+        // Never rounded down to 0, which would mean unlimited
+        assert_eq!(Aria2Client::share_limit_kb(10, 0, 1), 1);
+        // The per-download limit still caps it
+        assert_eq!(Aria2Client::share_limit_kb(1000, 300, 50), 300);
+        // A share of unlimited is unlimited, up to the per-download limit
+        assert_eq!(Aria2Client::share_limit_kb(0, 0, 50), 0);
+        assert_eq!(Aria2Client::share_limit_kb(0, 300, 50), 300);
+    }
+
+    /// Serves JSON-RPC over HTTP, answering each call with what `handler`'s future
+    /// resolves to
+    async fn mock_server<F, Fut>(handler: F) -> String
+    where
+        F: Fn(&serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = serde_json::Value> + Send + 'static,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let handler = Arc::new(handler);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc_url = format!("http://{}/jsonrpc", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    // Headers, then as much body as Content-Length says
+                    let body = loop {
+                        let n = socket.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&request);
+                        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                            let length = head
+                                .lines()
+                                .find_map(|line| {
+                                    let line = line.to_ascii_lowercase();
+                                    line.strip_prefix("content-length:")?.trim().parse::<usize>().ok()
+                                })
+                                .unwrap_or(0);
+                            if body.len() >= length || n == 0 {
+                                break body.to_string();
+                            }
+                        }
+                    };
+                    let call: serde_json::Value = serde_json::from_str(&body).unwrap();
+                    let result = handler(&call).await;
+                    let body = serde_json::json!({ "jsonrpc": "2.0", "id": call["id"], "result": result }).to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        rpc_url
+    }
+
+    /// Answers every JSON-RPC call with its first param, or "OK" without one.
+    /// `tellActive` waits for `release` to answer that nothing is active.
+    async fn mock_rpc_server(release: Arc<tokio::sync::Notify>) -> String {
+        mock_server(move |call| {
+            let held = (call["method"] == "aria2.tellActive").then(|| release.clone());
+            let result = match held {
+                Some(_) => serde_json::json!([]),
+                None => call["params"].get(0).cloned().unwrap_or(serde_json::json!("OK")),
+            };
+            async move {
+                if let Some(release) = held {
+                    release.notified().await;
+                }
+                result
+            }
+        })
+        .await
+    }
+
+    /// A daemon holding `active`, `waiting` and `stopped` downloads that answers the
+    /// `tell*` calls, alone or in a multicall, after `latency` per HTTP request.
+    /// Returns the RPC url and a count of requests served.
+    async fn mock_download_list_server(
+        latency: Duration,
+        active: usize,
+        waiting: usize,
+        stopped: usize,
+    ) -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        let list = |status: &str, count: usize, first: usize| -> Vec<serde_json::Value> {
+            (first..first + count)
+                .map(|i| serde_json::json!({ "gid": format!("{:016x}", i), "status": status }))
+                .collect()
+        };
+        let active = list("active", active, 0);
+        let waiting = list("waiting", waiting, active.len());
+        let stopped = list("complete", stopped, active.len() + waiting.len());
+        let answer = move |method: &serde_json::Value, params: &serde_json::Value| {
+            let page = |items: &[serde_json::Value]| {
+                let offset = params[0].as_u64().unwrap_or(0) as usize;
+                let num = params[1].as_u64().unwrap_or(0) as usize;
+                serde_json::json!(items.iter().skip(offset).take(num).collect::<Vec<_>>())
+            };
+            match method.as_str() {
+                Some("aria2.tellActive") => serde_json::json!(active),
+                Some("aria2.tellWaiting") => page(&waiting),
+                Some("aria2.tellStopped") => page(&stopped),
+                _ => serde_json::json!("OK"),
+            }
+        };
+
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = requests.clone();
+        let url = mock_server(move |call| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let result = if call["method"] == "system.multicall" {
+                let calls = call["params"][0].as_array().cloned().unwrap_or_default();
+                serde_json::json!(calls
+                    .iter()
+                    .map(|c| serde_json::json!([answer(&c["methodName"], &c["params"])]))
+                    .collect::<Vec<_>>())
+            } else {
+                answer(&call["method"], &call["params"])
+            };
+            async move {
+                tokio::time::sleep(latency).await;
+                result
+            }
+        })
+        .await;
+        (url, requests)
+    }
+
+    /// What `get_all_downloads` returned when it made one call per list
+    async fn get_all_downloads_sequentially(client: &Aria2Client, limit: Option<usize>) -> Vec<DownloadInfo> {
+        let room = |so_far: usize| limit.map_or(100, |limit| limit.saturating_sub(so_far)) as i32;
+
+        let mut statuses = client.tell_active().await.unwrap_or_default();
+        let num = room(statuses.len());
+        if num > 0 {
+            statuses.extend(client.tell_waiting(0, num).await.unwrap_or_default());
+        }
+        let num = room(statuses.len());
+        if num > 0 {
+            statuses.extend(client.tell_stopped(0, num).await.unwrap_or_default());
         }
+        if let Some(limit) = limit {
+            statuses.truncate(limit);
+        }
+        statuses.into_iter().map(|status| client.download_info(status)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_get_all_downloads_batches_into_one_request() {
+        let (rpc_url, requests) = mock_download_list_server(Duration::ZERO, 20, 150, 30).await;
+        let client = Aria2Client::new(&rpc_url, 0, 0);
+        let gids = |downloads: Vec<DownloadInfo>| downloads.into_iter().map(|d| d.gid).collect::<Vec<_>>();
+
+        for limit in [None, Some(0), Some(10), Some(50), Some(190), Some(1000)] {
+            let before = requests.load(std::sync::atomic::Ordering::SeqCst);
+            let batched = client.get_all_downloads(limit).await.unwrap();
+            assert!(requests.load(std::sync::atomic::Ordering::SeqCst) - before <= 1);
+            assert_eq!(gids(batched), gids(get_all_downloads_sequentially(&client, limit).await), "limit {:?}", limit);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tell_all_waiting_pages_past_one_page() {
+        let (rpc_url, requests) = mock_download_list_server(Duration::ZERO, 0, 2 * LIST_PAGE_SIZE + 5, 0).await;
+        let client = Aria2Client::new(&rpc_url, 0, 0);
+
+        let waiting = client.tell_all_waiting().await.unwrap();
+        assert_eq!(waiting.len(), 2 * LIST_PAGE_SIZE + 5);
+        assert_eq!(waiting[LIST_PAGE_SIZE].gid, format!("{:016x}", LIST_PAGE_SIZE));
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_downloads_by_status_include_the_newest() {
+        let (rpc_url, _) = mock_download_list_server(Duration::ZERO, 0, 0, LIST_PAGE_SIZE + 50).await;
+        let client = Aria2Client::new(&rpc_url, 0, 0);
+
+        let complete = client.get_downloads_by_status(DownloadStatus::Complete).await.unwrap();
+        assert_eq!(complete.len(), LIST_PAGE_SIZE + 50);
+        assert_eq!(complete.last().unwrap().gid, format!("{:016x}", LIST_PAGE_SIZE + 49));
+    }
+
+    #[tokio::test]
+    async fn test_get_all_downloads_fails_without_daemon() {
+        // A port nothing listens on any more
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc_url = format!("http://{}/jsonrpc", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = Aria2Client::new(&rpc_url, 0, 0);
+        assert!(client.get_all_downloads(None).await.is_err());
+    }
+
+    /// `cargo test --release -- --ignored --nocapture bench_get_all_downloads`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_get_all_downloads() {
+        const ROUNDS: u32 = 20;
+        let (rpc_url, _) = mock_download_list_server(Duration::from_millis(5), 10, 50, 40).await;
+        let client = Aria2Client::new(&rpc_url, 0, 0);
+
+        let start = std::time::Instant::now();
+        for _ in 0..ROUNDS {
+            assert_eq!(get_all_downloads_sequentially(&client, None).await.len(), 100);
+        }
+        let sequential = start.elapsed() / ROUNDS;
+
+        let start = std::time::Instant::now();
+        for _ in 0..ROUNDS {
+            assert_eq!(client.get_all_downloads(None).await.unwrap().len(), 100);
+        }
+        let batched = start.elapsed() / ROUNDS;
+
+        println!("100 downloads: {:?} with three calls, {:?} with one multicall", sequential, batched);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_commands_share_one_client() {
+        let release = Arc::new(tokio::sync::Notify::new());
+        let rpc_url = mock_rpc_server(release.clone()).await;
+        let client = Arc::new(Aria2Client::new(&rpc_url, 0, 0));
+
+        // A slow call in flight mustn't hold up the rest
+        let slow = {
+            let client = client.clone();
+            tokio::spawn(async move { client.tell_active().await })
+        };
+        let calls: Vec<_> = (0..100)
+            .map(|i| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    let gid = format!("{:016x}", i);
+                    client.set_bandwidth_limit(i, i);
+                    let (overall, _) = client.get_bandwidth_limit();
+                    assert!(overall < 100);
+                    client.record_speed_sample(&gid, &DownloadStatus::Active, i);
+                    let result = if i % 2 == 0 { client.pause(&gid).await } else { client.unpause(&gid).await };
+                    assert_eq!(result.unwrap(), gid);
+                })
+            })
+            .collect();
+        for call in calls {
+            call.await.unwrap();
+        }
+        assert!(!slow.is_finished());
+        release.notify_one();
+
+        assert_eq!(client.speed_samples.lock().unwrap().len(), 100);
+        assert!(slow.await.unwrap().unwrap().is_empty());
     }
 }
@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Lines of aria2c output kept in memory for `get_aria2_logs`
+const LOG_CAPACITY: usize = 1000;
+
+/// Tail of aria2c's stdout/stderr, optionally mirrored to a log file
+#[derive(Default)]
+pub struct Aria2Logs {
+    lines: Mutex<VecDeque<String>>,
+    file: Mutex<Option<File>>,
+}
+
+impl Aria2Logs {
+    pub fn log_file_path() -> PathBuf {
+        crate::settings::api::get_config_dir().join("aria2.log")
+    }
+
+    /// Starts or stops mirroring lines to `log_file_path()`
+    pub fn set_log_to_file(&self, enabled: bool) {
+        let file = if enabled {
+            let path = Self::log_file_path();
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    log::warn!("Failed to open aria2 log file {}: {}", path.display(), e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        *self.file.lock().unwrap() = file;
+    }
+
+    pub fn push(&self, line: String) {
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let _ = writeln!(file, "{}", line);
+        }
+
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == LOG_CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn clear(&self) {
+        self.lines.lock().unwrap().clear();
+    }
+
+    /// The last `max_lines` lines, oldest first
+    pub fn tail(&self, max_lines: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines.iter().skip(lines.len().saturating_sub(max_lines)).cloned().collect()
+    }
+}
+
+/// Copies lines from one of the child's pipes into `logs` on a background thread.
+/// The thread ends once the process exits and the pipe closes.
+pub fn spawn_log_reader<R: Read + Send + 'static>(stream: R, logs: Arc<Aria2Logs>) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            logs.push(line);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_keeps_most_recent_lines() {
+        let logs = Aria2Logs::default();
+        for i in 0..LOG_CAPACITY + 5 {
+            logs.push(i.to_string());
+        }
+
+        assert_eq!(logs.tail(2), vec![(LOG_CAPACITY + 3).to_string(), (LOG_CAPACITY + 4).to_string()]);
+        assert_eq!(logs.tail(usize::MAX).len(), LOG_CAPACITY);
+        assert_eq!(logs.tail(usize::MAX)[0], "5");
+    }
+}
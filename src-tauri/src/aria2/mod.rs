@@ -1,4 +1,5 @@
 mod api;
+mod logs;
 mod types;
 
 pub use api::*;
@@ -1,4 +1,8 @@
+use crate::download::Priority;
+use crate::terabox::FileCategory;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Aria2Options {
@@ -10,12 +14,26 @@ pub struct Aria2Options {
     pub min_split_size: Option<String>,
     #[serde(rename = "user-agent")]
     pub user_agent: Option<String>,
+    pub referer: Option<String>,
     pub out: Option<String>,
-    pub header: Option<Vec<String>>,
+    /// Sent as repeated `--header "Name: value"` entries
+    #[serde(
+        rename = "header",
+        default,
+        serialize_with = "serialize_headers",
+        deserialize_with = "deserialize_headers"
+    )]
+    pub headers: HashMap<String, String>,
     #[serde(rename = "file-allocation")]
     pub file_allocation: Option<String>,
     #[serde(rename = "continue")]
     pub continue_download: Option<String>,
+    /// Add the download in the paused state
+    pub pause: Option<String>,
+    #[serde(rename = "allow-overwrite")]
+    pub allow_overwrite: Option<String>,
+    #[serde(rename = "auto-file-renaming")]
+    pub auto_file_renaming: Option<String>,
 }
 
 impl Default for Aria2Options {
@@ -26,14 +44,208 @@ impl Default for Aria2Options {
             split: Some("16".to_string()),
             min_split_size: Some("1M".to_string()),
             user_agent: Some("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36".to_string()),
+            referer: None,
             out: None,
-            header: None,
+            headers: HashMap::new(),
             file_allocation: Some("none".to_string()),
             continue_download: Some("true".to_string()),
+            pause: None,
+            allow_overwrite: None,
+            auto_file_renaming: None,
         }
     }
 }
 
+fn serialize_headers<S>(headers: &HashMap<String, String>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let lines: Vec<String> = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect();
+    lines.serialize(serializer)
+}
+
+fn deserialize_headers<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let lines: Option<Vec<String>> = Option::deserialize(deserializer)?;
+    Ok(lines
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect())
+}
+
+/// What happens when a download's target file already exists
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverwritePolicy {
+    /// Save next to it as `name.1.ext`
+    #[default]
+    Rename,
+    /// Replace it
+    Overwrite,
+    /// Leave it and don't download
+    Skip,
+}
+
+impl OverwritePolicy {
+    /// Value of `--allow-overwrite`
+    pub fn allow_overwrite(&self) -> &'static str {
+        match self {
+            OverwritePolicy::Overwrite => "true",
+            OverwritePolicy::Rename | OverwritePolicy::Skip => "false",
+        }
+    }
+
+    /// Value of `--auto-file-renaming`. With neither flag set, aria2 fails the download
+    /// instead of touching the file, which is what `Skip` relies on.
+    pub fn auto_file_renaming(&self) -> &'static str {
+        match self {
+            OverwritePolicy::Rename => "true",
+            OverwritePolicy::Overwrite | OverwritePolicy::Skip => "false",
+        }
+    }
+}
+
+/// How aria2 reserves disk space for a download (`--file-allocation`).
+///
+/// - `None`: reserves nothing. Starts instantly, but large files can end up fragmented.
+/// - `Prealloc`: writes out the full size before downloading. No fragmentation, but a big
+///   file can take minutes to start on a slow disk.
+/// - `Trunc`: sets the size with `ftruncate`. Instant, but the file is sparse, so it
+///   fragments like `None`.
+/// - `Falloc`: asks the filesystem to reserve the space. Instant and unfragmented, but
+///   only on filesystems with `fallocate` support (ext4, btrfs, xfs, NTFS, ...).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAllocation {
+    #[default]
+    None,
+    Prealloc,
+    Trunc,
+    Falloc,
+}
+
+impl FileAllocation {
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            FileAllocation::None => "none",
+            FileAllocation::Prealloc => "prealloc",
+            FileAllocation::Trunc => "trunc",
+            FileAllocation::Falloc => "falloc",
+        }
+    }
+
+    /// The strategies that work for files in `dir`
+    pub fn supported_for(dir: &Path) -> Vec<FileAllocation> {
+        let mut supported = vec![FileAllocation::None, FileAllocation::Prealloc, FileAllocation::Trunc];
+        if supports_falloc(dir) {
+            supported.push(FileAllocation::Falloc);
+        }
+        supported
+    }
+
+    /// This strategy, or `None` when `dir`'s filesystem can't do it
+    pub fn effective_for(self, dir: &Path) -> FileAllocation {
+        if self == FileAllocation::Falloc && !supports_falloc(dir) {
+            log::warn!("falloc isn't supported for {}, using none", dir.display());
+            return FileAllocation::None;
+        }
+        self
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn supports_falloc(dir: &Path) -> bool {
+    const FALLOC_FILESYSTEMS: &[&str] = &["ext4", "btrfs", "xfs", "f2fs", "tmpfs", "ocfs2", "bcachefs"];
+
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+
+    // The longest mount point containing `dir` is the filesystem it lives on
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?;
+            let fs_type = fields.next()?;
+            Some((mount_point, fs_type))
+        })
+        .filter(|(mount_point, _)| dir.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .is_some_and(|(_, fs_type)| FALLOC_FILESYSTEMS.contains(&fs_type))
+}
+
+#[cfg(target_os = "windows")]
+fn supports_falloc(_dir: &Path) -> bool {
+    // Downloads go to NTFS in practice; FAT/exFAT volumes fail their first write in aria2
+    true
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn supports_falloc(_dir: &Path) -> bool {
+    // No posix_fallocate on macOS
+    false
+}
+
+/// Optional per-call extras for `add_download`, on top of the saved settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddDownloadOptions {
+    pub max_connections: Option<u32>,
+    pub split: Option<u32>,
+    /// Original Terabox share URL, sent as the referer
+    pub share_url: Option<String>,
+    /// Terabox file id within the share, kept in the download's `DownloadOrigin`
+    pub fs_id: Option<String>,
+    /// Cookies returned alongside the resolved direct link
+    pub cookies: Option<String>,
+    /// Enqueue even if the same file is already queued or downloaded
+    pub force: bool,
+    /// Mirrors of `url` (e.g. `DownloadLink::urls`); `url` itself may be repeated here
+    pub urls: Vec<String>,
+    /// Queue the download paused, to be started later with `resume_download`
+    pub paused: bool,
+    /// Size reported by Terabox, used to spot a file that's already fully on disk
+    pub expected_size: Option<u64>,
+    /// Add an already-downloaded file to the history instead of just reporting it
+    pub record_existing: bool,
+    /// Overrides `AppSettings::overwrite_policy` for this download
+    pub overwrite_policy: Option<OverwritePolicy>,
+    /// Batch this download belongs to, kept in its `DownloadOrigin`
+    pub group_id: Option<String>,
+    /// `TeraboxFileInfo::file_category`, for picking the folder from
+    /// `AppSettings::category_dirs` when no `dir` is given
+    pub file_category: Option<FileCategory>,
+    /// Overrides `AppSettings::user_agent_for` for this download
+    pub user_agent: Option<String>,
+    /// Scales the connection count unless `max_connections`/`split` are given, and
+    /// decides where in aria2's queue the download goes
+    pub priority: Priority,
+}
+
+/// What a GID was added from, as far as aria2 can't tell us
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadOrigin {
+    pub share_url: Option<String>,
+    pub fs_id: Option<String>,
+    /// Filename as requested, before `sanitize_filename`
+    pub requested_name: Option<String>,
+    /// Set for downloads added together by `add_downloads_batch`
+    pub group_id: Option<String>,
+    /// RFC 3339; aria2 itself doesn't say when a download was added or finished
+    pub added_at: Option<String>,
+    pub completed_at: Option<String>,
+    /// Set once the finished download is in the history, which may since have dropped it
+    pub recorded: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Aria2RpcRequest {
     pub jsonrpc: String,
@@ -44,10 +256,19 @@ pub struct Aria2RpcRequest {
 
 impl Aria2RpcRequest {
     pub fn new(method: &str, params: Vec<serde_json::Value>) -> Self {
+        Self::with_method_name(format!("aria2.{}", method), params)
+    }
+
+    /// For `system.*` methods, which take no secret token
+    pub fn system(method: &str, params: Vec<serde_json::Value>) -> Self {
+        Self::with_method_name(format!("system.{}", method), params)
+    }
+
+    fn with_method_name(method: String, params: Vec<serde_json::Value>) -> Self {
         Self {
             jsonrpc: "2.0".to_string(),
             id: uuid::Uuid::new_v4().to_string(),
-            method: format!("aria2.{}", method),
+            method,
             params,
         }
     }
@@ -58,15 +279,22 @@ pub struct Aria2RpcResponse<T> {
     pub jsonrpc: String,
     pub id: String,
     pub result: Option<T>,
-    pub error: Option<Aria2Error>,
+    pub error: Option<Aria2RpcError>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Aria2Error {
+pub struct Aria2RpcError {
     pub code: i64,
     pub message: String,
 }
 
+/// One entry of the prebuilt aria2c manifest, keyed by `<os>-<arch>`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aria2BinaryAsset {
+    pub url: String,
+    pub sha256: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Aria2Status {
     pub gid: String,
@@ -79,6 +307,11 @@ pub struct Aria2Status {
     pub download_speed: Option<String>,
     #[serde(rename = "uploadSpeed")]
     pub upload_speed: Option<String>,
+    #[serde(rename = "uploadLength")]
+    pub upload_length: Option<String>,
+    #[serde(rename = "numSeeders")]
+    pub num_seeders: Option<String>,
+    pub bittorrent: Option<serde_json::Value>,
     pub connections: Option<String>,
     #[serde(rename = "errorCode")]
     pub error_code: Option<String>,
@@ -99,22 +332,98 @@ pub struct Aria2File {
     pub uris: Option<Vec<Aria2Uri>>,
 }
 
+/// Parsed `Aria2File`: one file inside a multi-file download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadFile {
+    /// 1-based, as `select-file` expects
+    pub index: u32,
+    pub path: String,
+    pub length: u64,
+    pub completed_length: u64,
+    pub selected: bool,
+}
+
+impl From<Aria2File> for DownloadFile {
+    fn from(file: Aria2File) -> Self {
+        Self {
+            index: file.index.parse().unwrap_or(0),
+            path: file.path,
+            length: file.length.parse().unwrap_or(0),
+            completed_length: file.completed_length.parse().unwrap_or(0),
+            selected: file.selected == "true",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Aria2Uri {
     pub uri: String,
     pub status: String,
 }
 
+/// One file's entry in `aria2.getServers`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aria2FileServers {
+    pub index: String,
+    pub servers: Vec<Aria2Server>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aria2Server {
+    pub uri: String,
+    /// Differs from `uri` after a redirect
+    #[serde(rename = "currentUri")]
+    pub current_uri: String,
+    #[serde(rename = "downloadSpeed")]
+    pub download_speed: String,
+}
+
+/// A server a download is fetching from, for spotting a slow CDN node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadServer {
+    /// 1-based, as in `DownloadFile::index`
+    pub file_index: u32,
+    /// The URI as added
+    pub uri: String,
+    /// Where aria2 ended up after redirects
+    pub current_uri: String,
+    /// Host of `current_uri`, e.g. the CDN node
+    pub host: Option<String>,
+    /// Bytes/sec from this server; 0 unless the download is active
+    pub speed: u64,
+}
+
+impl DownloadServer {
+    pub fn new(file_index: u32, uri: String, current_uri: String, speed: u64) -> Self {
+        let host = reqwest::Url::parse(&current_uri)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string));
+        Self { file_index, uri, current_uri, host, speed }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadInfo {
     pub gid: String,
     pub filename: String,
+    pub path: Option<String>,
     pub total_size: u64,
     pub downloaded: u64,
     pub speed: u64,
+    /// Moving average of the last few `speed` samples, steadier for display
+    pub average_speed: u64,
     pub progress: f64,
     pub status: DownloadStatus,
     pub error_message: Option<String>,
+    pub eta_seconds: Option<u64>,
+    pub connections: u32,
+    pub uploaded: u64,
+    pub upload_speed: u64,
+    pub seeders: u32,
+    pub source_uri: Option<String>,
+    /// From the download's `DownloadOrigin`, RFC 3339
+    pub created_time: Option<String>,
+    pub completed_time: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -130,7 +439,7 @@ pub enum DownloadStatus {
 
 impl From<&str> for DownloadStatus {
     fn from(s: &str) -> Self {
-        match s {
+        match s.trim().to_ascii_lowercase().as_str() {
             "active" => DownloadStatus::Active,
             "waiting" => DownloadStatus::Waiting,
             "paused" => DownloadStatus::Paused,
@@ -142,6 +451,162 @@ impl From<&str> for DownloadStatus {
     }
 }
 
+/// Result of `aria2.getVersion`, e.g. features `["BitTorrent", "Metalink", ...]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aria2Version {
+    pub version: String,
+    #[serde(alias = "enabledFeatures", default)]
+    pub enabled_features: Vec<String>,
+}
+
+/// Result of `validate_aria2_config`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Aria2ConfigCheck {
+    pub valid: bool,
+    /// What aria2c was run with, the RPC secret masked
+    pub args: Vec<String>,
+    /// aria2's own complaints: its startup output, or the options it rejected
+    pub problems: Vec<String>,
+    /// Set once aria2c came up
+    pub version: Option<String>,
+}
+
+/// Result of `ping_aria2`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aria2Ping {
+    pub reachable: bool,
+    pub latency_ms: u64,
+    pub version: Option<String>,
+}
+
+/// A slice of the waiting or stopped list. `total` is the list's full length.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadPage {
+    pub downloads: Vec<DownloadInfo>,
+    pub offset: u32,
+    pub total: u32,
+}
+
+/// An unfinished download as it was before a daemon restart, enough to add it back
+/// under the same GID and in the same paused or running state
+#[derive(Debug, Clone)]
+pub struct QueuedDownload {
+    pub gid: String,
+    pub uris: Vec<String>,
+    /// From `getOption`, so dir, out, headers and limits carry over
+    pub options: serde_json::Map<String, serde_json::Value>,
+    pub paused: bool,
+}
+
+impl QueuedDownload {
+    /// None for downloads without URIs to add back, such as torrents
+    pub fn from_status(
+        status: &Aria2Status,
+        options: serde_json::Map<String, serde_json::Value>,
+    ) -> Option<Self> {
+        let mut uris: Vec<String> = Vec::new();
+        for uri in status.files.iter().flatten().flat_map(|f| f.uris.iter().flatten()) {
+            if !uris.contains(&uri.uri) {
+                uris.push(uri.uri.clone());
+            }
+        }
+        if uris.is_empty() || status.bittorrent.is_some() {
+            return None;
+        }
+
+        Some(Self {
+            gid: status.gid.clone(),
+            uris,
+            options,
+            paused: DownloadStatus::from(status.status.as_str()) == DownloadStatus::Paused,
+        })
+    }
+
+    /// `addUri` options that recreate it
+    pub fn add_options(&self) -> serde_json::Value {
+        let mut options = self.options.clone();
+        options.insert("gid".to_string(), serde_json::json!(self.gid));
+        if self.paused {
+            options.insert("pause".to_string(), serde_json::json!("true"));
+        } else {
+            options.remove("pause");
+        }
+        serde_json::Value::Object(options)
+    }
+}
+
+/// Combined progress of a download group, counting only members aria2 still knows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupProgress {
+    pub group_id: String,
+    pub downloaded: u64,
+    pub total_size: u64,
+    pub progress: f64,
+    pub downloads: Vec<DownloadInfo>,
+    /// The download a sequential batch is running; None for parallel batches
+    pub current_gid: Option<String>,
+}
+
+/// Parsed `Aria2GlobalStat`: aggregate speeds across every active download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalStats {
+    pub download_speed: u64,
+    pub upload_speed: u64,
+    pub num_active: u32,
+    pub num_waiting: u32,
+    pub num_stopped: u32,
+}
+
+impl From<Aria2GlobalStat> for GlobalStats {
+    fn from(stat: Aria2GlobalStat) -> Self {
+        Self {
+            download_speed: stat.download_speed.parse().unwrap_or(0),
+            upload_speed: stat.upload_speed.parse().unwrap_or(0),
+            num_active: stat.num_active.parse().unwrap_or(0),
+            num_waiting: stat.num_waiting.parse().unwrap_or(0),
+            num_stopped: stat.num_stopped.parse().unwrap_or(0),
+        }
+    }
+}
+
+/// Totals across every active download, for a global progress bar
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OverallProgress {
+    pub total_bytes: u64,
+    pub downloaded_bytes: u64,
+    pub overall_speed: u64,
+    /// None while nothing is moving or a download's size isn't known yet
+    pub eta_seconds: Option<u64>,
+}
+
+impl OverallProgress {
+    pub fn from_active(active: &[Aria2Status], overall_speed: u64) -> Self {
+        let parse = |value: &Option<String>| value.as_deref().and_then(|v| v.parse::<u64>().ok());
+        let sizes: Vec<(Option<u64>, u64)> = active
+            .iter()
+            .map(|status| {
+                let total = parse(&status.total_length).filter(|total| *total > 0);
+                (total, parse(&status.completed_length).unwrap_or(0))
+            })
+            .collect();
+
+        let total_bytes: u64 = sizes.iter().filter_map(|(total, _)| *total).sum();
+        let downloaded_bytes = sizes.iter().map(|(_, done)| done).sum();
+        let sizes_known = sizes.iter().all(|(total, _)| total.is_some());
+        let eta_seconds = (sizes_known && overall_speed > 0).then(|| {
+            let remaining = total_bytes.saturating_sub(downloaded_bytes);
+            remaining.div_ceil(overall_speed)
+        });
+
+        Self {
+            total_bytes,
+            downloaded_bytes,
+            overall_speed,
+            eta_seconds,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Aria2GlobalStat {
     #[serde(rename = "downloadSpeed")]
@@ -157,3 +622,90 @@ pub struct Aria2GlobalStat {
     #[serde(rename = "numStoppedTotal")]
     pub num_stopped_total: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_status_from_str() {
+        assert_eq!(DownloadStatus::from("waiting"), DownloadStatus::Waiting);
+        assert_eq!(DownloadStatus::from("paused"), DownloadStatus::Paused);
+        assert_eq!(DownloadStatus::from("Paused"), DownloadStatus::Paused);
+        assert_eq!(DownloadStatus::from("complete"), DownloadStatus::Complete);
+        assert_eq!(DownloadStatus::from("removed"), DownloadStatus::Removed);
+    }
+
+    #[test]
+    fn test_paused_download_stays_paused_across_restart() {
+        let status: Aria2Status = serde_json::from_value(serde_json::json!({
+            "gid": "2089b05ecca3d829",
+            "status": "paused",
+            "files": [{
+                "index": "1",
+                "path": "/dl/movie.mkv",
+                "length": "100",
+                "completedLength": "40",
+                "selected": "true",
+                "uris": [
+                    {"uri": "https://d.terabox.com/file", "status": "used"},
+                    {"uri": "https://d.terabox.com/file", "status": "waiting"}
+                ]
+            }]
+        }))
+        .unwrap();
+        let mut options = serde_json::Map::new();
+        options.insert("dir".to_string(), serde_json::json!("/dl"));
+
+        let queued = QueuedDownload::from_status(&status, options.clone()).unwrap();
+        assert_eq!(queued.uris, vec!["https://d.terabox.com/file".to_string()]);
+        let readded = queued.add_options();
+        assert_eq!(readded["gid"], "2089b05ecca3d829");
+        assert_eq!(readded["pause"], "true");
+        assert_eq!(readded["dir"], "/dl");
+
+        let active = Aria2Status {
+            status: "active".to_string(),
+            ..status
+        };
+        options.insert("pause".to_string(), serde_json::json!("true"));
+        let readded = QueuedDownload::from_status(&active, options).unwrap().add_options();
+        assert!(readded.get("pause").is_none());
+    }
+
+    #[test]
+    fn test_overall_progress_needs_every_size_for_eta() {
+        let status = |total: &str, done: &str| -> Aria2Status {
+            serde_json::from_value(serde_json::json!({
+                "gid": "2089b05ecca3d829",
+                "status": "active",
+                "totalLength": total,
+                "completedLength": done,
+            }))
+            .unwrap()
+        };
+
+        let progress = OverallProgress::from_active(&[status("1000", "400"), status("600", "0")], 100);
+        assert_eq!(progress.total_bytes, 1600);
+        assert_eq!(progress.downloaded_bytes, 400);
+        assert_eq!(progress.eta_seconds, Some(12));
+
+        // aria2 reports 0 until it has seen the server's Content-Length
+        let unknown = OverallProgress::from_active(&[status("1000", "400"), status("0", "0")], 100);
+        assert_eq!(unknown.total_bytes, 1000);
+        assert_eq!(unknown.eta_seconds, None);
+        assert_eq!(OverallProgress::from_active(&[status("1000", "400")], 0).eta_seconds, None);
+    }
+
+    #[test]
+    fn test_download_server_host_follows_redirect() {
+        let server = DownloadServer::new(
+            1,
+            "https://d.terabox.com/file/abc".to_string(),
+            "https://data-cdn7.terabox.com:443/file/abc?sign=x".to_string(),
+            2048,
+        );
+        assert_eq!(server.host.as_deref(), Some("data-cdn7.terabox.com"));
+        assert_eq!(DownloadServer::new(1, String::new(), "not a uri".to_string(), 0).host, None);
+    }
+}
@@ -0,0 +1,371 @@
+use crate::aria2::{AddDownloadOptions, Aria2Client, DownloadInfo, DownloadOrigin, DownloadStatus};
+use crate::download::BatchMode;
+use crate::error::AppError;
+use crate::settings::api::{add_history_item, load_history};
+use crate::settings::types::DownloadHistoryItem;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// How often the completion watcher polls aria2 for finished downloads
+pub const COMPLETION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Event emitted with a `DownloadAdded` once an `add_download_async` call is accepted
+pub const DOWNLOAD_ADDED_EVENT: &str = "download-added";
+/// Event emitted with a `DownloadAddFailed` when an `add_download_async` call fails
+pub const DOWNLOAD_ADD_FAILED_EVENT: &str = "download-add-failed";
+
+static RECORDING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Records every newly finished (complete or errored) download in the history and
+/// returns them. A GID is never recorded twice, even once the capped or pruned history
+/// no longer has it, so results that survive a restart in the aria2 session aren't
+/// duplicated.
+pub async fn record_finished_downloads(client: &Aria2Client) -> Vec<DownloadInfo> {
+    // The completion loop and the idle supervisor both call this; one at a time, or
+    // both could see a download missing from the history and record it twice
+    let _recording = RECORDING.lock().await;
+    // aria2 lists stopped results oldest first; a negative offset counts from the newest,
+    // so new completions are seen however many results have piled up
+    let stopped = match client.tell_stopped(-1, 100).await {
+        Ok(stopped) => stopped,
+        Err(_) => return Vec::new(),
+    };
+
+    let history = load_history();
+    let mut finished = Vec::new();
+
+    // Oldest first again, so the history lists them in the order they finished
+    for entry in stopped.into_iter().rev() {
+        let status = DownloadStatus::from(entry.status.as_str());
+        if !matches!(status, DownloadStatus::Complete | DownloadStatus::Error) {
+            continue;
+        }
+        // Histories from before `recorded` existed are the only record of older GIDs
+        let recorded = client.get_origin(&entry.gid).is_some_and(|origin| origin.recorded);
+        if recorded || history.items.iter().any(|item| item.id == entry.gid) {
+            continue;
+        }
+
+        let mut info = match client.get_download_info(&entry.gid).await {
+            Ok(info) => info,
+            Err(_) => continue,
+        };
+        if status == DownloadStatus::Complete {
+            info.completed_time = client.mark_completed(&info.gid);
+        }
+
+        let origin = client.get_origin(&info.gid).unwrap_or_default();
+        if let Err(e) = add_history_item(history_item_from(&info, origin)) {
+            log::warn!("Failed to record history for {}: {}", info.gid, e);
+            continue;
+        }
+        client.mark_recorded(&info.gid);
+
+        finished.push(info);
+    }
+
+    finished
+}
+
+/// What `add_download` added
+#[derive(Debug, Clone, Serialize)]
+pub struct AddedDownload {
+    pub gid: String,
+    /// Percent already on disk when an earlier partial download is being resumed
+    pub resumed_from: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadAdded {
+    /// The id `add_download_async` returned
+    pub tracking_id: String,
+    #[serde(flatten)]
+    pub download: AddedDownload,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadAddFailed {
+    pub tracking_id: String,
+    pub error: AppError,
+}
+
+/// Outcome of retrying every failed download at once
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetryReport {
+    /// GIDs of the re-added downloads
+    pub retried: Vec<String>,
+    pub failed: Vec<RetryFailure>,
+}
+
+/// What `bootstrap` found and did at launch
+#[derive(Debug, Clone, Serialize)]
+pub struct Bootstrap {
+    pub aria2_running: bool,
+    pub downloads: Vec<DownloadInfo>,
+    /// Queued downloads aria2 came back without, added again from their shares
+    pub requeued: RetryReport,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RetryFailure {
+    /// GID of the failed download that couldn't be retried
+    pub gid: String,
+    pub message: String,
+}
+
+/// One entry of `add_downloads_batch`, with the same meaning as `add_download`'s arguments
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchDownload {
+    pub url: String,
+    pub filename: Option<String>,
+    pub options: Option<AddDownloadOptions>,
+}
+
+/// Outcome of `add_downloads_batch`; the added downloads share `group_id`
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchReport {
+    pub group_id: String,
+    pub mode: BatchMode,
+    pub gids: Vec<String>,
+    pub failed: Vec<BatchFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchFailure {
+    pub url: String,
+    pub message: String,
+}
+
+fn history_item_from(info: &DownloadInfo, origin: DownloadOrigin) -> DownloadHistoryItem {
+    let status = if info.status == DownloadStatus::Complete {
+        "completed"
+    } else {
+        "failed"
+    };
+
+    DownloadHistoryItem {
+        id: info.gid.clone(),
+        // Show the name as it was on Terabox; `path` has the sanitized one on disk
+        filename: origin.requested_name.unwrap_or_else(|| info.filename.clone()),
+        // Prefer the share URL: the resolved direct link expires within hours
+        url: origin.share_url.or_else(|| info.source_uri.clone()),
+        size: info.total_size,
+        status: status.to_string(),
+        // When it finished, not when the completion loop got to it
+        downloaded_at: info
+            .completed_time
+            .clone()
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        path: info.path.clone().unwrap_or_default(),
+    }
+}
+
+/// Returns the path of `dir/filename` if it's already there in full: the size matches and
+/// there's no `.aria2` control file, which aria2 keeps next to partial downloads
+pub fn existing_complete_file(dir: &str, filename: &str, expected_size: u64) -> Option<PathBuf> {
+    let path = Path::new(dir).join(filename);
+    let size = std::fs::metadata(&path).ok().filter(|m| m.is_file())?.len();
+
+    if size != expected_size || has_control_file(&path) {
+        return None;
+    }
+
+    Some(path)
+}
+
+fn control_file_path(path: &Path) -> PathBuf {
+    let mut control_file = path.as_os_str().to_owned();
+    control_file.push(".aria2");
+    PathBuf::from(control_file)
+}
+
+/// Whether aria2 left a `.aria2` control file next to `path`, i.e. it's a partial download
+pub fn has_control_file(path: &Path) -> bool {
+    control_file_path(path).exists()
+}
+
+/// Deletes a partial download and its `.aria2` control file, returning the paths that
+/// were deleted. Files that are already gone are skipped.
+pub fn delete_partial_files(path: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let mut deleted = Vec::new();
+    for file in [path.to_path_buf(), control_file_path(path)] {
+        match std::fs::remove_file(&file) {
+            Ok(()) => deleted.push(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(AppError::io(format!("Failed to delete {}: {}", file.display(), e))),
+        }
+    }
+    Ok(deleted)
+}
+
+/// How far a partial download at `path` got, in percent, from its `.aria2` control file.
+/// None when there's no partial download there to resume.
+pub fn partial_download_progress(path: &Path) -> Option<f64> {
+    if !path.is_file() {
+        return None;
+    }
+    let control = std::fs::read(control_file_path(path)).ok()?;
+    let (completed, total) = parse_control_file(&control)?;
+    Some(completed as f64 / total as f64 * 100.0)
+}
+
+/// Completed and total bytes recorded in an aria2 control file. Version 1 files are
+/// big-endian; version 0 used the writer's byte order, in practice little-endian.
+fn parse_control_file(bytes: &[u8]) -> Option<(u64, u64)> {
+    let big_endian = match bytes.get(..2)? {
+        [0, 1] => true,
+        [0, 0] => false,
+        _ => return None,
+    };
+    let mut pos = 2;
+    let mut take = |len: usize| {
+        let field = bytes.get(pos..pos + len)?;
+        pos += len;
+        Some(field)
+    };
+    let read_u32 = |field: &[u8]| {
+        let field: [u8; 4] = field.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(field) } else { u32::from_le_bytes(field) })
+    };
+
+    take(4)?; // extension flags
+    let info_hash_len = read_u32(take(4)?)? as usize;
+    take(info_hash_len)?;
+    let piece_length = read_u32(take(4)?)? as u64;
+    let total: [u8; 8] = take(8)?.try_into().ok()?;
+    let total = if big_endian { u64::from_be_bytes(total) } else { u64::from_le_bytes(total) };
+    take(8)?; // uploaded bytes
+    let bitfield_len = read_u32(take(4)?)? as usize;
+    let bitfield = take(bitfield_len)?;
+    if total == 0 {
+        return None;
+    }
+
+    let pieces: u64 = bitfield.iter().map(|byte| byte.count_ones() as u64).sum();
+    // The last piece is usually short, so a full bitfield can overshoot the total
+    Some((pieces.saturating_mul(piece_length).min(total), total))
+}
+
+/// Records a file found already complete on disk, without a GID to key it by
+pub fn record_existing_file(path: &Path, filename: &str, share_url: Option<String>) {
+    let item = DownloadHistoryItem {
+        id: uuid::Uuid::new_v4().to_string(),
+        filename: filename.to_string(),
+        url: share_url,
+        size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        status: "completed".to_string(),
+        downloaded_at: chrono::Utc::now().to_rfc3339(),
+        path: path.to_string_lossy().to_string(),
+    };
+
+    if let Err(e) = add_history_item(item) {
+        log::warn!("Failed to record existing file {}: {}", path.display(), e);
+    }
+}
+
+/// Looks for a queued, active or already downloaded file that would end up at
+/// `dir/filename`, returning its GID. Failed and removed downloads don't count,
+/// since retrying them is the whole point.
+pub async fn find_duplicate(client: &Aria2Client, dir: &str, filename: &str) -> Option<String> {
+    let target = download_key(Path::new(dir), filename);
+
+    // All of them: a long queue, say from a folder import, is where a repeat is likeliest
+    if let Ok(downloads) = client.get_all_downloads(Some(usize::MAX)).await {
+        let existing = downloads.into_iter().find(|d| {
+            !matches!(d.status, DownloadStatus::Error | DownloadStatus::Removed)
+                && d.path.as_deref().and_then(path_key).as_ref() == Some(&target)
+        });
+        if let Some(existing) = existing {
+            return Some(existing.gid);
+        }
+    }
+
+    load_history()
+        .items
+        .into_iter()
+        .find(|item| item.status == "completed" && path_key(&item.path).as_ref() == Some(&target))
+        .map(|item| item.id)
+}
+
+/// Normalized directory plus lowercased filename, so `Foo.MP4` in `/dl/` and
+/// `foo.mp4` in `/dl` compare equal
+fn download_key(dir: &Path, filename: &str) -> (PathBuf, String) {
+    (normalize_dir(dir), filename.to_lowercase())
+}
+
+fn path_key(path: &str) -> Option<(PathBuf, String)> {
+    let path = Path::new(path);
+    let filename = path.file_name()?.to_str()?;
+    Some(download_key(path.parent()?, filename))
+}
+
+fn normalize_dir(dir: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in dir.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_partial_files_skips_missing_ones() {
+        let dir = std::env::temp_dir().join(format!("trauso-partial-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("movie.mkv");
+        std::fs::write(&path, b"partial").unwrap();
+        std::fs::write(dir.join("movie.mkv.aria2"), b"control").unwrap();
+
+        assert_eq!(delete_partial_files(&path).unwrap(), vec![path.clone(), dir.join("movie.mkv.aria2")]);
+        assert!(delete_partial_files(&path).unwrap().is_empty());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_control_file_progress() {
+        let control = |version: [u8; 2], bitfield: &[u8]| {
+            let mut bytes = version.to_vec();
+            bytes.extend([0; 4]); // extension
+            bytes.extend(0u32.to_be_bytes()); // no info hash
+            bytes.extend((1024u32 * 1024).to_be_bytes());
+            bytes.extend((10u64 * 1024 * 1024).to_be_bytes());
+            bytes.extend(0u64.to_be_bytes());
+            bytes.extend((bitfield.len() as u32).to_be_bytes());
+            bytes.extend(bitfield);
+            bytes
+        };
+        const MB: u64 = 1024 * 1024;
+
+        // 10 pieces of 1 MiB; the first four are done
+        assert_eq!(parse_control_file(&control([0, 1], &[0b1111_0000, 0])), Some((4 * MB, 10 * MB)));
+        assert_eq!(parse_control_file(&control([0, 1], &[0xff, 0xff])), Some((10 * MB, 10 * MB)));
+        assert_eq!(parse_control_file(&control([0, 1], &[0xff])[..20]), None);
+        assert_eq!(parse_control_file(&control([0, 2], &[0xff, 0xff])), None);
+    }
+
+    #[test]
+    fn test_path_key_ignores_case_and_trailing_separators() {
+        assert_eq!(
+            path_key("/home/user/Downloads/./Movie.MP4"),
+            Some(download_key(Path::new("/home/user/Downloads/"), "movie.mp4"))
+        );
+        assert_eq!(
+            path_key("/home/user/tmp/../Downloads/a.zip"),
+            Some(download_key(Path::new("/home/user/Downloads"), "A.zip"))
+        );
+        assert_ne!(
+            path_key("/home/user/Other/a.zip"),
+            Some(download_key(Path::new("/home/user/Downloads"), "a.zip"))
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::aria2::{Aria2Client, DownloadStatus};
+use crate::error::AppError;
+use std::sync::Mutex;
+
+/// Event emitted when every download was paused because the connection became metered
+pub const AUTO_PAUSED_EVENT: &str = "downloads-auto-paused";
+/// Event emitted when every download was paused because the battery ran low
+pub const BATTERY_PAUSED_EVENT: &str = "downloads-paused-battery";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Metered,
+    LowBattery,
+    /// The OS is about to suspend; see `prepare_for_sleep`
+    Sleep,
+    /// The network went away; see `notify_network_changed`
+    Offline,
+}
+
+struct AutoPauseState {
+    metered: bool,
+    low_battery: bool,
+    sleeping: bool,
+    offline: bool,
+    /// GIDs we paused ourselves; `Some` while auto-paused. Only these are resumed,
+    /// so downloads the user paused by hand stay paused.
+    paused_gids: Option<Vec<String>>,
+}
+
+impl AutoPauseState {
+    fn wants_pause(&self) -> bool {
+        self.metered || self.low_battery || self.sleeping || self.offline
+    }
+}
+
+static STATE: Mutex<AutoPauseState> = Mutex::new(AutoPauseState {
+    metered: false,
+    low_battery: false,
+    sleeping: false,
+    offline: false,
+    paused_gids: None,
+});
+
+/// True while downloads are held for any reason; new downloads should be added paused
+pub fn is_auto_paused() -> bool {
+    STATE.lock().unwrap().paused_gids.is_some()
+}
+
+/// Remembers a download that was added paused, so it resumes with the rest
+pub fn track_auto_paused(gid: &str) {
+    if let Some(paused) = STATE.lock().unwrap().paused_gids.as_mut() {
+        paused.push(gid.to_string());
+    }
+}
+
+/// Turns one pause condition on or off, pausing everything when the first condition
+/// appears and resuming what we paused once none are left. Returns true if this call
+/// paused the downloads.
+pub async fn set_pause_condition(
+    client: &Aria2Client,
+    reason: PauseReason,
+    active: bool,
+) -> Result<bool, AppError> {
+    let (should_pause, to_resume) = {
+        let mut state = STATE.lock().unwrap();
+        match reason {
+            PauseReason::Metered => state.metered = active,
+            PauseReason::LowBattery => state.low_battery = active,
+            PauseReason::Sleep => state.sleeping = active,
+            PauseReason::Offline => state.offline = active,
+        }
+
+        if state.wants_pause() {
+            (state.paused_gids.is_none(), None)
+        } else {
+            (false, state.paused_gids.take())
+        }
+    };
+
+    if should_pause {
+        let mut running: Vec<String> = client
+            .tell_active()
+            .await?
+            .into_iter()
+            .map(|s| s.gid)
+            .collect();
+        // All of them: `pause_all` pauses every one, and only these are resumed
+        running.extend(
+            client
+                .tell_all_waiting()
+                .await?
+                .into_iter()
+                .filter(|s| DownloadStatus::from(s.status.as_str()) == DownloadStatus::Waiting)
+                .map(|s| s.gid),
+        );
+
+        client.pause_all().await?;
+        STATE.lock().unwrap().paused_gids = Some(running);
+        return Ok(true);
+    }
+
+    for gid in to_resume.unwrap_or_default() {
+        // It may have been removed or resumed by hand in the meantime
+        let _ = client.unpause(&gid).await;
+    }
+
+    Ok(false)
+}
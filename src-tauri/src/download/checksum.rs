@@ -0,0 +1,213 @@
+use crate::error::AppError;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Event emitted with a `ChecksumProgress` while a file is being hashed
+pub const CHECKSUM_PROGRESS_EVENT: &str = "checksum-progress";
+
+/// Large reads keep the disk busy; two of them in flight let reading and hashing overlap
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+const CHUNKS_IN_FLIGHT: usize = 2;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecksumProgress {
+    pub path: String,
+    pub hashed_bytes: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecksumResult {
+    pub matched: bool,
+    pub actual: String,
+}
+
+/// Cancel flags of the verifications in progress, keyed by file path
+static CANCEL_FLAGS: LazyLock<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Registers a verification of `path` so `cancel_checksum` can stop it
+pub fn begin_checksum(path: &str) -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    CANCEL_FLAGS
+        .lock()
+        .unwrap()
+        .insert(path.to_string(), flag.clone());
+    flag
+}
+
+pub fn finish_checksum(path: &str) {
+    CANCEL_FLAGS.lock().unwrap().remove(path);
+}
+
+/// Asks the verification of `path` to stop; false if none is running
+pub fn cancel_checksum(path: &str) -> bool {
+    match CANCEL_FLAGS.lock().unwrap().get(path) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// SHA-256 of the file as lowercase hex. A reader thread fills buffers while this
+/// thread hashes the previous one, so a large file is bound by the disk rather than
+/// by doing both in turn. `on_progress` gets the bytes hashed so far and the file size.
+pub fn sha256_file(
+    path: &Path,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<String, AppError> {
+    let mut file = File::open(path)
+        .map_err(|e| AppError::io(format!("Failed to open {}: {}", path.display(), e)))?;
+    let total = file.metadata()?.len();
+
+    let (full_tx, full_rx) = mpsc::sync_channel::<std::io::Result<Vec<u8>>>(CHUNKS_IN_FLIGHT);
+    let (empty_tx, empty_rx) = mpsc::channel::<Vec<u8>>();
+    for _ in 0..CHUNKS_IN_FLIGHT {
+        let _ = empty_tx.send(vec![0; CHUNK_SIZE]);
+    }
+
+    let reader = std::thread::spawn(move || {
+        // Stops as soon as the hasher drops its ends of the channels
+        while let Ok(mut buffer) = empty_rx.recv() {
+            buffer.resize(CHUNK_SIZE, 0);
+            let read = read_full(&mut file, &mut buffer);
+            let done = !matches!(read, Ok(n) if n > 0);
+            let chunk = read.map(|n| {
+                buffer.truncate(n);
+                buffer
+            });
+            if full_tx.send(chunk).is_err() || done {
+                break;
+            }
+        }
+    });
+
+    let mut hasher = Sha256::new();
+    let mut hashed = 0u64;
+    let result = loop {
+        if cancel.load(Ordering::Relaxed) {
+            break Err(AppError::Cancelled {
+                message: format!("Verification of {} was cancelled", path.display()),
+            });
+        }
+        let chunk = match full_rx.recv() {
+            Ok(Ok(chunk)) => chunk,
+            Ok(Err(e)) => break Err(AppError::io(format!("Failed to read {}: {}", path.display(), e))),
+            Err(_) => break Err(AppError::io("Checksum reader stopped unexpectedly")),
+        };
+        if chunk.is_empty() {
+            break Ok(hex::encode(hasher.finalize()));
+        }
+
+        hasher.update(&chunk);
+        hashed += chunk.len() as u64;
+        on_progress(hashed, total);
+        let _ = empty_tx.send(chunk);
+    };
+
+    drop(empty_tx);
+    drop(full_rx);
+    let _ = reader.join();
+    result
+}
+
+/// Fills `buffer` unless the file ends first, so chunks stay large on short reads
+fn read_full(file: &mut File, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Instant;
+
+    fn temp_file(size: usize) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("trauso-checksum-{}", uuid::Uuid::new_v4()));
+        let mut file = File::create(&path).unwrap();
+        let block: Vec<u8> = (0..CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+        let mut remaining = size;
+        while remaining > 0 {
+            let n = remaining.min(block.len());
+            file.write_all(&block[..n]).unwrap();
+            remaining -= n;
+        }
+        path
+    }
+
+    #[test]
+    fn test_sha256_file_matches_single_pass_digest() {
+        // Not a multiple of the chunk size, so the last chunk is short
+        let path = temp_file(CHUNK_SIZE * 2 + 12345);
+        let expected = hex::encode(Sha256::digest(std::fs::read(&path).unwrap()));
+
+        let mut last = (0, 0);
+        let actual = sha256_file(&path, &AtomicBool::new(false), |hashed, total| last = (hashed, total));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(actual.unwrap(), expected);
+        assert_eq!(last, (CHUNK_SIZE as u64 * 2 + 12345, CHUNK_SIZE as u64 * 2 + 12345));
+    }
+
+    #[test]
+    fn test_sha256_file_stops_when_cancelled() {
+        let path = temp_file(CHUNK_SIZE * 4);
+        let cancel = AtomicBool::new(false);
+        let result = sha256_file(&path, &cancel, |_, _| cancel.store(true, Ordering::Relaxed));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(AppError::Cancelled { .. })));
+    }
+
+    /// `cargo test --release -- --ignored --nocapture bench_sha256_file_throughput`
+    #[test]
+    #[ignore]
+    fn bench_sha256_file_throughput() {
+        let size = 1024 * 1024 * 1024;
+        let path = temp_file(size);
+
+        let start = Instant::now();
+        sha256_file(&path, &AtomicBool::new(false), |_, _| {}).unwrap();
+        let pipelined = start.elapsed();
+
+        let start = Instant::now();
+        let mut file = File::open(&path).unwrap();
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut hasher = Sha256::new();
+        loop {
+            let n = read_full(&mut file, &mut buffer).unwrap();
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+        }
+        let sequential = start.elapsed();
+        std::fs::remove_file(&path).unwrap();
+
+        let mib = (size / (1024 * 1024)) as f64;
+        println!(
+            "pipelined: {:.0} MiB/s, sequential: {:.0} MiB/s",
+            mib / pipelined.as_secs_f64(),
+            mib / sequential.as_secs_f64()
+        );
+    }
+}
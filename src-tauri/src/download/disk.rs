@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Result of `check_download_dir`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirCheck {
+    pub writable: bool,
+    /// Whether the directory had to be created
+    pub created: bool,
+    /// Space available to the current user, when the platform can tell
+    pub free_bytes: Option<u64>,
+}
+
+/// Makes sure `dir` exists and that we can write into it, by creating and deleting a
+/// probe file, so a read-only pick fails up front instead of inside aria2
+pub fn check_download_dir(dir: &Path) -> DirCheck {
+    let existed = dir.is_dir();
+    let created = !existed && fs::create_dir_all(dir).is_ok();
+
+    let probe = dir.join(format!(".trauso-write-test-{}", uuid::Uuid::new_v4()));
+    let writable = fs::write(&probe, b"").is_ok();
+    if writable {
+        let _ = fs::remove_file(&probe);
+    }
+
+    DirCheck {
+        writable,
+        created,
+        free_bytes: free_bytes(dir),
+    }
+}
+
+/// Space available to the current user on `dir`'s volume. A folder that doesn't exist
+/// yet is measured at its closest existing parent.
+pub fn free_space(dir: &Path) -> Option<u64> {
+    free_bytes(dir.ancestors().find(|dir| dir.exists())?)
+}
+
+#[cfg(unix)]
+fn free_bytes(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+fn free_bytes(dir: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            free_bytes_available: *mut u64,
+            total_bytes: *mut u64,
+            total_free_bytes: *mut u64,
+        ) -> i32;
+    }
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut())
+    };
+
+    (ok != 0).then_some(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn free_bytes(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_download_dir_creates_missing_dir() {
+        let root = std::env::temp_dir().join(format!("trauso-test-{}", uuid::Uuid::new_v4()));
+        let dir = root.join("nested").join("downloads");
+
+        let check = check_download_dir(&dir);
+        assert!(check.created);
+        assert!(check.writable);
+        assert!(dir.is_dir());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
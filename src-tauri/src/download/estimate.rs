@@ -0,0 +1,105 @@
+use crate::aria2::{Aria2Client, GlobalStats};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Per-download speed samples kept, one per completion poll: about five minutes
+const THROUGHPUT_WINDOW: usize = 150;
+
+/// Recent speed of a single download, in bytes/s: the total speed split across the
+/// downloads running at the time
+static SAMPLES: Mutex<VecDeque<u64>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchEstimate {
+    /// Best case, from the faster end of recent speeds
+    pub low_secs: u64,
+    /// Worst case, from the slower end
+    pub high_secs: u64,
+    /// Projected combined speeds behind `high_secs` and `low_secs`, in bytes/s
+    pub slow_bytes_per_sec: u64,
+    pub fast_bytes_per_sec: u64,
+    /// How many speed samples the estimate is based on
+    pub samples: usize,
+}
+
+/// Records what each active download is getting right now; idle polls are skipped
+/// so the history only reflects time spent downloading
+pub async fn record_throughput(client: &Aria2Client) {
+    let Ok(stat) = client.get_global_stat().await else {
+        return;
+    };
+    let stats = GlobalStats::from(stat);
+    if stats.num_active == 0 {
+        return;
+    }
+
+    let mut samples = SAMPLES.lock().unwrap();
+    if samples.len() == THROUGHPUT_WINDOW {
+        samples.pop_front();
+    }
+    samples.push_back(stats.download_speed / stats.num_active as u64);
+}
+
+pub fn throughput_samples() -> Vec<u64> {
+    SAMPLES.lock().unwrap().iter().copied().collect()
+}
+
+/// Projects how long `total_bytes` takes with `concurrency` downloads at once, bounded
+/// by the 25th and 75th percentile of recent per-download speed. `download_cap` and
+/// `overall_cap` are aria2's limits in bytes/s, 0 for none. None without any samples.
+pub fn estimate_batch_time(
+    total_bytes: u64,
+    concurrency: u32,
+    download_cap: u64,
+    overall_cap: u64,
+    samples: &[u64],
+) -> Option<BatchEstimate> {
+    let mut sorted: Vec<u64> = samples.iter().copied().filter(|s| *s > 0).collect();
+    if sorted.is_empty() || concurrency == 0 {
+        return None;
+    }
+    sorted.sort_unstable();
+    let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+
+    let combined = |per_download: u64| {
+        let per_download = if download_cap > 0 { per_download.min(download_cap) } else { per_download };
+        let total = per_download.saturating_mul(concurrency as u64);
+        if overall_cap > 0 { total.min(overall_cap) } else { total }
+    };
+    let slow = combined(percentile(25));
+    let fast = combined(percentile(75));
+
+    Some(BatchEstimate {
+        low_secs: total_bytes.div_ceil(fast),
+        high_secs: total_bytes.div_ceil(slow),
+        slow_bytes_per_sec: slow,
+        fast_bytes_per_sec: fast,
+        samples: sorted.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_batch_time_bounds_and_caps() {
+        const MB: u64 = 1024 * 1024;
+        let samples = [0, MB, 2 * MB, 2 * MB, 4 * MB, 8 * MB];
+
+        let estimate = estimate_batch_time(600 * MB, 3, 0, 0, &samples).unwrap();
+        assert_eq!(estimate.samples, 5);
+        assert_eq!(estimate.slow_bytes_per_sec, 6 * MB);
+        assert_eq!(estimate.fast_bytes_per_sec, 12 * MB);
+        assert_eq!((estimate.low_secs, estimate.high_secs), (50, 100));
+
+        // The overall cap flattens both ends
+        let capped = estimate_batch_time(600 * MB, 3, 0, 5 * MB, &samples).unwrap();
+        assert_eq!((capped.low_secs, capped.high_secs), (120, 120));
+        let per_download = estimate_batch_time(600 * MB, 3, MB, 0, &samples).unwrap();
+        assert_eq!(per_download.fast_bytes_per_sec, 3 * MB);
+
+        assert_eq!(estimate_batch_time(MB, 3, 0, 0, &[0, 0]), None);
+    }
+}
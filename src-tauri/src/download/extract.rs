@@ -0,0 +1,292 @@
+use crate::error::AppError;
+use serde::Serialize;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Event emitted with an `ExtractProgress` while an archive is being unpacked
+pub const EXTRACT_PROGRESS_EVENT: &str = "extract-progress";
+/// Event emitted with an `ExtractFailure` when an automatic extraction fails
+pub const EXTRACT_FAILED_EVENT: &str = "extract-failed";
+
+/// Extensions unpacked: .zip with the `zip` crate, the others with 7-Zip
+const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "rar", "7z"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractProgress {
+    pub gid: String,
+    pub percent: u8,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractFailure {
+    pub gid: String,
+    /// `password_required` means the UI should ask and call `extract_download` again
+    pub error: AppError,
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| ARCHIVE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// The bundled 7-Zip next to the app, or one on the PATH. `7za` can't read rar,
+/// so it's only a last resort.
+fn find_7z() -> Result<PathBuf, AppError> {
+    let possible_paths = [
+        PathBuf::from("7z/7z.exe"),
+        PathBuf::from("../7z/7z.exe"),
+        PathBuf::from("_internal/7z/7z.exe"),
+    ];
+    for path in &possible_paths {
+        if path.exists() {
+            return Ok(path.clone());
+        }
+    }
+
+    let names = ["7z", "7zz", "7za"];
+    for name in names {
+        if Command::new(name).stdout(Stdio::null()).stderr(Stdio::null()).status().is_ok() {
+            return Ok(PathBuf::from(name));
+        }
+    }
+
+    let mut searched_paths = possible_paths.to_vec();
+    searched_paths.extend(names.iter().map(PathBuf::from));
+    Err(AppError::BinaryNotFound { searched_paths })
+}
+
+/// Sibling folder named after the archive, e.g. `movies.zip` -> `movies/`
+pub fn extract_dir_for(archive: &Path) -> PathBuf {
+    let stem = archive
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "extracted".to_string());
+    archive.with_file_name(stem)
+}
+
+/// Unpacks `archive` into its sibling folder, returning that folder. Blocks until it's
+/// done, so call it off the async runtime. Existing files there are kept and the
+/// extracted ones renamed.
+pub fn extract_archive(
+    archive: &Path,
+    password: Option<&str>,
+    mut on_progress: impl FnMut(u8),
+) -> Result<PathBuf, AppError> {
+    if extension(archive).as_deref() == Some("zip") {
+        return extract_zip(archive, password, on_progress);
+    }
+
+    let seven_zip = find_7z()?;
+    let out_dir = extract_dir_for(archive);
+
+    let mut cmd = Command::new(seven_zip);
+    cmd.arg("x")
+        .arg(archive)
+        .arg(format!("-o{}", out_dir.display()))
+        // Always pass one: without it 7-Zip waits for a password on stdin
+        .arg(format!("-p{}", password.unwrap_or_default()))
+        .args(["-aou", "-y", "-bso0", "-bsp1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| AppError::io(format!("Failed to start 7-Zip: {}", e)))?;
+
+    let mut stderr = child.stderr.take();
+    let stderr_reader = std::thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(stderr) = stderr.as_mut() {
+            let _ = stderr.read_to_string(&mut text);
+        }
+        text
+    });
+
+    // Progress is redrawn in place with backspaces, so split on those as well as newlines
+    if let Some(stdout) = child.stdout.take() {
+        let mut reader = BufReader::new(stdout);
+        let mut last = None;
+        let mut segment = Vec::new();
+        loop {
+            segment.clear();
+            match reader.read_until(b'\x08', &mut segment) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let text = String::from_utf8_lossy(&segment);
+                    for percent in text.split(['\r', '\n']).filter_map(parse_percent) {
+                        if last != Some(percent) {
+                            last = Some(percent);
+                            on_progress(percent);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| AppError::io(format!("Failed to wait for 7-Zip: {}", e)))?;
+    let errors = stderr_reader.join().unwrap_or_default();
+
+    if status.success() {
+        return Ok(out_dir);
+    }
+    if errors.contains("Wrong password") {
+        let message = if password.is_some() {
+            "Wrong password"
+        } else {
+            "Archive is password protected"
+        };
+        return Err(AppError::PasswordRequired { message: message.to_string() });
+    }
+
+    let detail = errors.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+    Err(AppError::io(format!("7-Zip failed ({}): {}", status, detail)))
+}
+
+/// `extract_archive` for a .zip, which needs no 7-Zip
+fn extract_zip(archive: &Path, password: Option<&str>, mut on_progress: impl FnMut(u8)) -> Result<PathBuf, AppError> {
+    let out_dir = extract_dir_for(archive);
+    let file = fs::File::open(archive)
+        .map_err(|e| AppError::io(format!("Failed to open {}: {}", archive.display(), e)))?;
+    let mut zip = zip::ZipArchive::new(BufReader::new(file)).map_err(|e| zip_error(e, password))?;
+
+    let total = zip.decompressed_size().unwrap_or(0).max(1);
+    let mut written: u128 = 0;
+    let mut last = None;
+    let mut buf = vec![0u8; 64 * 1024];
+    for index in 0..zip.len() {
+        let entry = match password {
+            Some(password) => zip.by_index_decrypt(index, password.as_bytes()),
+            None => zip.by_index(index),
+        };
+        let mut entry = entry.map_err(|e| zip_error(e, password))?;
+        // A name with `..` or a root would land outside `out_dir`
+        let Some(name) = entry.enclosed_name() else {
+            log::warn!("Skipped unsafe path {:?} in {}", entry.name(), archive.display());
+            continue;
+        };
+        let target = out_dir.join(name);
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut out = fs::File::create(unused_path(target))?;
+        loop {
+            let n = entry
+                .read(&mut buf)
+                .map_err(|e| AppError::io(format!("Failed to extract {}: {}", entry.name(), e)))?;
+            if n == 0 {
+                break;
+            }
+            out.write_all(&buf[..n])?;
+            written += n as u128;
+            let percent = (written * 100 / total).min(100) as u8;
+            if last != Some(percent) {
+                last = Some(percent);
+                on_progress(percent);
+            }
+        }
+    }
+    // Skipped entries count toward the total but are never written
+    if last != Some(100) {
+        on_progress(100);
+    }
+    Ok(out_dir)
+}
+
+fn zip_error(error: zip::result::ZipError, password: Option<&str>) -> AppError {
+    match error {
+        zip::result::ZipError::InvalidPassword => AppError::PasswordRequired {
+            message: "Wrong password".to_string(),
+        },
+        zip::result::ZipError::UnsupportedArchive(zip::result::ZipError::PASSWORD_REQUIRED) => {
+            let message = if password.is_some() { "Wrong password" } else { "Archive is password protected" };
+            AppError::PasswordRequired { message: message.to_string() }
+        }
+        e => AppError::io(format!("Failed to read zip: {}", e)),
+    }
+}
+
+/// `path`, or the first free `name_1.ext`, `name_2.ext`... next to it, like 7-Zip's `-aou`
+fn unused_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let ext = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{}_{}{}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .expect("some numbered name is free")
+}
+
+/// Reads `42%` out of a 7-Zip progress line like ` 42% 13 - movie.mkv`
+fn parse_percent(line: &str) -> Option<u8> {
+    let (number, _) = line.trim_start().split_once('%')?;
+    number.parse::<u8>().ok().filter(|p| *p <= 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_percent_and_archive_detection() {
+        assert_eq!(parse_percent("  42% 13 - movie.mkv"), Some(42));
+        assert_eq!(parse_percent("100%"), Some(100));
+        assert_eq!(parse_percent("Extracting archive: a.zip"), None);
+
+        assert!(is_archive(Path::new("/dl/Movies.ZIP")));
+        assert!(!is_archive(Path::new("/dl/movie.mkv")));
+        assert_eq!(extract_dir_for(Path::new("/dl/movies.rar")), PathBuf::from("/dl/movies"));
+    }
+
+    #[test]
+    fn test_zip_extracts_without_7z() {
+        let dir = std::env::temp_dir().join(format!("trauso-zip-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("movies.zip");
+        {
+            let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+            let options = zip::write::SimpleFileOptions::default();
+            zip.start_file("a.txt", options).unwrap();
+            zip.write_all(b"top").unwrap();
+            zip.start_file("extras/b.txt", options).unwrap();
+            zip.write_all(b"nested").unwrap();
+            zip.start_file("../escape.txt", options).unwrap();
+            zip.write_all(b"outside").unwrap();
+            zip.finish().unwrap();
+        }
+
+        let mut progress = Vec::new();
+        let out_dir = extract_archive(&archive, None, |percent| progress.push(percent)).unwrap();
+        assert_eq!(out_dir, dir.join("movies"));
+        assert_eq!(fs::read_to_string(out_dir.join("a.txt")).unwrap(), "top");
+        assert_eq!(fs::read_to_string(out_dir.join("extras/b.txt")).unwrap(), "nested");
+        assert!(!dir.join("escape.txt").exists());
+        assert_eq!(progress.last(), Some(&100));
+
+        // A second run keeps the first one's files
+        extract_archive(&archive, None, |_| {}).unwrap();
+        assert_eq!(fs::read_to_string(out_dir.join("a_1.txt")).unwrap(), "top");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+/// Longest filename, in bytes, most filesystems accept
+const MAX_FILENAME_BYTES: usize = 255;
+
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Makes a Terabox filename safe to create on any platform: characters Windows rejects
+/// become `_`, trailing dots/spaces are trimmed, reserved device names are prefixed and
+/// overly long names are shortened while keeping the extension.
+pub fn sanitize_filename(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let mut sanitized = replaced.trim().trim_end_matches(['.', ' ']).to_string();
+    if sanitized.is_empty() {
+        return "download".to_string();
+    }
+
+    let stem = sanitized.split('.').next().unwrap_or_default();
+    if WINDOWS_RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        sanitized.insert(0, '_');
+    }
+
+    truncate_keeping_extension(&sanitized, MAX_FILENAME_BYTES)
+}
+
+fn truncate_keeping_extension(name: &str, max_bytes: usize) -> String {
+    if name.len() <= max_bytes {
+        return name.to_string();
+    }
+
+    let (stem, extension) = match name.rfind('.') {
+        // Only treat short suffixes as an extension worth keeping
+        Some(dot) if dot > 0 && name.len() - dot <= 16 => name.split_at(dot),
+        _ => (name, ""),
+    };
+
+    let mut cut = max_bytes.saturating_sub(extension.len());
+    while !stem.is_char_boundary(cut) {
+        cut -= 1;
+    }
+
+    format!("{}{}", stem[..cut].trim_end_matches(['.', ' ']), extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("Movie: Part 1?.mp4"), "Movie_ Part 1_.mp4");
+        assert_eq!(sanitize_filename("notes.txt. . "), "notes.txt");
+        assert_eq!(sanitize_filename("con.txt"), "_con.txt");
+        assert_eq!(sanitize_filename("..."), "download");
+        assert_eq!(sanitize_filename("plain.zip"), "plain.zip");
+
+        let long = format!("{}.mkv", "é".repeat(200));
+        let sanitized = sanitize_filename(&long);
+        assert!(sanitized.len() <= MAX_FILENAME_BYTES);
+        assert!(sanitized.ends_with(".mkv"));
+    }
+}
@@ -0,0 +1,114 @@
+use serde::Serialize;
+use std::process::Stdio;
+use std::time::Duration;
+
+/// Event emitted with a `HookResult` when a post-download command exits or times out
+pub const POST_HOOK_FINISHED_EVENT: &str = "post-hook-finished";
+/// Event emitted with the command when a hook is set up but the user hasn't confirmed it yet
+pub const POST_HOOK_APPROVAL_EVENT: &str = "post-hook-needs-approval";
+
+/// A hook still running after this long is killed
+pub const POST_HOOK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookResult {
+    pub gid: String,
+    /// The command as run, with `{path}` filled in
+    pub command: String,
+    pub success: bool,
+    /// Missing when the command was killed or couldn't be started
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub error: Option<String>,
+}
+
+/// Fills `{path}` in `template` with the quoted file path
+pub fn hook_command(template: &str, path: &str) -> String {
+    template.replace("{path}", &shell_quote(path))
+}
+
+/// Runs the hook through the platform shell and waits for it at most `POST_HOOK_TIMEOUT`.
+/// Its output isn't captured; the hook is expected to log on its own if it needs to.
+pub async fn run_post_download_hook(template: &str, path: &str, gid: &str) -> HookResult {
+    let command = hook_command(template, path);
+    let mut result = HookResult {
+        gid: gid.to_string(),
+        command: command.clone(),
+        success: false,
+        exit_code: None,
+        timed_out: false,
+        error: None,
+    };
+
+    let mut child = match shell(&command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            result.error = Some(format!("Failed to start hook: {}", e));
+            return result;
+        }
+    };
+
+    match tokio::time::timeout(POST_HOOK_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) => {
+            result.success = status.success();
+            result.exit_code = status.code();
+        }
+        Ok(Err(e)) => result.error = Some(format!("Failed to wait for hook: {}", e)),
+        Err(_) => {
+            let _ = child.kill().await;
+            result.timed_out = true;
+            result.error = Some(format!(
+                "Hook didn't finish within {} seconds",
+                POST_HOOK_TIMEOUT.as_secs()
+            ));
+        }
+    }
+
+    result
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> tokio::process::Command {
+    let mut shell = tokio::process::Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> tokio::process::Command {
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let mut shell = tokio::process::Command::new("cmd");
+    shell.arg("/C").raw_arg(command).creation_flags(CREATE_NO_WINDOW);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Windows paths can't contain `"`, so wrapping is enough
+#[cfg(windows)]
+fn shell_quote(value: &str) -> String {
+    format!("\"{}\"", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_hook_command_quotes_path() {
+        assert_eq!(
+            hook_command("unzip {path} -d /tmp", "/dl/it's here.zip"),
+            r"unzip '/dl/it'\''s here.zip' -d /tmp"
+        );
+    }
+}
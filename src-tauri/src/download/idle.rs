@@ -0,0 +1,74 @@
+use crate::aria2::Aria2Client;
+use crate::download::record_finished_downloads;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often the idle supervisor checks whether the daemon has anything to do
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+struct IdleState {
+    /// Last time a download was added, resumed or seen in progress
+    last_activity: Option<Instant>,
+    /// Set when we shut the daemon down for being idle, so the next download brings it back
+    stopped_for_idle: bool,
+}
+
+static STATE: Mutex<IdleState> = Mutex::new(IdleState {
+    last_activity: None,
+    stopped_for_idle: false,
+});
+
+/// Resets the idle timer
+pub fn touch_activity() {
+    STATE.lock().unwrap().last_activity = Some(Instant::now());
+}
+
+/// Forgets an idle shutdown, once the daemon is back or the user stopped it by hand
+pub fn clear_idle_shutdown() {
+    STATE.lock().unwrap().stopped_for_idle = false;
+}
+
+/// True after an idle shutdown until the daemon is started again
+pub fn is_idle_shutdown() -> bool {
+    STATE.lock().unwrap().stopped_for_idle
+}
+
+/// Stops the daemon once nothing has been active or waiting for `idle_after`.
+/// Only a daemon this app spawned is stopped; a remote or externally started one is left alone.
+/// Returns true if it was stopped.
+pub async fn stop_if_idle(client: &Aria2Client, idle_after: Duration) -> bool {
+    if !client.owns_daemon() {
+        return false;
+    }
+
+    let active = client.tell_active().await.map(|a| !a.is_empty());
+    let waiting = client.tell_waiting(0, 1).await.map(|w| !w.is_empty());
+    let busy = match (active, waiting) {
+        (Ok(active), Ok(waiting)) => active || waiting,
+        // Can't tell, so don't risk stopping a busy daemon
+        _ => true,
+    };
+
+    let idle_for = {
+        let mut state = STATE.lock().unwrap();
+        let now = Instant::now();
+        if busy {
+            state.last_activity = Some(now);
+        }
+        now - *state.last_activity.get_or_insert(now)
+    };
+    if idle_for < idle_after {
+        return false;
+    }
+
+    // Stopped results live only in the daemon, so get them into the history first
+    record_finished_downloads(client).await;
+    if let Err(e) = client.stop_daemon().await {
+        log::warn!("Failed to stop idle aria2: {}", e);
+        return false;
+    }
+
+    log::info!("Stopped aria2 after {}s without downloads", idle_for.as_secs());
+    STATE.lock().unwrap().stopped_for_idle = true;
+    true
+}
@@ -0,0 +1,31 @@
+mod api;
+mod auto_pause;
+mod checksum;
+mod disk;
+mod estimate;
+mod extract;
+mod filename;
+mod hook;
+mod idle;
+mod network;
+mod queue;
+mod report;
+mod schedule;
+mod sequence;
+mod stall;
+
+pub use api::*;
+pub use auto_pause::*;
+pub use checksum::*;
+pub use disk::*;
+pub use estimate::*;
+pub use extract::*;
+pub use filename::*;
+pub use hook::*;
+pub use idle::*;
+pub use network::*;
+pub use queue::*;
+pub use report::*;
+pub use schedule::*;
+pub use sequence::*;
+pub use stall::*;
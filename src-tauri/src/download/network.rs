@@ -0,0 +1,52 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Event emitted with a `RetryReport` after downloads that failed while offline were retried
+pub const RECONNECT_RETRIED_EVENT: &str = "downloads-retried-on-reconnect";
+
+/// GIDs that had already failed when the connection dropped; `Some` while offline
+static ERRORED_AT_DISCONNECT: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Remembers which downloads had failed before the disconnect. Returns false if we
+/// already knew we were offline, in which case the first snapshot stands.
+pub fn mark_offline(errored: impl IntoIterator<Item = String>) -> bool {
+    let mut state = ERRORED_AT_DISCONNECT.lock().unwrap();
+    if state.is_some() {
+        return false;
+    }
+    *state = Some(errored.into_iter().collect());
+    true
+}
+
+pub fn is_offline() -> bool {
+    ERRORED_AT_DISCONNECT.lock().unwrap().is_some()
+}
+
+/// The downloads in `errored` that failed while offline, so the disconnect is to blame.
+/// Empty if we never saw the connection drop.
+pub fn mark_online(errored: Vec<String>) -> Vec<String> {
+    let Some(before) = ERRORED_AT_DISCONNECT.lock().unwrap().take() else {
+        return Vec::new();
+    };
+    errored.into_iter().filter(|gid| !before.contains(gid)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_downloads_failed_while_offline_are_retried() {
+        let gids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        assert!(mark_online(gids(&["a"])).is_empty());
+
+        assert!(mark_offline(gids(&["old"])));
+        // A second notice doesn't move the baseline
+        assert!(!mark_offline(gids(&["old", "a"])));
+        assert!(is_offline());
+
+        assert_eq!(mark_online(gids(&["old", "a", "b"])), gids(&["a", "b"]));
+        assert!(!is_offline());
+        assert!(mark_online(gids(&["c"])).is_empty());
+    }
+}
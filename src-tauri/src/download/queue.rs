@@ -0,0 +1,289 @@
+use crate::aria2::DownloadInfo;
+use crate::error::AppError;
+use crate::settings::api::{get_config_dir, write_atomic};
+use crate::terabox::FileCategory;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often the queue's `last_known` progress is brought up to date
+pub const PROGRESS_SAVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// aria2's limit on connections per server
+const MAX_CONNECTIONS: u32 = 16;
+/// Most `split` a priority scales up to, whatever `split_count` is stored
+const MAX_SPLIT: u32 = 64;
+
+/// Coarse control over a download's place in aria2's queue and its connection count
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Goes to the end of the queue with a quarter of the connections
+    Low,
+    #[default]
+    Normal,
+    /// Goes to the front of the queue with twice the connections and split. aria2 caps
+    /// connections at 16, so from the default `max_connections` of 16 only split grows.
+    High,
+}
+
+impl Priority {
+    /// `max-connection-per-server` for this priority, starting from the usual count
+    pub fn connections(self, base: u32) -> u32 {
+        match self {
+            Priority::Low => (base / 4).max(1),
+            Priority::Normal => base,
+            Priority::High => base.saturating_mul(2).min(MAX_CONNECTIONS),
+        }
+    }
+
+    /// `split` for this priority, starting from the usual count
+    pub fn split(self, base: u32) -> u32 {
+        match self {
+            Priority::Low => (base / 4).max(1),
+            Priority::Normal => base,
+            Priority::High => base.saturating_mul(2).min(MAX_SPLIT),
+        }
+    }
+}
+
+/// App-level metadata of a queued download, which aria2's own session file can't hold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub gid: String,
+    pub share_url: Option<String>,
+    pub category: Option<FileCategory>,
+    pub group_id: Option<String>,
+    /// RFC 3339
+    pub added_at: String,
+    #[serde(default)]
+    pub priority: Priority,
+    /// aria2's view as of the last save, so a relaunch can show progress before the
+    /// daemon reports again. Speeds are zeroed, as they're stale by then.
+    #[serde(default)]
+    pub last_known: Option<DownloadInfo>,
+}
+
+/// A `QueueEntry` with aria2's current view of it, if the daemon was reachable
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedItem {
+    #[serde(flatten)]
+    pub entry: QueueEntry,
+    pub download: Option<DownloadInfo>,
+}
+
+/// Format version of the files `export_queue` writes
+pub const QUEUE_FILE_VERSION: u32 = 1;
+
+/// A queue exported to a portable `.trauso` file. It carries the shares rather than
+/// resolved direct links, which expire within hours, so `import_queue` resolves each
+/// one again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueFile {
+    pub version: u32,
+    /// RFC 3339
+    pub exported_at: String,
+    pub downloads: Vec<SharedDownload>,
+}
+
+/// One download of a `QueueFile`, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedDownload {
+    pub share_url: String,
+    /// Finds the file again even if it was in a subfolder of the share
+    pub fs_id: Option<String>,
+    pub filename: String,
+    /// Where it was being saved on the exporting machine; default folder if unknown
+    pub dir: Option<String>,
+    pub category: Option<FileCategory>,
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+/// Outcome of `import_queue`; failures are reported per share
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueueImportReport {
+    /// GIDs of the re-added downloads
+    pub gids: Vec<String>,
+    pub failed: Vec<super::BatchFailure>,
+}
+
+pub fn write_queue_file(path: &Path, file: &QueueFile) -> Result<(), AppError> {
+    let content = serde_json::to_string_pretty(file)
+        .map_err(|e| format!("Failed to serialize queue: {}", e))?;
+    Ok(write_atomic(path, &content)?)
+}
+
+pub fn read_queue_file(path: &Path) -> Result<QueueFile, AppError> {
+    let content = fs::read_to_string(path)?;
+    let file: QueueFile = serde_json::from_str(&content)
+        .map_err(|e| AppError::invalid_input(format!("Not a Trauso queue file: {}", e)))?;
+    if file.version > QUEUE_FILE_VERSION {
+        return Err(AppError::invalid_input(format!(
+            "Queue file version {} needs a newer version of Trauso",
+            file.version
+        )));
+    }
+    Ok(file)
+}
+
+/// Oldest first, mirrored to queue.json
+static QUEUE: Mutex<Option<Vec<QueueEntry>>> = Mutex::new(None);
+
+fn queue_path() -> PathBuf {
+    get_config_dir().join("queue.json")
+}
+
+/// Runs `f` on the queue, loading it first, and saves it if `f` returns true
+fn with_queue<T>(f: impl FnOnce(&mut Vec<QueueEntry>) -> (T, bool)) -> T {
+    let mut guard = QUEUE.lock().unwrap();
+    let queue = guard.get_or_insert_with(|| {
+        fs::read_to_string(queue_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    });
+
+    let (result, changed) = f(queue);
+    if changed {
+        let saved = serde_json::to_string_pretty(queue)
+            .map_err(|e| e.to_string())
+            .and_then(|content| write_atomic(&queue_path(), &content).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            log::warn!("Failed to save download queue: {}", e);
+        }
+    }
+    result
+}
+
+pub fn enqueue(entry: QueueEntry) {
+    with_queue(|queue| {
+        queue.retain(|e| e.gid != entry.gid);
+        queue.push(entry);
+        ((), true)
+    })
+}
+
+pub fn dequeue(gids: &[String]) {
+    with_queue(|queue| {
+        let before = queue.len();
+        queue.retain(|e| !gids.contains(&e.gid));
+        ((), queue.len() != before)
+    })
+}
+
+/// Drops entries for GIDs aria2 no longer knows, e.g. after a restart without a session
+pub fn reconcile_queue(known: &HashSet<String>) -> usize {
+    with_queue(|queue| {
+        let before = queue.len();
+        queue.retain(|e| known.contains(&e.gid));
+        let dropped = before - queue.len();
+        (dropped, dropped > 0)
+    })
+}
+
+/// Returns false if `gid` isn't queued
+pub fn set_queue_priority(gid: &str, priority: Priority) -> bool {
+    with_queue(|queue| match queue.iter_mut().find(|e| e.gid == gid) {
+        Some(entry) => {
+            let changed = entry.priority != priority;
+            entry.priority = priority;
+            (true, changed)
+        }
+        None => (false, false),
+    })
+}
+
+/// Updates `last_known` for the queued downloads in `downloads`, saving only if one
+/// of them moved on
+pub fn record_last_known(downloads: &[DownloadInfo]) {
+    with_queue(|queue| {
+        let mut changed = false;
+        for entry in queue.iter_mut() {
+            let Some(info) = downloads.iter().find(|d| d.gid == entry.gid) else {
+                continue;
+            };
+            let moved = entry.last_known.as_ref().is_none_or(|known| {
+                (known.downloaded, known.total_size, &known.status)
+                    != (info.downloaded, info.total_size, &info.status)
+            });
+            if moved {
+                entry.last_known = Some(DownloadInfo {
+                    speed: 0,
+                    average_speed: 0,
+                    upload_speed: 0,
+                    eta_seconds: None,
+                    connections: 0,
+                    ..info.clone()
+                });
+                changed = true;
+            }
+        }
+        ((), changed)
+    })
+}
+
+pub fn queue_entries() -> Vec<QueueEntry> {
+    with_queue(|queue| (queue.clone(), false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_scales_connections() {
+        assert_eq!(Priority::Normal.connections(8), 8);
+        assert_eq!(Priority::High.connections(8), 16);
+        assert_eq!(Priority::High.connections(16), 16);
+        assert_eq!(Priority::Low.connections(8), 2);
+        assert_eq!(Priority::Low.connections(2), 1);
+        assert_eq!(Priority::High.split(16), 32);
+        assert_eq!(Priority::High.split(u32::MAX), MAX_SPLIT);
+        assert_eq!(Priority::High.connections(u32::MAX), MAX_CONNECTIONS);
+
+        // Entries saved before priorities and progress existed still load
+        let entry: QueueEntry = serde_json::from_str(
+            r#"{"gid":"a","share_url":null,"category":null,"group_id":null,"added_at":"2024-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.priority, Priority::Normal);
+        assert!(entry.last_known.is_none());
+    }
+
+    #[test]
+    fn test_queue_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("trauso-queue-file-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("batch.trauso");
+
+        let file = QueueFile {
+            version: QUEUE_FILE_VERSION,
+            exported_at: "2024-01-01T00:00:00Z".to_string(),
+            downloads: vec![SharedDownload {
+                share_url: "https://terabox.com/s/1abc".to_string(),
+                fs_id: Some("42".to_string()),
+                filename: "movie.mkv".to_string(),
+                dir: None,
+                category: Some(FileCategory::Video),
+                priority: Priority::High,
+            }],
+        };
+        write_queue_file(&path, &file).unwrap();
+        let read = read_queue_file(&path).unwrap();
+        assert_eq!(read.downloads.len(), 1);
+        assert_eq!(read.downloads[0].fs_id.as_deref(), Some("42"));
+        assert_eq!(read.downloads[0].priority, Priority::High);
+
+        // Files from a newer app, or that aren't queue files at all, are refused
+        fs::write(&path, r#"{"version":99,"exported_at":"","downloads":[]}"#).unwrap();
+        assert!(matches!(read_queue_file(&path), Err(AppError::InvalidInput { .. })));
+        fs::write(&path, "[]").unwrap();
+        assert!(matches!(read_queue_file(&path), Err(AppError::InvalidInput { .. })));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+use crate::aria2::{DownloadInfo, DownloadOrigin};
+use std::fmt::Write;
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+/// Plain-text summary of a download for pasting into a bug report
+pub fn format_download_report(info: &DownloadInfo, origin: Option<&DownloadOrigin>) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "File: {}", info.filename);
+    let _ = writeln!(text, "GID: {}", info.gid);
+    let _ = writeln!(text, "Status: {:?}", info.status);
+    let size = if info.total_size > 0 { format_bytes(info.total_size) } else { "unknown".to_string() };
+    let _ = writeln!(
+        text,
+        "Progress: {} of {} ({:.1}%)",
+        format_bytes(info.downloaded),
+        size,
+        info.progress
+    );
+    let _ = writeln!(text, "Speed: {}/s", format_bytes(info.speed));
+    if let Some(path) = &info.path {
+        let _ = writeln!(text, "Path: {}", path);
+    }
+    if let Some(share_url) = origin.and_then(|o| o.share_url.as_deref()) {
+        let _ = writeln!(text, "Share: {}", share_url);
+    }
+    if let Some(source_uri) = &info.source_uri {
+        let _ = writeln!(text, "Source: {}", source_uri);
+    }
+    if let Some(error) = &info.error_message {
+        let _ = writeln!(text, "Error: {}", error);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aria2::DownloadStatus;
+
+    #[test]
+    fn test_download_report_lists_the_error() {
+        let info = DownloadInfo {
+            gid: "2089b05ecca3d829".to_string(),
+            filename: "movie.mkv".to_string(),
+            path: Some("/downloads/movie.mkv".to_string()),
+            total_size: 3 * 1024 * 1024,
+            downloaded: 1024 * 1024,
+            speed: 0,
+            average_speed: 0,
+            progress: 33.33,
+            status: DownloadStatus::Error,
+            error_message: Some("HTTP 403".to_string()),
+            eta_seconds: None,
+            connections: 0,
+            uploaded: 0,
+            upload_speed: 0,
+            seeders: 0,
+            source_uri: None,
+            created_time: None,
+            completed_time: None,
+        };
+        let origin = DownloadOrigin {
+            share_url: Some("https://terabox.com/s/1abc".to_string()),
+            ..DownloadOrigin::default()
+        };
+
+        assert_eq!(
+            format_download_report(&info, Some(&origin)),
+            "File: movie.mkv\n\
+             GID: 2089b05ecca3d829\n\
+             Status: Error\n\
+             Progress: 1.00 MiB of 3.00 MiB (33.3%)\n\
+             Speed: 0 B/s\n\
+             Path: /downloads/movie.mkv\n\
+             Share: https://terabox.com/s/1abc\n\
+             Error: HTTP 403\n"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::aria2::Aria2Client;
+use crate::error::AppError;
+use crate::settings::types::{AppSettings, BandwidthRule};
+use chrono::{Datelike, Timelike};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Event emitted with an `ActiveBandwidthLimit` whenever the schedule changes the limit
+pub const BANDWIDTH_LIMIT_CHANGED_EVENT: &str = "bandwidth-limit-changed";
+
+/// How often the schedule is re-evaluated; rules have minute resolution
+pub const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActiveBandwidthLimit {
+    /// 0 means unlimited
+    pub limit_kb_per_sec: u64,
+    /// The rule in effect, or `None` for the default limit from the settings
+    pub rule_id: Option<String>,
+}
+
+/// What the schedule last applied, so a tick that changes nothing stays quiet
+static APPLIED: Mutex<Option<ActiveBandwidthLimit>> = Mutex::new(None);
+
+/// The first rule covering `now`
+pub fn active_rule(rules: &[BandwidthRule], now: impl Datelike + Timelike) -> Option<&BandwidthRule> {
+    let weekday = now.weekday().num_days_from_monday();
+    let minute = now.hour() * 60 + now.minute();
+    rules.iter().find(|rule| rule.matches(weekday, minute))
+}
+
+/// Applies the limit the schedule calls for right now. Returns it if it differs from
+/// the last one applied; the caller emits `BANDWIDTH_LIMIT_CHANGED_EVENT` then.
+pub async fn apply_bandwidth_schedule(
+    client: &Aria2Client,
+    settings: &AppSettings,
+) -> Result<Option<ActiveBandwidthLimit>, AppError> {
+    let limit = match active_rule(&settings.bandwidth_schedule, chrono::Local::now()) {
+        Some(rule) => ActiveBandwidthLimit {
+            limit_kb_per_sec: rule.limit_kb_per_sec,
+            rule_id: Some(rule.id.clone()),
+        },
+        None => ActiveBandwidthLimit {
+            limit_kb_per_sec: settings.max_overall_download_limit_kb_per_sec,
+            rule_id: None,
+        },
+    };
+    if APPLIED.lock().unwrap().as_ref() == Some(&limit) {
+        return Ok(None);
+    }
+
+    // Also covers the next daemon start
+    client.set_bandwidth_limit(limit.limit_kb_per_sec, settings.max_download_limit_kb_per_sec);
+    if client.is_running().await {
+        client
+            .change_global_option(
+                "max-overall-download-limit",
+                &format!("{}K", limit.limit_kb_per_sec),
+            )
+            .await?;
+        client.apply_download_shares().await;
+    }
+
+    *APPLIED.lock().unwrap() = Some(limit.clone());
+    Ok(Some(limit))
+}
+
+/// Forgets what was applied, so the next check re-applies even an unchanged limit
+pub fn reset_bandwidth_schedule() {
+    *APPLIED.lock().unwrap() = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn rule(id: &str, weekdays: u8, start: &str, end: &str) -> BandwidthRule {
+        BandwidthRule {
+            id: id.to_string(),
+            weekdays,
+            start: start.to_string(),
+            end: end.to_string(),
+            limit_kb_per_sec: 2048,
+        }
+    }
+
+    #[test]
+    fn test_active_rule_by_weekday_and_time() {
+        const WEEKDAYS: u8 = 0b0011111;
+        let rules = [rule("office", WEEKDAYS, "08:00", "18:00"), rule("night", 0b1000000, "22:00", "06:00")];
+        // 2026-10-12 is a Monday
+        let at = |day: u32, h: u32, m: u32| NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(h, m, 0).unwrap();
+        let active = |day, h, m| active_rule(&rules, at(day, h, m)).map(|r| r.id.as_str());
+
+        assert_eq!(active(12, 8, 0), Some("office"));
+        assert_eq!(active(12, 17, 59), Some("office"));
+        assert_eq!(active(12, 18, 0), None);
+        assert_eq!(active(17, 12, 0), None);
+        // Sunday night runs into Monday morning
+        assert_eq!(active(18, 23, 30), Some("night"));
+        assert_eq!(active(19, 5, 59), Some("night"));
+        assert_eq!(active(19, 6, 0), None);
+        assert_eq!(active(18, 5, 0), None);
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+/// How `add_downloads_batch` runs the downloads it adds
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchMode {
+    /// All at once, up to aria2's `max-concurrent-downloads`
+    #[default]
+    Parallel,
+    /// One at a time, in the order given, so each gets the whole bandwidth
+    Sequential,
+}
+
+/// A sequential batch: `gids[current]` runs, the ones after it wait paused
+#[derive(Debug)]
+struct Sequence {
+    gids: Vec<String>,
+    current: usize,
+}
+
+/// Sequential batches by group ID. Kept in memory only: after a restart the remaining
+/// downloads stay paused until resumed.
+static SEQUENCES: LazyLock<Mutex<HashMap<String, Sequence>>> = LazyLock::new(Mutex::default);
+
+/// Starts tracking a sequential batch and returns the GID to start first
+pub fn start_sequence(group_id: &str, gids: Vec<String>) -> Option<String> {
+    let first = gids.first().cloned()?;
+    SEQUENCES
+        .lock()
+        .unwrap()
+        .insert(group_id.to_string(), Sequence { gids, current: 0 });
+    Some(first)
+}
+
+/// Moves past every running download in `stopped` (complete, failed or removed) and
+/// returns the GIDs to start next. Finished sequences are dropped.
+pub fn advance_sequences(stopped: &[String]) -> Vec<String> {
+    let mut sequences = SEQUENCES.lock().unwrap();
+    let mut next = Vec::new();
+    sequences.retain(|_, sequence| {
+        if !stopped.contains(&sequence.gids[sequence.current]) {
+            return true;
+        }
+        sequence.current += 1;
+        match sequence.gids.get(sequence.current) {
+            Some(gid) => {
+                next.push(gid.clone());
+                true
+            }
+            None => false,
+        }
+    });
+    next
+}
+
+/// Stops tracking a batch, e.g. once it's cancelled
+pub fn end_sequence(group_id: &str) {
+    SEQUENCES.lock().unwrap().remove(group_id);
+}
+
+/// Keeps a sequence going when one of its downloads is replaced, e.g. by a retry
+pub fn replace_in_sequence(old_gid: &str, new_gid: &str) {
+    for sequence in SEQUENCES.lock().unwrap().values_mut() {
+        if let Some(gid) = sequence.gids.iter_mut().find(|gid| *gid == old_gid) {
+            *gid = new_gid.to_string();
+        }
+    }
+}
+
+/// The download a sequential batch is running; None for parallel batches
+pub fn sequence_current(group_id: &str) -> Option<String> {
+    let sequences = SEQUENCES.lock().unwrap();
+    let sequence = sequences.get(group_id)?;
+    sequence.gids.get(sequence.current).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_starts_one_download_at_a_time() {
+        let gids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        assert_eq!(start_sequence("seq-test", gids(&["a", "b", "c"])), Some("a".to_string()));
+        assert_eq!(sequence_current("seq-test").as_deref(), Some("a"));
+
+        // A download that isn't the running one doesn't move things along
+        assert!(advance_sequences(&gids(&["c"])).is_empty());
+        assert_eq!(advance_sequences(&gids(&["a"])), gids(&["b"]));
+
+        replace_in_sequence("b", "b2");
+        assert_eq!(sequence_current("seq-test").as_deref(), Some("b2"));
+        assert_eq!(advance_sequences(&gids(&["b2"])), gids(&["c"]));
+        assert!(advance_sequences(&gids(&["c"])).is_empty());
+        assert_eq!(sequence_current("seq-test"), None);
+    }
+}
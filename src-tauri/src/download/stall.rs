@@ -0,0 +1,101 @@
+use crate::aria2::Aria2Client;
+use std::collections::{HashMap, HashSet};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Emitted once per download with its GID when it has been at 0 B/s for `stall_timeout_secs`
+pub const DOWNLOAD_STALLED_EVENT: &str = "download-stalled";
+
+/// How often active downloads are checked for stalls
+pub const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+struct StallTracker {
+    /// When each active download was first seen at 0 B/s in its current run of zeros
+    zero_since: HashMap<String, Instant>,
+    stalled: HashSet<String>,
+}
+
+impl StallTracker {
+    /// Takes the speed of every active download and returns the ones that just crossed
+    /// `timeout`. Downloads that picked up speed or stopped being active are forgotten.
+    fn observe(&mut self, active: &[(String, u64)], now: Instant, timeout: Duration) -> Vec<String> {
+        let zero: HashSet<&str> = active
+            .iter()
+            .filter(|(_, speed)| *speed == 0)
+            .map(|(gid, _)| gid.as_str())
+            .collect();
+        self.zero_since.retain(|gid, _| zero.contains(gid.as_str()));
+        self.stalled.retain(|gid| zero.contains(gid.as_str()));
+
+        let mut newly_stalled = Vec::new();
+        for gid in zero {
+            let since = *self.zero_since.entry(gid.to_string()).or_insert(now);
+            if now - since >= timeout && self.stalled.insert(gid.to_string()) {
+                newly_stalled.push(gid.to_string());
+            }
+        }
+        newly_stalled
+    }
+}
+
+static TRACKER: LazyLock<Mutex<StallTracker>> = LazyLock::new(Mutex::default);
+
+/// Checks the daemon's active downloads and returns the GIDs that have just stalled.
+/// Nothing is reported while the daemon can't be reached.
+pub async fn check_stalls(client: &Aria2Client, timeout: Duration) -> Vec<String> {
+    let Ok(active) = client.tell_active().await else {
+        return Vec::new();
+    };
+    let speeds: Vec<(String, u64)> = active
+        .into_iter()
+        .map(|status| {
+            let speed = status.download_speed.and_then(|s| s.parse().ok()).unwrap_or(0);
+            (status.gid, speed)
+        })
+        .collect();
+
+    TRACKER.lock().unwrap().observe(&speeds, Instant::now(), timeout)
+}
+
+/// Forgets every stall, e.g. when the check is switched off
+pub fn clear_stalls() {
+    *TRACKER.lock().unwrap() = StallTracker::default();
+}
+
+pub fn stalled_downloads() -> Vec<String> {
+    let mut stalled: Vec<String> = TRACKER.lock().unwrap().stalled.iter().cloned().collect();
+    stalled.sort();
+    stalled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_reported_once_and_cleared_by_progress() {
+        let mut tracker = StallTracker::default();
+        let timeout = Duration::from_secs(60);
+        let start = Instant::now();
+        let speeds = |a: u64, b: u64| vec![("a".to_string(), a), ("b".to_string(), b)];
+
+        assert!(tracker.observe(&speeds(0, 100), start, timeout).is_empty());
+        assert!(tracker.observe(&speeds(0, 0), start + Duration::from_secs(30), timeout).is_empty());
+        assert_eq!(tracker.observe(&speeds(0, 0), start + timeout, timeout), vec!["a"]);
+        // Still stuck, but already reported
+        assert!(tracker.observe(&speeds(0, 0), start + Duration::from_secs(70), timeout).is_empty());
+        assert_eq!(tracker.observe(&speeds(0, 0), start + Duration::from_secs(90), timeout), vec!["b"]);
+
+        // Any progress restarts the clock
+        tracker.observe(&speeds(10, 0), start + Duration::from_secs(95), timeout);
+        assert_eq!(tracker.stalled, HashSet::from(["b".to_string()]));
+        let later = start + Duration::from_secs(100);
+        assert!(tracker.observe(&speeds(0, 0), later, timeout).is_empty());
+        assert_eq!(tracker.observe(&speeds(0, 0), later + timeout, timeout), vec!["a"]);
+
+        // Downloads that are no longer active drop out
+        tracker.observe(&[], later + timeout, timeout);
+        assert!(tracker.stalled.is_empty() && tracker.zero_since.is_empty());
+    }
+}
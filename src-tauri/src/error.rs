@@ -0,0 +1,125 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Error returned by the aria2 and terabox modules and surfaced to the frontend.
+///
+/// Serialized as `{ "kind": "...", ...fields }` so the UI can branch on `kind`,
+/// while `Display` keeps the plain message for code that only shows text.
+#[derive(Debug, Clone, Serialize, Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AppError {
+    #[error("{message}")]
+    Network { message: String },
+
+    #[error("aria2 error: {message} (code: {code})")]
+    Aria2Rpc { code: i64, message: String },
+
+    #[error("{message}")]
+    TeraboxAuth { message: String },
+
+    /// Terabox rejected the saved login; the user has to log in again
+    #[error("{message}")]
+    SessionExpired { message: String },
+
+    #[error("{message}")]
+    NotFound { message: String },
+
+    #[error("{message}")]
+    Io { message: String },
+
+    #[error("{message}")]
+    InvalidResponse { message: String },
+
+    #[error("{message}")]
+    InvalidInput { message: String },
+
+    #[error("{message}")]
+    Cancelled { message: String },
+
+    /// The archive is encrypted and no password, or the wrong one, was given
+    #[error("{message}")]
+    PasswordRequired { message: String },
+
+    /// Over what a free Terabox account may download; a premium account's login is needed.
+    /// `size` is the file's, when Terabox says.
+    #[error("File is too large for a free Terabox account, which can download up to {limit} bytes")]
+    FileTooLarge { size: Option<u64>, limit: u64 },
+
+    #[error("Download already exists (gid: {gid})")]
+    DownloadExists { gid: String },
+
+    #[error("Already downloaded: {}", path.display())]
+    AlreadyDownloaded { path: PathBuf },
+
+    #[error("aria2c not found")]
+    BinaryNotFound { searched_paths: Vec<PathBuf> },
+
+    #[error("{message}")]
+    Other { message: String },
+}
+
+impl AppError {
+    pub fn network(message: impl Into<String>) -> Self {
+        AppError::Network { message: message.into() }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound { message: message.into() }
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        AppError::Io { message: message.into() }
+    }
+
+    pub fn invalid_response(message: impl Into<String>) -> Self {
+        AppError::InvalidResponse { message: message.into() }
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        AppError::InvalidInput { message: message.into() }
+    }
+
+    /// Prefixes the message while keeping the error kind
+    pub fn context(self, prefix: &str) -> Self {
+        let prefixed = |message: String| format!("{}: {}", prefix, message);
+        match self {
+            AppError::Network { message } => AppError::Network { message: prefixed(message) },
+            AppError::Aria2Rpc { code, message } => AppError::Aria2Rpc { code, message: prefixed(message) },
+            AppError::TeraboxAuth { message } => AppError::TeraboxAuth { message: prefixed(message) },
+            AppError::SessionExpired { message } => AppError::SessionExpired { message: prefixed(message) },
+            AppError::NotFound { message } => AppError::NotFound { message: prefixed(message) },
+            AppError::Io { message } => AppError::Io { message: prefixed(message) },
+            AppError::InvalidResponse { message } => AppError::InvalidResponse { message: prefixed(message) },
+            AppError::InvalidInput { message } => AppError::InvalidInput { message: prefixed(message) },
+            AppError::Cancelled { message } => AppError::Cancelled { message: prefixed(message) },
+            AppError::PasswordRequired { message } => AppError::PasswordRequired { message: prefixed(message) },
+            AppError::Other { message } => AppError::Other { message: prefixed(message) },
+            other => other,
+        }
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other { message }
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::Other { message: message.to_string() }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        AppError::io(error.to_string())
+    }
+}
+
+impl From<AppError> for String {
+    fn from(error: AppError) -> Self {
+        error.to_string()
+    }
+}
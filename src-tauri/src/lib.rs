@@ -1,180 +1,2110 @@
 mod aria2;
+mod download;
+mod error;
+mod logging;
 mod settings;
 mod terabox;
+mod update;
+mod window;
 
-use aria2::{Aria2Client, Aria2Options, DownloadInfo};
-use settings::types::AppSettings;
-use terabox::{DownloadLink, DownloadParams, TeraboxApi, TeraboxInfo};
-use std::sync::LazyLock;
+use aria2::{
+    AddDownloadOptions, Aria2Client, Aria2ConfigCheck, Aria2Options, Aria2Ping, Aria2Version,
+    DownloadFile, DownloadInfo, DownloadOrigin, DownloadPage, DownloadServer, DownloadStatus,
+    FileAllocation, GlobalStats, GroupProgress, OverallProgress, OverwritePolicy,
+};
+use error::AppError;
+use settings::types::{
+    AppSettings, BandwidthPreset, BandwidthRule, ConfigFileState, ConfigRepair, HistoryStatsReport, SavedBandwidthLimit,
+};
+use terabox::{
+    DirectUrl, DownloadLink, DownloadParams, FileCategory, FolderListing, FolderSize,
+    LinkBenchmark, LinkHealth, TeraboxApi, TeraboxInfo,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tauri::{Emitter, Manager};
+use tauri_plugin_opener::OpenerExt;
 use tauri_plugin_store::StoreExt;
 
 static TERABOX_API: LazyLock<TeraboxApi> = LazyLock::new(TeraboxApi::new);
-static ARIA2_CLIENT: LazyLock<Mutex<Aria2Client>> = LazyLock::new(|| {
-    Mutex::new(Aria2Client::new(
+/// Shared without a lock: its methods take `&self` and keep their state behind their own
+/// mutexes, so a slow RPC doesn't hold up other commands
+static ARIA2_CLIENT: LazyLock<Aria2Client> = LazyLock::new(|| {
+    Aria2Client::new(
         "http://localhost:6800/jsonrpc",
         0,
         0,
-    ))
+    )
 });
+/// Held for the whole of `add_download`, so two adds of the same file can't both get past
+/// the duplicate check
+static ADD_LOCK: Mutex<()> = Mutex::const_new(());
+/// How long `resume_after_wake` waits for aria2 to answer again
+const WAKE_RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The "settings" store as `settings::api` reads and writes it
+struct PluginSettingsStore(tauri::AppHandle);
+
+impl settings::api::SettingsStore for PluginSettingsStore {
+    fn get(&self, key: &str) -> Option<serde_json::Value> {
+        settings_store(&self.0).get(key)
+    }
+
+    fn set(&self, key: &str, value: serde_json::Value) {
+        settings_store(&self.0).set(key, value);
+        if let Err(e) = persist_settings_store(&self.0) {
+            log::warn!("Failed to save settings: {}", e);
+        }
+    }
+}
+
+fn open_settings_store(handle: &tauri::AppHandle) {
+    settings::api::init_settings_store(|| Arc::new(PluginSettingsStore(handle.clone())));
+}
+
+/// The "settings" store, which also holds the profiles and the window state. Its
+/// auto-save is off: the plugin saves with an in-place write a crash can tear, so every
+/// change goes through `persist_settings_store` instead.
+fn settings_store(handle: &tauri::AppHandle) -> Arc<tauri_plugin_store::Store<tauri::Wry>> {
+    handle.store_builder("settings").disable_auto_save().build().unwrap()
+}
+
+/// Writes the whole settings store out atomically
+fn persist_settings_store(handle: &tauri::AppHandle) -> Result<(), AppError> {
+    let entries: serde_json::Map<String, serde_json::Value> = settings_store(handle).entries().into_iter().collect();
+    let content = serde_json::to_string_pretty(&entries)
+        .map_err(|e| AppError::from(format!("Failed to serialize the settings store: {}", e)))?;
+    let path = settings_store_path(handle)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(settings::api::write_settings_store(&path, &content)?)
+}
+
+fn get_settings(handle: &tauri::AppHandle) -> AppSettings {
+    open_settings_store(handle);
+    settings::api::load_settings()
+}
+
+fn save_settings(handle: &tauri::AppHandle, settings: &AppSettings) -> Result<(), AppError> {
+    open_settings_store(handle);
+    Ok(settings::api::save_settings(settings)?)
+}
+
+fn settings_store_path(handle: &tauri::AppHandle) -> Result<std::path::PathBuf, AppError> {
+    tauri_plugin_store::resolve_store_path(handle, "settings")
+        .map_err(|e| AppError::io(format!("Failed to locate the settings store: {}", e)))
+}
+
+fn get_profiles(handle: &tauri::AppHandle) -> HashMap<String, AppSettings> {
+    let store = settings_store(handle);
+    match store.get("profiles") {
+        Some(serde_json::Value::Object(profiles)) => profiles
+            .into_iter()
+            .map(|(name, value)| (name, settings::api::migrate(value)))
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+fn save_profiles(handle: &tauri::AppHandle, profiles: &HashMap<String, AppSettings>) -> Result<(), AppError> {
+    let value = serde_json::to_value(profiles)
+        .map_err(|e| AppError::from(format!("Failed to serialize profiles: {}", e)))?;
+    settings_store(handle).set("profiles", value);
+    persist_settings_store(handle)
+}
+
+fn get_active_profile_name(handle: &tauri::AppHandle) -> Option<String> {
+    let store = settings_store(handle);
+    store.get("active_profile").and_then(|v| v.as_str().map(str::to_string))
+}
+
+fn set_active_profile_name(handle: &tauri::AppHandle, name: Option<&str>) -> Result<(), AppError> {
+    let store = settings_store(handle);
+    match name {
+        Some(name) => store.set("active_profile", name),
+        None => {
+            store.delete("active_profile");
+        }
+    }
+    persist_settings_store(handle)
+}
+
+fn profile_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::invalid_input("Profile name can't be empty"));
+    }
+    Ok(name.to_string())
+}
+
+fn apply_settings(client: &Aria2Client, settings: &AppSettings) {
+    client.set_rpc_endpoint(&settings.rpc_url, settings.rpc_secret.clone());
+    client.set_bandwidth_limit(
+        settings.max_overall_download_limit_kb_per_sec,
+        settings.max_download_limit_kb_per_sec,
+    );
+    client.set_extra_args(settings.extra_aria2_args.clone());
+    client.set_seed_ratio(settings.seed_ratio);
+    client.set_max_concurrent_downloads(settings.max_concurrent_downloads);
+    client.set_file_allocation(
+        settings
+            .file_allocation
+            .effective_for(std::path::Path::new(&settings.download_dir)),
+    );
+    client.set_overwrite_policy(settings.overwrite_policy);
+    client.set_start_up_timeout(Duration::from_secs(settings.aria2_startup_timeout_secs));
+    client.set_disable_ipv6(settings.disable_ipv6);
+    client.set_async_dns_server(settings.async_dns_server.clone());
+    client.set_connect_timeout(settings.connect_timeout_secs);
+    client.set_lowest_speed_limit(settings.lowest_speed_limit.clone());
+    client.set_keep_on_exit(settings.keep_daemon_on_exit);
+    client.set_log_to_file(settings.log_to_file);
+    TERABOX_API.set_link_cache_ttl(Duration::from_secs(settings.link_cache_ttl_secs));
+    TERABOX_API.set_max_concurrency(settings.terabox_max_concurrency);
+}
+
+/// A 403 from the CDN means the cached direct link went stale
+fn forget_rejected_link(info: &DownloadInfo) {
+    let forbidden = info.status == DownloadStatus::Error
+        && info.error_message.as_deref().is_some_and(|m| m.contains("403"));
+    if let (true, Some(uri)) = (forbidden, info.source_uri.as_deref()) {
+        TERABOX_API.invalidate_link(uri);
+    }
+}
+
+/// Unpacks `archive` off the async runtime, emitting progress for `gid`, and removes the
+/// archive afterwards if the user asked for that
+async fn extract_and_report(
+    handle: tauri::AppHandle,
+    gid: String,
+    archive: std::path::PathBuf,
+    password: Option<String>,
+) -> Result<std::path::PathBuf, AppError> {
+    let delete_after = get_settings(&handle).delete_after_extract;
+    tauri::async_runtime::spawn_blocking(move || {
+        let out_dir = download::extract_archive(&archive, password.as_deref(), |percent| {
+            let progress = download::ExtractProgress { gid: gid.clone(), percent };
+            let _ = handle.emit(download::EXTRACT_PROGRESS_EVENT, progress);
+        })?;
+        if delete_after {
+            if let Err(e) = std::fs::remove_file(&archive) {
+                log::warn!("Failed to delete {} after extracting: {}", archive.display(), e);
+            }
+        }
+        Ok(out_dir)
+    })
+    .await
+    .map_err(|e| AppError::from(format!("Extraction task failed: {}", e)))?
+}
+
+fn auto_extract(handle: &tauri::AppHandle, finished: &[DownloadInfo]) {
+    if !get_settings(handle).auto_extract {
+        return;
+    }
+
+    for info in finished.iter().filter(|info| info.status == DownloadStatus::Complete) {
+        let Some(archive) = info.path.as_deref().map(std::path::PathBuf::from) else {
+            continue;
+        };
+        if !download::is_archive(&archive) {
+            continue;
+        }
+
+        let handle = handle.clone();
+        let gid = info.gid.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(error) = extract_and_report(handle.clone(), gid.clone(), archive, None).await {
+                log::warn!("Failed to extract {}: {}", gid, error);
+                let _ = handle.emit(download::EXTRACT_FAILED_EVENT, download::ExtractFailure { gid, error });
+            }
+        });
+    }
+}
+
+/// Applies the scheduled bandwidth limit and tells the UI if it changed
+async fn apply_bandwidth_schedule(handle: &tauri::AppHandle) {
+    let settings = get_settings(handle);
+    let client = &*ARIA2_CLIENT;
+    match download::apply_bandwidth_schedule(client, &settings).await {
+        Ok(Some(limit)) => {
+            let _ = handle.emit(download::BANDWIDTH_LIMIT_CHANGED_EVENT, limit);
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to apply bandwidth schedule: {}", e),
+    }
+}
+
+/// Runs the user's post-download hook for each completed download, without waiting for it
+fn run_post_download_hooks(handle: &tauri::AppHandle, finished: Vec<DownloadInfo>) {
+    let paths: Vec<(String, String)> = finished
+        .into_iter()
+        .filter(|info| info.status == DownloadStatus::Complete)
+        .filter_map(|info| Some((info.gid, info.path?)))
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+
+    let settings = get_settings(handle);
+    let Some(template) = settings.post_download_hook() else {
+        if settings.enable_post_download_hooks && settings.post_download_command.is_some() {
+            let _ = handle.emit(download::POST_HOOK_APPROVAL_EVENT, &settings.post_download_command);
+        }
+        return;
+    };
+
+    for (gid, path) in paths {
+        let handle = handle.clone();
+        let template = template.to_string();
+        tauri::async_runtime::spawn(async move {
+            let result = download::run_post_download_hook(&template, &path, &gid).await;
+            if !result.success {
+                log::warn!("Post-download hook for {} failed: {:?}", gid, result.error);
+            }
+            let _ = handle.emit(download::POST_HOOK_FINISHED_EVENT, result);
+        });
+    }
+}
+
+#[tauri::command]
+async fn get_terabox_info(url: String) -> Result<TeraboxInfo, AppError> {
+    TERABOX_API.get_info(&url).await
+}
+
+#[tauri::command]
+async fn list_folder(handle: tauri::AppHandle, url: String) -> Result<FolderListing, AppError> {
+    TERABOX_API
+        .list_folder(&url, |progress| {
+            let _ = handle.emit(terabox::FOLDER_SCAN_PROGRESS_EVENT, progress);
+        })
+        .await
+}
+
+/// Total size of every file in a share, with the free space in `dir` (the download
+/// folder by default) to compare it against. Progress comes as `folder-scan-progress`
+/// events and `cancel_folder_scan` stops it early, with `complete` false.
+#[tauri::command]
+async fn get_folder_size(
+    handle: tauri::AppHandle,
+    url: String,
+    dir: Option<String>,
+) -> Result<FolderSize, AppError> {
+    let listing = list_folder(handle.clone(), url).await?;
+    let dir = dir.unwrap_or_else(|| get_settings(&handle).download_dir);
+    let free_bytes = download::free_space(std::path::Path::new(&dir));
+    Ok(FolderSize::from_listing(&listing).with_free_space(free_bytes))
+}
+
+#[tauri::command]
+fn cancel_folder_scan(url: String) -> bool {
+    TERABOX_API.cancel_folder_scan(&url)
+}
+
+/// Logs in with the `ndus` cookie of a Terabox account; every later request sends it
+#[tauri::command]
+fn login_terabox(cookie: String) -> Result<(), AppError> {
+    let cookie = terabox::normalize_session_cookie(&cookie)?;
+    terabox::save_session_cookie(&cookie)?;
+    TERABOX_API.set_session_cookie(Some(cookie));
+    Ok(())
+}
+
+#[tauri::command]
+fn logout_terabox() -> Result<(), AppError> {
+    terabox::clear_session_cookie()?;
+    TERABOX_API.set_session_cookie(None);
+    Ok(())
+}
+
+#[tauri::command]
+fn is_terabox_logged_in() -> bool {
+    TERABOX_API.is_logged_in()
+}
+
+#[tauri::command]
+fn get_terabox_max_concurrency() -> usize {
+    TERABOX_API.max_concurrency()
+}
+
+/// Saves and applies the cap on Terabox API requests in flight at once
+#[tauri::command]
+fn set_terabox_max_concurrency(handle: tauri::AppHandle, limit: usize) -> Result<(), AppError> {
+    let mut settings = get_settings(&handle);
+    settings.terabox_max_concurrency = limit;
+    settings.validate().map_err(AppError::invalid_input)?;
+    save_settings(&handle, &settings)?;
+    TERABOX_API.set_max_concurrency(limit);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_download_link(params: DownloadParams) -> Result<DownloadLink, AppError> {
+    TERABOX_API.get_download_link(params).await
+}
+
+#[tauri::command]
+async fn resolve_direct_url(handle: tauri::AppHandle, params: DownloadParams) -> Result<DirectUrl, AppError> {
+    let settings = get_settings(&handle);
+    TERABOX_API.resolve_direct_url(params, &settings.user_agent).await
+}
+
+#[tauri::command]
+async fn get_thumbnail(url: String) -> Result<Option<String>, AppError> {
+    TERABOX_API.get_thumbnail(&url).await
+}
+
+#[tauri::command]
+async fn check_link_health(url: String) -> Result<LinkHealth, AppError> {
+    TERABOX_API.check_link_health(&url).await
+}
+
+#[tauri::command]
+async fn benchmark_link(url: String, duration_secs: u64) -> Result<LinkBenchmark, AppError> {
+    if !(1..=60).contains(&duration_secs) {
+        return Err(AppError::invalid_input("Benchmark duration must be between 1 and 60 seconds"));
+    }
+    TERABOX_API
+        .benchmark_link(&url, Duration::from_secs(duration_secs))
+        .await
+}
+
+/// Projects how long `total_bytes` takes from recent download speeds, with at most
+/// `concurrency` (default: the concurrent download setting) running at once
+#[tauri::command]
+async fn estimate_batch_time(
+    handle: tauri::AppHandle,
+    total_bytes: u64,
+    concurrency: Option<u32>,
+) -> Result<download::BatchEstimate, AppError> {
+    if concurrency == Some(0) {
+        return Err(AppError::invalid_input("Concurrency must be at least 1"));
+    }
+    let settings = get_settings(&handle);
+    let concurrency = concurrency
+        .unwrap_or(settings.max_concurrent_downloads)
+        .min(settings.max_concurrent_downloads);
+    // The client holds whatever limit is in effect, scheduled or not
+    let (overall_kb, download_kb) = ARIA2_CLIENT.get_bandwidth_limit();
+
+    download::estimate_batch_time(
+        total_bytes,
+        concurrency,
+        download_kb * 1024,
+        overall_kb * 1024,
+        &download::throughput_samples(),
+    )
+    .ok_or_else(|| AppError::not_found("No recent download speeds to estimate from yet"))
+}
+
+#[tauri::command]
+fn clear_link_cache() {
+    TERABOX_API.clear_link_cache();
+}
+
+#[tauri::command]
+fn extract_shorturl(url: String) -> Option<String> {
+    TeraboxApi::extract_shorturl(&url)
+}
+
+#[tauri::command]
+async fn start_aria2(handle: tauri::AppHandle) -> Result<(), AppError> {
+    let settings = get_settings(&handle);
+    {
+        let client = &*ARIA2_CLIENT;
+        apply_settings(client, &settings);
+        client.start_daemon().await?;
+    }
+    download::clear_idle_shutdown();
+    download::touch_activity();
+
+    // `apply_settings` put back the default limit; reapply any scheduled one
+    download::reset_bandwidth_schedule();
+    apply_bandwidth_schedule(&handle).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn download_aria2_binary() -> Result<String, AppError> {
+    let path = Aria2Client::install_binary().await?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn stop_aria2() -> Result<(), AppError> {
+    let client = &*ARIA2_CLIENT;
+    // Stopped on purpose, so don't bring it back on the next download
+    download::clear_idle_shutdown();
+    client.stop_daemon().await
+}
+
+#[tauri::command]
+async fn is_aria2_running() -> bool {
+    let client = &*ARIA2_CLIENT;
+    client.is_running().await
+}
+
+#[tauri::command]
+async fn is_aria2_remote() -> bool {
+    let client = &*ARIA2_CLIENT;
+    client.is_remote()
+}
+
+#[tauri::command]
+async fn get_aria2_version() -> Result<Aria2Version, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.get_version().await
+}
+
+#[tauri::command]
+async fn ping_aria2() -> Aria2Ping {
+    let client = &*ARIA2_CLIENT;
+    client.ping().await
+}
+
+/// Checks that aria2c accepts the saved settings and `extra_aria2_args`, using a
+/// throwaway daemon, so a typo shows up here rather than as a failed start or download
+#[tauri::command]
+async fn validate_aria2_config(handle: tauri::AppHandle) -> Result<Aria2ConfigCheck, AppError> {
+    let settings = get_settings(&handle);
+    let client = Aria2Client::new(&settings.rpc_url, 0, 0);
+    apply_settings(&client, &settings);
+    let per_download = [
+        ("max-connection-per-server", settings.max_connections.to_string()),
+        ("split", settings.split_count.to_string()),
+        ("min-split-size", settings.min_split_size.clone()),
+        ("user-agent", settings.user_agent.clone()),
+    ];
+    client.validate_config(&per_download).await
+}
+
+#[tauri::command]
+async fn get_aria2_logs(max_lines: Option<usize>) -> Vec<String> {
+    let client = &*ARIA2_CLIENT;
+    client.get_logs(max_lines.unwrap_or(200))
+}
+
+#[tauri::command]
+async fn set_rpc_endpoint(
+    handle: tauri::AppHandle,
+    url: String,
+    secret: Option<String>,
+) -> Result<(), AppError> {
+    reqwest::Url::parse(&url)
+        .map_err(|e| AppError::invalid_input(format!("Invalid RPC URL: {}", e)))?;
+
+    let client = &*ARIA2_CLIENT;
+    if client.get_rpc_url() != url {
+        // Release a local daemon we spawned before pointing somewhere else
+        client.stop_daemon().await?;
+    }
+    client.set_rpc_endpoint(&url, secret.clone());
+
+    let mut settings = get_settings(&handle);
+    settings.rpc_url = url;
+    settings.rpc_secret = secret;
+    save_settings(&handle, &settings)
+}
+
+#[tauri::command]
+async fn add_download(
+    handle: tauri::AppHandle,
+    url: String,
+    dir: Option<String>,
+    filename: Option<String>,
+    options: Option<AddDownloadOptions>,
+) -> Result<download::AddedDownload, AppError> {
+    let settings = get_settings(&handle);
+    let extra = options.unwrap_or_default();
+    // Small files get fewer connections, unless the caller asked for a number
+    let scaled = extra
+        .expected_size
+        .and_then(|size| settings.connections_for_size(size));
+    let max_connections = extra.max_connections.unwrap_or_else(|| {
+        extra
+            .priority
+            .connections(scaled.unwrap_or(settings.max_connections))
+    });
+    let split = extra.split.unwrap_or_else(|| {
+        let split = scaled.map_or(settings.split_count, |connections| {
+            connections.min(settings.split_count)
+        });
+        extra.priority.split(split)
+    });
+
+    if !(1..=16).contains(&max_connections) {
+        return Err(AppError::invalid_input("max_connections must be between 1 and 16"));
+    }
+    if split == 0 {
+        return Err(AppError::invalid_input("split must be at least 1"));
+    }
+
+    let client = &*ARIA2_CLIENT;
+    let _adding = ADD_LOCK.lock().await;
+
+    // Only restart a daemon we stopped for being idle; otherwise `auto_start_aria2` decides
+    if download::is_idle_shutdown() {
+        apply_settings(client, &settings);
+        client.start_daemon().await?;
+        download::clear_idle_shutdown();
+    }
+
+    let out = filename.as_deref().map(download::sanitize_filename);
+    let auto_paused = download::is_auto_paused();
+    let category = extra
+        .file_category
+        .or_else(|| filename.as_deref().map(FileCategory::from_filename));
+    let dir = dir.or_else(|| settings.category_dir(category?));
+    let target_dir = dir.as_deref().unwrap_or(&settings.download_dir);
+
+    if !std::path::Path::new(target_dir).exists() {
+        let check = download::check_download_dir(std::path::Path::new(target_dir));
+        if !check.writable {
+            return Err(AppError::io(format!(
+                "Can't write to download folder {}. Pick a different folder in settings.",
+                target_dir
+            )));
+        }
+    }
+
+    let overwrite_policy = extra.overwrite_policy.unwrap_or(settings.overwrite_policy);
+    if overwrite_policy == OverwritePolicy::Skip {
+        // A control file means aria2 would resume it, which isn't overwriting anything
+        let existing = out
+            .as_deref()
+            .map(|name| std::path::Path::new(target_dir).join(name))
+            .filter(|path| path.is_file() && !download::has_control_file(path));
+        if let Some(path) = existing {
+            return Err(AppError::AlreadyDownloaded { path });
+        }
+    }
+
+    if !extra.force {
+        if let Some(name) = out.as_deref() {
+            if let Some(gid) = download::find_duplicate(client, target_dir, name).await {
+                return Err(AppError::DownloadExists { gid });
+            }
+
+            // A partial file is fine: aria2 resumes it from its control file
+            let existing = extra
+                .expected_size
+                .and_then(|size| download::existing_complete_file(target_dir, name, size));
+            if let Some(path) = existing {
+                if extra.record_existing {
+                    let original = filename.as_deref().unwrap_or(name);
+                    download::record_existing_file(&path, original, extra.share_url.clone());
+                }
+                return Err(AppError::AlreadyDownloaded { path });
+            }
+        }
+    }
+
+    // aria2 picks a partial download up from its control file as long as dir and out match
+    let resumed_from = out
+        .as_deref()
+        .map(|name| std::path::Path::new(target_dir).join(name))
+        .and_then(|path| download::partial_download_progress(&path));
+    if let Some(percent) = resumed_from {
+        log::info!("Resuming {} from {:.1}%", out.as_deref().unwrap_or_default(), percent);
+    }
+
+    let mut headers = HashMap::new();
+    if let Some(cookies) = extra.cookies.filter(|c| !c.is_empty()) {
+        headers.insert("Cookie".to_string(), cookies);
+    }
+
+    let file_allocation = settings
+        .file_allocation
+        .effective_for(std::path::Path::new(target_dir));
+
+    let options = Aria2Options {
+        dir,
+        out: out.clone(),
+        file_allocation: Some(file_allocation.as_arg().to_string()),
+        max_connection_per_server: Some(max_connections.to_string()),
+        split: Some(split.to_string()),
+        min_split_size: Some(settings.min_split_size.clone()),
+        user_agent: Some(
+            extra
+                .user_agent
+                .clone()
+                .unwrap_or_else(|| settings.user_agent_for(&url).to_string()),
+        ),
+        // Terabox CDNs expect the request to look like it came from the share page
+        referer: extra.share_url.clone(),
+        headers,
+        // Also held back while downloads are auto-paused (metered connection, low battery)
+        pause: (extra.paused || auto_paused).then(|| "true".to_string()),
+        allow_overwrite: Some(overwrite_policy.allow_overwrite().to_string()),
+        auto_file_renaming: Some(overwrite_policy.auto_file_renaming().to_string()),
+        continue_download: Some("true".to_string()),
+    };
+
+    let mut uris = vec![url];
+    for mirror in extra.urls {
+        if !mirror.is_empty() && !uris.contains(&mirror) {
+            uris.push(mirror);
+        }
+    }
+
+    let gid = client.add_uri(&uris, Some(options)).await?;
+    download::touch_activity();
+    if extra.priority == download::Priority::High {
+        // Fails if aria2 started it right away, which is as good as the front
+        if let Err(e) = client.change_position(&gid, 0, "POS_SET").await {
+            log::debug!("Couldn't move {} to the front: {}", gid, e);
+        }
+    }
+    if auto_paused && !extra.paused {
+        // Start it along with the others once the auto-pause lifts
+        download::track_auto_paused(&gid);
+    }
+    let added_at = chrono::Local::now().to_rfc3339();
+    download::enqueue(download::QueueEntry {
+        gid: gid.clone(),
+        share_url: extra.share_url.clone(),
+        category,
+        group_id: extra.group_id.clone(),
+        added_at: added_at.clone(),
+        priority: extra.priority,
+        last_known: None,
+    });
+    let origin = DownloadOrigin {
+        share_url: extra.share_url,
+        fs_id: extra.fs_id,
+        requested_name: filename,
+        group_id: extra.group_id,
+        added_at: Some(added_at),
+        ..DownloadOrigin::default()
+    };
+    client.record_origin(&gid, origin);
+
+    Ok(download::AddedDownload { gid, resumed_from })
+}
+
+/// `add_download` without waiting for aria2: returns a tracking id at once, and the
+/// outcome follows as a `download-added` or `download-add-failed` event with that id
+#[tauri::command]
+fn add_download_async(
+    handle: tauri::AppHandle,
+    url: String,
+    dir: Option<String>,
+    filename: Option<String>,
+    options: Option<AddDownloadOptions>,
+) -> String {
+    let tracking_id = uuid::Uuid::new_v4().to_string();
+    let id = tracking_id.clone();
+    tauri::async_runtime::spawn(async move {
+        match add_download(handle.clone(), url, dir, filename, options).await {
+            Ok(download) => {
+                let added = download::DownloadAdded { tracking_id: id, download };
+                let _ = handle.emit(download::DOWNLOAD_ADDED_EVENT, added);
+            }
+            Err(error) => {
+                let failed = download::DownloadAddFailed { tracking_id: id, error };
+                let _ = handle.emit(download::DOWNLOAD_ADD_FAILED_EVENT, failed);
+            }
+        }
+    });
+    tracking_id
+}
+
+/// Adds several downloads as one group, e.g. the files of a folder. A download that
+/// can't be added doesn't stop the rest.
+#[tauri::command]
+async fn add_downloads_batch(
+    handle: tauri::AppHandle,
+    downloads: Vec<download::BatchDownload>,
+    dir: Option<String>,
+    mode: Option<download::BatchMode>,
+) -> Result<download::BatchReport, AppError> {
+    if downloads.is_empty() {
+        return Err(AppError::invalid_input("No downloads to add"));
+    }
+
+    let mode = mode.unwrap_or_default();
+    let sequential = mode == download::BatchMode::Sequential;
+    let mut report = download::BatchReport {
+        group_id: uuid::Uuid::new_v4().to_string(),
+        mode,
+        gids: Vec::new(),
+        failed: Vec::new(),
+    };
+    for item in downloads {
+        let options = item.options.unwrap_or_default();
+        let options = AddDownloadOptions {
+            group_id: Some(report.group_id.clone()),
+            // The completion loop releases them one by one
+            paused: options.paused || sequential,
+            ..options
+        };
+        let added = add_download(
+            handle.clone(),
+            item.url.clone(),
+            dir.clone(),
+            item.filename,
+            Some(options),
+        )
+        .await;
+        match added {
+            Ok(added) => report.gids.push(added.gid),
+            Err(e) => report.failed.push(download::BatchFailure {
+                url: item.url,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    if sequential {
+        if let Some(first) = download::start_sequence(&report.group_id, report.gids.clone()) {
+            ARIA2_CLIENT.unpause(&first).await?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Starts the next download of each sequential batch that `stopped` moves along
+async fn advance_sequences(client: &Aria2Client, stopped: &[String]) {
+    for gid in download::advance_sequences(stopped) {
+        if let Err(e) = client.unpause(&gid).await {
+            log::warn!("Failed to start next download {} in sequence: {}", gid, e);
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_group_progress(group_id: String) -> Result<GroupProgress, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let mut progress = client.get_group_progress(&group_id).await?;
+    progress.current_gid = download::sequence_current(&group_id);
+    Ok(progress)
+}
+
+#[tauri::command]
+async fn pause_group(group_id: String) -> Result<usize, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.group_action(&group_id, "pause").await
+}
+
+#[tauri::command]
+async fn resume_group(group_id: String) -> Result<usize, AppError> {
+    let client = &*ARIA2_CLIENT;
+    download::touch_activity();
+    client.group_action(&group_id, "unpause").await
+}
+
+#[tauri::command]
+async fn cancel_group(group_id: String) -> Result<usize, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let cancelled = client.group_action(&group_id, "forceRemove").await?;
+    download::end_sequence(&group_id);
+    download::dequeue(&client.group_gids(&group_id));
+    Ok(cancelled)
+}
+
+/// Resolves and enqueues one file of a share, however deep in its folders, under the
+/// file's own name. Nothing else in the share is downloaded.
+#[tauri::command]
+async fn add_terabox_file(
+    handle: tauri::AppHandle,
+    url: String,
+    fs_id: String,
+    dir: Option<String>,
+) -> Result<download::AddedDownload, AppError> {
+    let (params, file) = TERABOX_API.find_file(&url, &fs_id).await?;
+    if file.is_dir {
+        return Err(AppError::invalid_input(format!("{} is a folder, not a file", file.name)));
+    }
+
+    let link = TERABOX_API.get_download_link(params).await?;
+    let direct_link = link.download_link.ok_or_else(|| {
+        AppError::not_found(link.error_message.unwrap_or("No download link returned".to_string()))
+    })?;
+
+    let options = AddDownloadOptions {
+        share_url: Some(url),
+        fs_id: Some(file.fs_id),
+        file_category: Some(file.file_category),
+        expected_size: file.size.and_then(|size| u64::try_from(size).ok()),
+        cookies: link.cookies,
+        urls: link.urls,
+        ..Default::default()
+    };
+    add_download(handle, direct_link, dir, Some(file.name), Some(options)).await
+}
+
+/// Resolves a fresh direct link for `filename` in a share and enqueues it into `dir`.
+/// Used when the old direct link has expired, so resolution starts over from the share.
+/// `force` skips the duplicate check.
+async fn enqueue_from_share(
+    handle: tauri::AppHandle,
+    share_url: String,
+    fs_id: Option<String>,
+    filename: String,
+    dir: Option<String>,
+    force: bool,
+) -> Result<String, AppError> {
+    let unavailable = |e: AppError| match e {
+        AppError::Network { .. } => e,
+        other => AppError::not_found(format!("Share no longer available: {}", other)),
+    };
+    let (params, file) = match fs_id.as_deref() {
+        // Also finds files in subfolders
+        Some(fs_id) => TERABOX_API.find_file(&share_url, fs_id).await.map_err(|e| match e {
+            AppError::NotFound { .. } => {
+                AppError::not_found(format!("Share no longer contains {}", filename))
+            }
+            other => unavailable(other),
+        })?,
+        None => {
+            let info = TERABOX_API.get_info(&share_url).await.map_err(unavailable)?;
+            let file = info
+                .list
+                .iter()
+                .find(|f| !f.is_dir && f.name.eq_ignore_ascii_case(&filename))
+                .cloned()
+                .ok_or_else(|| AppError::not_found(format!("Share no longer contains {}", filename)))?;
+            let params = DownloadParams {
+                shareid: info.shareid,
+                uk: info.uk,
+                sign: info.sign.clone(),
+                timestamp: info.timestamp,
+                fs_id: file.fs_id.clone(),
+                mode: terabox::default_mode(),
+            };
+            (params, file)
+        }
+    };
+
+    let link = TERABOX_API.get_download_link(params).await?;
+    let direct_link = link.download_link.ok_or_else(|| {
+        AppError::not_found(link.error_message.unwrap_or("No download link returned".to_string()))
+    })?;
+
+    let options = AddDownloadOptions {
+        share_url: Some(share_url),
+        fs_id: Some(file.fs_id.clone()),
+        file_category: Some(file.file_category),
+        expected_size: file.size.and_then(|size| u64::try_from(size).ok()),
+        cookies: link.cookies,
+        urls: link.urls,
+        force,
+        ..Default::default()
+    };
+
+    let added = add_download(handle, direct_link, dir, Some(filename), Some(options)).await?;
+    Ok(added.gid)
+}
+
+fn parent_dir(path: &str) -> Option<String> {
+    std::path::Path::new(path)
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .filter(|p| !p.is_empty())
+}
+
+/// Re-resolves a history item's share and enqueues it again into the same folder
+#[tauri::command]
+async fn redownload(handle: tauri::AppHandle, history_id: String) -> Result<String, AppError> {
+    let item = settings::api::load_history()
+        .items
+        .into_iter()
+        .find(|item| item.id == history_id)
+        .ok_or_else(|| AppError::not_found("History item not found"))?;
+
+    // A URL kept only as a hash, or not at all, can't be resolved again
+    let url = item
+        .url
+        .filter(|url| TeraboxApi::extract_shorturl(url).is_some())
+        .ok_or_else(|| AppError::invalid_input("History item has no Terabox share URL"))?;
+
+    let dir = parent_dir(&item.path);
+    // Downloading it again is the point, so skip the duplicate check
+    enqueue_from_share(handle, url, None, item.filename, dir, true).await
+}
+
+/// Replaces a failed download (typically an expired link) with a freshly resolved one
+/// into the same dir and filename. Returns the new GID.
+#[tauri::command]
+async fn retry_download(handle: tauri::AppHandle, gid: String) -> Result<String, AppError> {
+    let (info, origin) = {
+        let client = &*ARIA2_CLIENT;
+        let info = client.get_download_info(&gid).await?;
+        (info, client.get_origin(&gid).unwrap_or_default())
+    };
+
+    let share_url = origin
+        .share_url
+        .ok_or_else(|| AppError::not_found("Original Terabox share for this download is unknown"))?;
+    if let Some(source_uri) = info.source_uri.as_deref() {
+        TERABOX_API.invalidate_link(source_uri);
+    }
+
+    let filename = origin.requested_name.unwrap_or_else(|| info.filename.clone());
+    let dir = info.path.as_deref().and_then(parent_dir);
+    let new_gid = enqueue_from_share(handle, share_url, origin.fs_id, filename, dir, true).await?;
+
+    let client = &*ARIA2_CLIENT;
+    if matches!(info.status, DownloadStatus::Error | DownloadStatus::Removed) {
+        let _ = client.remove_download_result(&gid).await;
+    } else {
+        let _ = client.force_remove(&gid).await;
+        client.forget_origin(&gid);
+    }
+    download::replace_in_sequence(&gid, &new_gid);
+    download::dequeue(&[gid]);
+
+    Ok(new_gid)
+}
+
+/// Retries every failed download; one that can't be retried doesn't stop the rest
+#[tauri::command]
+async fn retry_errored(handle: tauri::AppHandle) -> Result<download::RetryReport, AppError> {
+    let errored = ARIA2_CLIENT.errored_gids().await?;
+    Ok(retry_downloads(&handle, errored).await)
+}
+
+async fn retry_downloads(handle: &tauri::AppHandle, gids: Vec<String>) -> download::RetryReport {
+    let mut report = download::RetryReport::default();
+    for gid in gids {
+        match retry_download(handle.clone(), gid.clone()).await {
+            Ok(new_gid) => report.retried.push(new_gid),
+            Err(e) => report.failed.push(download::RetryFailure {
+                gid,
+                message: e.to_string(),
+            }),
+        }
+    }
+    report
+}
+
+/// Fed by the frontend's online/offline events. Going offline pauses the downloads so
+/// they don't fail on a dead connection. Coming back resumes them and, with
+/// `retry_on_reconnect`, retries on fresh links the ones that failed in between.
+/// Downloads that failed before the disconnect, or were cancelled, are left alone.
+#[tauri::command]
+async fn notify_network_changed(
+    handle: tauri::AppHandle,
+    online: bool,
+) -> Result<download::RetryReport, AppError> {
+    let client = &*ARIA2_CLIENT;
+    if !online {
+        if download::mark_offline(client.errored_gids().await?) {
+            download::set_pause_condition(client, download::PauseReason::Offline, true).await?;
+        }
+        return Ok(download::RetryReport::default());
+    }
+
+    download::set_pause_condition(client, download::PauseReason::Offline, false).await?;
+    let failed_offline = download::mark_online(client.errored_gids().await?);
+    if failed_offline.is_empty() || !get_settings(&handle).retry_on_reconnect {
+        return Ok(download::RetryReport::default());
+    }
+
+    let report = retry_downloads(&handle, failed_offline).await;
+    let _ = handle.emit(download::RECONNECT_RETRIED_EVENT, &report);
+    Ok(report)
+}
+
+/// A download's details as text; the frontend puts it on the clipboard
+#[tauri::command]
+async fn copy_download_info(gid: String) -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let info = client.get_download_info(&gid).await?;
+    Ok(download::format_download_report(&info, client.get_origin(&gid).as_ref()))
+}
+
+/// Every failed download's details, separated by blank lines
+#[tauri::command]
+async fn copy_all_failed() -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let mut reports = Vec::new();
+    for gid in client.errored_gids().await? {
+        if let Ok(info) = client.get_download_info(&gid).await {
+            reports.push(download::format_download_report(&info, client.get_origin(&gid).as_ref()));
+        }
+    }
+    Ok(reports.join("\n"))
+}
+
+/// Active downloads that have been at 0 B/s for longer than `stall_timeout_secs`
+#[tauri::command]
+fn get_stalled_downloads() -> Vec<String> {
+    download::stalled_downloads()
+}
+
+#[tauri::command]
+async fn get_download_origin(gid: String) -> Option<DownloadOrigin> {
+    let client = &*ARIA2_CLIENT;
+    client.get_origin(&gid)
+}
+
+#[tauri::command]
+async fn get_download_status(gid: String) -> Result<DownloadInfo, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let info = client.get_download_info(&gid).await?;
+    forget_rejected_link(&info);
+    Ok(info)
+}
+
+/// Moves a download that hasn't started yet to `new_dir`
+#[tauri::command]
+async fn change_download_dir(gid: String, new_dir: String) -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let info = client.get_download_info(&gid).await?;
+    match info.status {
+        DownloadStatus::Waiting | DownloadStatus::Paused => {}
+        DownloadStatus::Active => {
+            return Err(AppError::invalid_input(
+                "Can't change the folder of a download in progress. Pause it first.",
+            ))
+        }
+        _ => return Err(AppError::invalid_input("Download has already finished")),
+    }
+
+    let check = download::check_download_dir(std::path::Path::new(&new_dir));
+    if !check.writable {
+        return Err(AppError::io(format!("Can't write to download folder {}", new_dir)));
+    }
+
+    client.change_option(&gid, "dir", &new_dir).await
+}
+
+/// Unpacks a completed archive download, returning the folder it went into.
+/// Pass `password` after a `password_required` error.
+#[tauri::command]
+async fn extract_download(
+    handle: tauri::AppHandle,
+    gid: String,
+    password: Option<String>,
+) -> Result<String, AppError> {
+    let info = ARIA2_CLIENT.get_download_info(&gid).await?;
+    if info.status != DownloadStatus::Complete {
+        return Err(AppError::invalid_input("Download hasn't finished yet"));
+    }
+    let archive = info
+        .path
+        .map(std::path::PathBuf::from)
+        .filter(|path| path.exists())
+        .ok_or_else(|| AppError::not_found("Downloaded file no longer exists"))?;
+    if !download::is_archive(&archive) {
+        return Err(AppError::invalid_input("Only zip, rar and 7z archives can be extracted"));
+    }
+
+    let out_dir = extract_and_report(handle, gid, archive, password).await?;
+    Ok(out_dir.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn get_download_files(gid: String) -> Result<Vec<DownloadFile>, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.get_download_files(&gid).await
+}
+
+/// Which servers (e.g. Terabox CDN nodes) a download is using, with the speed from each
+#[tauri::command]
+async fn get_download_servers(gid: String) -> Result<Vec<DownloadServer>, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.get_download_servers(&gid).await
+}
+
+#[tauri::command]
+async fn select_files(gid: String, indices: Vec<u32>) -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.select_files(&gid, &indices).await
+}
+
+#[tauri::command]
+async fn pause_download(gid: String) -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.pause(&gid).await
+}
+
+#[tauri::command]
+async fn resume_download(gid: String) -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    download::touch_activity();
+    client.unpause(&gid).await
+}
+
+#[tauri::command]
+async fn cancel_download(gid: String) -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let result = client.force_remove(&gid).await?;
+    advance_sequences(client, std::slice::from_ref(&gid)).await;
+    download::dequeue(&[gid]);
+    Ok(result)
+}
+
+/// Cancels a download and deletes what it left on disk: the partial file and its
+/// `.aria2` control file. Returns the paths deleted, which may be none.
+#[tauri::command]
+async fn cancel_and_delete(gid: String) -> Result<Vec<String>, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let info = client.get_download_info(&gid).await?;
+    if info.status == DownloadStatus::Complete {
+        return Err(AppError::invalid_input("This download is complete, so there's no partial file to delete"));
+    }
+
+    cancel_download(gid.clone()).await?;
+    // aria2 may still be writing until it has closed the download
+    for _ in 0..25 {
+        match client.get_download_info(&gid).await {
+            Ok(info) if info.status != DownloadStatus::Removed => {
+                tokio::time::sleep(Duration::from_millis(200)).await
+            }
+            _ => break,
+        }
+    }
+
+    let Some(path) = info.path else {
+        return Ok(Vec::new());
+    };
+    let deleted = download::delete_partial_files(std::path::Path::new(&path))?;
+    Ok(deleted.iter().map(|p| p.display().to_string()).collect())
+}
 
-fn get_settings(handle: &tauri::AppHandle) -> AppSettings {
-    let store = handle.store("settings").unwrap();
-    let result = store.get("app_settings");
-    match result {
-        Some(value) => serde_json::from_value(value).unwrap_or(AppSettings::default()),
-        _ => AppSettings::default(),
+/// Renames a queued download before it starts and returns the sanitized name. A
+/// download that has written data already is refused, as aria2 would leave that partial
+/// file behind and start over under the new name.
+#[tauri::command]
+async fn rename_download(gid: String, new_name: String) -> Result<String, AppError> {
+    let new_name = new_name.trim();
+    if new_name.is_empty() {
+        return Err(AppError::invalid_input("The new name can't be empty"));
+    }
+    let out = download::sanitize_filename(new_name);
+
+    let client = &*ARIA2_CLIENT;
+    let info = client.get_download_info(&gid).await?;
+    match info.status {
+        DownloadStatus::Waiting | DownloadStatus::Paused if info.downloaded == 0 => {}
+        DownloadStatus::Waiting | DownloadStatus::Paused => {
+            return Err(AppError::invalid_input(format!(
+                "{} has already started downloading and can't be renamed",
+                info.filename
+            )));
+        }
+        DownloadStatus::Active => {
+            return Err(AppError::invalid_input(format!(
+                "{} is downloading; only queued or paused downloads can be renamed",
+                info.filename
+            )));
+        }
+        _ => {
+            return Err(AppError::invalid_input(format!(
+                "{} has finished and can't be renamed",
+                info.filename
+            )));
+        }
     }
+
+    client.change_option(&gid, "out", &out).await?;
+    // So a retry saves under the new name too
+    let mut origin = client.get_origin(&gid).unwrap_or_default();
+    origin.requested_name = Some(new_name.to_string());
+    client.record_origin(&gid, origin);
+    Ok(out)
 }
 
-fn save_settings(handle: &tauri::AppHandle, settings: &AppSettings) -> Result<(), String> {
-    let store = handle.store("settings").unwrap();
-    let value = serde_json::to_value(settings)
-        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-    store.set("app_settings", value);
+/// Changes a download's priority: moves it to the front or end of aria2's queue and
+/// rescales its connections from the saved settings. aria2 only moves waiting downloads,
+/// so an active one just gets the new connection count.
+#[tauri::command]
+async fn set_priority(
+    handle: tauri::AppHandle,
+    gid: String,
+    priority: download::Priority,
+) -> Result<(), AppError> {
+    let settings = get_settings(&handle);
+    let client = &*ARIA2_CLIENT;
+    let info = client.get_download_info(&gid).await?;
+
+    if matches!(info.status, DownloadStatus::Waiting | DownloadStatus::Paused) {
+        let moved = match priority {
+            download::Priority::High => Some(client.change_position(&gid, 0, "POS_SET").await),
+            download::Priority::Low => Some(client.change_position(&gid, 0, "POS_END").await),
+            download::Priority::Normal => None,
+        };
+        if let Some(Err(e)) = moved {
+            log::warn!("Couldn't move {} in the queue: {}", gid, e);
+        }
+    }
+    if matches!(
+        info.status,
+        DownloadStatus::Active | DownloadStatus::Waiting | DownloadStatus::Paused
+    ) {
+        client
+            .change_option(
+                &gid,
+                "max-connection-per-server",
+                &priority.connections(settings.max_connections).to_string(),
+            )
+            .await?;
+        client
+            .change_option(&gid, "split", &priority.split(settings.split_count).to_string())
+            .await?;
+    }
+
+    download::set_queue_priority(&gid, priority);
     Ok(())
 }
 
+/// The app's queue with aria2's status for each entry. While aria2 is reachable,
+/// entries it no longer knows are dropped first; until then each entry's `last_known`
+/// stands in for the live status.
 #[tauri::command]
-async fn get_terabox_info(url: String) -> Result<TeraboxInfo, String> {
-    TERABOX_API.get_info(&url).await
+async fn get_queue() -> Vec<download::QueuedItem> {
+    let client = &*ARIA2_CLIENT;
+    // All of them, or entries past the first 100 waiting would be dropped
+    let mut downloads: HashMap<String, DownloadInfo> = match client.get_all_downloads(Some(usize::MAX)).await {
+        Ok(all) => {
+            let all: HashMap<_, _> = all.into_iter().map(|d| (d.gid.clone(), d)).collect();
+            download::reconcile_queue(&all.keys().cloned().collect());
+            all
+        }
+        Err(_) => HashMap::new(),
+    };
+
+    download::queue_entries()
+        .into_iter()
+        .map(|entry| download::QueuedItem {
+            download: downloads.remove(&entry.gid),
+            entry,
+        })
+        .collect()
+}
+
+/// `entry` as a share that can be resolved again, seen as `info`. `None` for finished
+/// downloads and ones added from a plain direct link, which have no share to carry.
+fn shared_download(
+    client: &Aria2Client,
+    entry: download::QueueEntry,
+    info: Option<&DownloadInfo>,
+) -> Option<download::SharedDownload> {
+    if info.is_some_and(|info| info.status == DownloadStatus::Complete) {
+        return None;
+    }
+    let origin = client.get_origin(&entry.gid).unwrap_or_default();
+    let share_url = entry.share_url.or(origin.share_url)?;
+    let filename = origin
+        .requested_name
+        .or_else(|| info.map(|info| info.filename.clone()))
+        .filter(|name| !name.is_empty())?;
+    Some(download::SharedDownload {
+        share_url,
+        fs_id: origin.fs_id,
+        filename,
+        dir: info.and_then(|info| info.path.as_deref()).and_then(parent_dir),
+        category: entry.category,
+        priority: entry.priority,
+    })
+}
+
+/// Adds `shared` back from a freshly resolved link, into `dir` if given, with its
+/// priority. Returns the new GID.
+async fn enqueue_shared(
+    handle: &tauri::AppHandle,
+    shared: download::SharedDownload,
+    dir: Option<String>,
+) -> Result<String, AppError> {
+    let dir = dir.or(shared.dir);
+    let gid = enqueue_from_share(
+        handle.clone(),
+        shared.share_url,
+        shared.fs_id,
+        shared.filename,
+        dir,
+        false,
+    )
+    .await?;
+    if shared.priority != download::Priority::Normal {
+        if let Err(e) = set_priority(handle.clone(), gid.clone(), shared.priority).await {
+            log::warn!("Couldn't restore the priority of {}: {}", gid, e);
+        }
+    }
+    Ok(gid)
 }
 
+/// Writes the unfinished downloads of the queue to a `.trauso` file at `path`, as shares
+/// that `import_queue` can resolve again on any machine. Downloads added from a plain
+/// direct link have no share to carry and are left out. Returns how many were written.
 #[tauri::command]
-async fn get_download_link(params: DownloadParams) -> Result<DownloadLink, String> {
-    TERABOX_API.get_download_link(params).await
+async fn export_queue(path: String) -> Result<usize, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let downloads: HashMap<String, DownloadInfo> = client
+        .get_all_downloads(None)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|d| (d.gid.clone(), d))
+        .collect();
+
+    let shared: Vec<download::SharedDownload> = download::queue_entries()
+        .into_iter()
+        .filter_map(|entry| {
+            let info = downloads.get(&entry.gid).or(entry.last_known.as_ref()).cloned();
+            shared_download(client, entry, info.as_ref())
+        })
+        .collect();
+
+    let count = shared.len();
+    download::write_queue_file(
+        std::path::Path::new(&path),
+        &download::QueueFile {
+            version: download::QUEUE_FILE_VERSION,
+            exported_at: chrono::Local::now().to_rfc3339(),
+            downloads: shared,
+        },
+    )?;
+    Ok(count)
 }
 
+/// Re-adds every download of a file written by `export_queue`, resolving a fresh link
+/// from each share. With `dir`, everything goes there instead of the exported folders,
+/// which may not exist on this machine. A download already in aria2 or on disk fails
+/// like any other duplicate; one share failing doesn't stop the rest.
 #[tauri::command]
-fn extract_shorturl(url: String) -> Option<String> {
-    TeraboxApi::extract_shorturl(&url)
+async fn import_queue(
+    handle: tauri::AppHandle,
+    path: String,
+    dir: Option<String>,
+) -> Result<download::QueueImportReport, AppError> {
+    let file = download::read_queue_file(std::path::Path::new(&path))?;
+
+    let mut report = download::QueueImportReport::default();
+    for shared in file.downloads {
+        let url = shared.share_url.clone();
+        match enqueue_shared(&handle, shared, dir.clone()).await {
+            Ok(gid) => report.gids.push(gid),
+            Err(e) => report.failed.push(download::BatchFailure {
+                url,
+                message: e.to_string(),
+            }),
+        }
+    }
+    Ok(report)
+}
+
+/// The frontend's one call at launch. With `auto_start_aria2`, starts the daemon, which
+/// loads its session file if the extra args give one, then re-queues from their shares
+/// the queued downloads it came back without and drops the queue entries left over.
+/// Returns every download as aria2 now has it.
+#[tauri::command]
+async fn bootstrap(handle: tauri::AppHandle) -> Result<download::Bootstrap, AppError> {
+    let client = &*ARIA2_CLIENT;
+    // Taken first: the completion loop drops entries aria2 doesn't know once it's up
+    let queued = download::queue_entries();
+
+    let mut requeued = download::RetryReport::default();
+    if get_settings(&handle).auto_start_aria2 {
+        start_aria2(handle.clone()).await?;
+
+        let known: HashSet<String> = client
+            .get_all_downloads(Some(usize::MAX))
+            .await?
+            .into_iter()
+            .map(|d| d.gid)
+            .collect();
+        for entry in queued.into_iter().filter(|entry| !known.contains(&entry.gid)) {
+            let gid = entry.gid.clone();
+            let paused = entry
+                .last_known
+                .as_ref()
+                .is_some_and(|info| info.status == DownloadStatus::Paused);
+            let Some(shared) = shared_download(client, entry.clone(), entry.last_known.as_ref()) else {
+                continue;
+            };
+            match enqueue_shared(&handle, shared, None).await {
+                Ok(new_gid) => {
+                    if paused {
+                        let _ = client.pause(&new_gid).await;
+                    }
+                    requeued.retried.push(new_gid);
+                }
+                Err(e) => requeued.failed.push(download::RetryFailure {
+                    gid,
+                    message: e.to_string(),
+                }),
+            }
+        }
+    }
+
+    let aria2_running = client.is_running().await;
+    let downloads = if aria2_running {
+        // All of them, not just the first 100 waiting and stopped
+        client.get_all_downloads(Some(usize::MAX)).await?
+    } else {
+        Vec::new()
+    };
+    if aria2_running {
+        download::reconcile_queue(&downloads.iter().map(|d| d.gid.clone()).collect());
+    }
+    Ok(download::Bootstrap {
+        aria2_running,
+        downloads,
+        requeued,
+    })
 }
 
 #[tauri::command]
-async fn start_aria2() -> Result<(), String> {
-    let client = ARIA2_CLIENT.lock().await;
-    client.start_daemon().await
+async fn remove_download_result(gid: String) -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.remove_download_result(&gid).await
 }
 
 #[tauri::command]
-async fn stop_aria2() -> Result<(), String> {
-    let client = ARIA2_CLIENT.lock().await;
-    client.stop_daemon().await
+async fn clear_completed() -> Result<usize, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.clear_completed_results().await
 }
 
+/// Like `clear_completed`, but for failed downloads only
 #[tauri::command]
-async fn is_aria2_running() -> bool {
-    let client = ARIA2_CLIENT.lock().await;
-    client.is_running().await
+async fn cancel_errored() -> Result<usize, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.clear_errored_results().await
 }
 
 #[tauri::command]
-async fn add_download(url: String, dir: Option<String>, filename: Option<String>) -> Result<String, String> {
-    let client = ARIA2_CLIENT.lock().await;
-    
-    let options = Aria2Options {
-        dir,
-        out: filename,
-        ..Default::default()
-    };
-    
-    client.add_uri(&url, Some(options)).await
+async fn clear_all_results() -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.purge_download_result().await
 }
 
+/// Combined speed across all downloads, e.g. to see what extra mirrors bring
 #[tauri::command]
-async fn get_download_status(gid: String) -> Result<DownloadInfo, String> {
-    let client = ARIA2_CLIENT.lock().await;
-    client.get_download_info(&gid).await
+async fn get_global_stats() -> Result<GlobalStats, AppError> {
+    let client = &*ARIA2_CLIENT;
+    Ok(client.get_global_stat().await?.into())
 }
 
 #[tauri::command]
-async fn pause_download(gid: String) -> Result<String, String> {
-    let client = ARIA2_CLIENT.lock().await;
-    client.pause(&gid).await
+async fn get_overall_progress() -> Result<OverallProgress, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.get_overall_progress().await
 }
 
 #[tauri::command]
-async fn resume_download(gid: String) -> Result<String, String> {
-    let client = ARIA2_CLIENT.lock().await;
-    client.unpause(&gid).await
+async fn get_all_downloads(limit: Option<usize>) -> Result<Vec<DownloadInfo>, AppError> {
+    let client = &*ARIA2_CLIENT;
+    let downloads = client.get_all_downloads(limit).await?;
+    downloads.iter().for_each(forget_rejected_link);
+    Ok(downloads)
+}
+
+fn check_page_size(num: u32) -> Result<(), AppError> {
+    if !(1..=1000).contains(&num) {
+        return Err(AppError::invalid_input("Page size must be between 1 and 1000"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_waiting(offset: u32, num: u32) -> Result<DownloadPage, AppError> {
+    check_page_size(num)?;
+    let client = &*ARIA2_CLIENT;
+    client.get_waiting_page(offset, num).await
+}
+
+#[tauri::command]
+async fn get_stopped(offset: u32, num: u32) -> Result<DownloadPage, AppError> {
+    check_page_size(num)?;
+    let client = &*ARIA2_CLIENT;
+    let page = client.get_stopped_page(offset, num).await?;
+    page.downloads.iter().for_each(forget_rejected_link);
+    Ok(page)
 }
 
 #[tauri::command]
-async fn cancel_download(gid: String) -> Result<String, String> {
-    let client = ARIA2_CLIENT.lock().await;
-    client.force_remove(&gid).await
+async fn get_downloads_by_status(status: DownloadStatus) -> Result<Vec<DownloadInfo>, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.get_downloads_by_status(status).await
 }
 
 #[tauri::command]
-async fn get_all_downloads() -> Result<Vec<DownloadInfo>, String> {
-    let client = ARIA2_CLIENT.lock().await;
-    client.get_all_downloads().await
+async fn reveal_in_folder(handle: tauri::AppHandle, gid: String) -> Result<(), AppError> {
+    let info = ARIA2_CLIENT.get_download_info(&gid).await?;
+    let path = info
+        .path
+        .map(std::path::PathBuf::from)
+        .ok_or_else(|| AppError::not_found("Download has no file path yet"))?;
+
+    // The user may have moved or deleted it since aria2 finished
+    if !path.exists() {
+        return Err(AppError::not_found(format!(
+            "File no longer exists: {}",
+            path.display()
+        )));
+    }
+
+    let opener = handle.opener();
+    if opener.reveal_item_in_dir(&path).is_ok() {
+        return Ok(());
+    }
+
+    // No way to select the file here, so just open its folder
+    let dir = path.parent().unwrap_or(&path);
+    opener
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| AppError::io(format!("Failed to open folder: {}", e)))
 }
 
 #[tauri::command]
-async fn pause_all_downloads() -> Result<String, String> {
-    let client = ARIA2_CLIENT.lock().await;
+async fn pause_all_downloads() -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
     client.pause_all().await
 }
 
 #[tauri::command]
-async fn resume_all_downloads() -> Result<String, String> {
-    let client = ARIA2_CLIENT.lock().await;
+async fn resume_all_downloads() -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    download::touch_activity();
     client.unpause_all().await
 }
 
+#[tauri::command]
+async fn set_metered(handle: tauri::AppHandle, metered: bool) -> Result<(), AppError> {
+    let settings = get_settings(&handle);
+    let client = &*ARIA2_CLIENT;
+
+    let pause = metered && settings.pause_on_metered;
+    if download::set_pause_condition(client, download::PauseReason::Metered, pause).await? {
+        let _ = handle.emit(download::AUTO_PAUSED_EVENT, "metered");
+    }
+
+    Ok(())
+}
+
+/// Fed by the frontend, which can read the battery state on every platform
+#[tauri::command]
+async fn set_battery_level(handle: tauri::AppHandle, percent: u8, charging: bool) -> Result<(), AppError> {
+    let settings = get_settings(&handle);
+    let client = &*ARIA2_CLIENT;
+
+    let low = settings.pause_on_low_battery && !charging && percent < settings.low_battery_threshold;
+    if download::set_pause_condition(client, download::PauseReason::LowBattery, low).await? {
+        let _ = handle.emit(download::BATTERY_PAUSED_EVENT, percent);
+    }
+
+    Ok(())
+}
+
+/// Called by the frontend when the OS is about to sleep. Pausing closes aria2's
+/// connections cleanly, so downloads don't sit on dead ones after wake-up.
+#[tauri::command]
+async fn prepare_for_sleep() -> Result<(), AppError> {
+    let client = &*ARIA2_CLIENT;
+    download::set_pause_condition(client, download::PauseReason::Sleep, true).await?;
+    if let Err(e) = client.save_session().await {
+        // Only possible when `--save-session` is among the extra aria2 args
+        log::debug!("Didn't save the aria2 session before sleep: {}", e);
+    }
+    Ok(())
+}
+
+/// Resumes what `prepare_for_sleep` paused once aria2 answers again. Downloads held for
+/// another reason (metered connection, low battery) stay paused until that clears.
+#[tauri::command]
+async fn resume_after_wake() -> Result<(), AppError> {
+    let client = &*ARIA2_CLIENT;
+    let deadline = std::time::Instant::now() + WAKE_RECONNECT_TIMEOUT;
+    while !client.is_running().await {
+        if std::time::Instant::now() >= deadline {
+            return Err(AppError::network("aria2 didn't respond after wake-up"));
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    download::set_pause_condition(client, download::PauseReason::Sleep, false).await?;
+    download::touch_activity();
+    Ok(())
+}
+
 #[tauri::command]
 async fn set_bandwidth_limit(
     handle: tauri::AppHandle,
     max_overall_limit_kb_per_sec: u64,
     max_download_limit_kb_per_sec: u64,
-) -> Result<(), String> {
-    let was_running = {
-        let client = ARIA2_CLIENT.lock().await;
-        client.is_running().await
-    };
-
-    if was_running {
-        let client = ARIA2_CLIENT.lock().await;
-        client.stop_daemon().await?;
-    }
-
-    let client = ARIA2_CLIENT.lock().await;
+) -> Result<(), AppError> {
+    let client = &*ARIA2_CLIENT;
     client.set_bandwidth_limit(max_overall_limit_kb_per_sec, max_download_limit_kb_per_sec);
 
     let mut settings = get_settings(&handle);
     settings.max_overall_download_limit_kb_per_sec = max_overall_limit_kb_per_sec;
     settings.max_download_limit_kb_per_sec = max_download_limit_kb_per_sec;
     save_settings(&handle, &settings)?;
+    download::reset_bandwidth_schedule();
 
-    if was_running {
-        client.start_daemon().await?;
+    if client.is_running().await {
+        let live_options = [
+            ("max-overall-download-limit", format!("{}K", max_overall_limit_kb_per_sec)),
+            ("max-download-limit", format!("{}K", max_download_limit_kb_per_sec)),
+        ];
+        for (key, value) in &live_options {
+            if let Err(e) = client.change_global_option(key, value).await {
+                // Last resort; the limits are already set for the new daemon to start with
+                log::warn!("Failed to change {} live, restarting aria2: {}", key, e);
+                return client.restart_daemon().await;
+            }
+        }
+        client.apply_download_shares().await;
     }
 
     Ok(())
 }
 
+/// Gives `gid` `percent` (1-100) of the overall limit instead of a fixed KB/s, kept up
+/// to date as the overall limit changes; `None` removes the share. Returns the KB/s
+/// applied, 0 meaning unlimited.
 #[tauri::command]
-async fn get_bandwidth_limit(handle: tauri::AppHandle) -> (u64, u64) {
+async fn set_download_share(gid: String, percent: Option<u8>) -> Result<u64, AppError> {
+    if percent.is_some_and(|percent| !(1..=100).contains(&percent)) {
+        return Err(AppError::invalid_input("Share must be between 1 and 100 percent"));
+    }
+    let client = &*ARIA2_CLIENT;
+    client.set_download_share(&gid, percent).await
+}
+
+/// Saves the default user agent and applies it to the running daemon. Hosts in
+/// `user_agent_by_domain` keep theirs.
+#[tauri::command]
+async fn set_user_agent(handle: tauri::AppHandle, user_agent: String) -> Result<(), AppError> {
+    let mut settings = get_settings(&handle);
+    settings.user_agent = user_agent.trim().to_string();
+    settings.validate().map_err(AppError::invalid_input)?;
+    save_settings(&handle, &settings)?;
+
+    let client = &*ARIA2_CLIENT;
+    if client.is_running().await {
+        client.change_global_option("user-agent", &settings.user_agent).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_bandwidth_presets(handle: tauri::AppHandle) -> Vec<BandwidthPreset> {
+    get_settings(&handle).bandwidth_presets()
+}
+
+/// Sets the overall limit to the preset's, live; the per-download limit is kept
+#[tauri::command]
+async fn apply_bandwidth_preset(handle: tauri::AppHandle, name: String) -> Result<BandwidthPreset, AppError> {
     let settings = get_settings(&handle);
-    let client = ARIA2_CLIENT.lock().await;
-    client.set_bandwidth_limit(
-        settings.max_overall_download_limit_kb_per_sec,
+    let preset = settings
+        .bandwidth_presets()
+        .into_iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| AppError::not_found(format!("No bandwidth preset named {}", name.trim())))?;
+
+    set_bandwidth_limit(
+        handle,
+        preset.limit_kb_per_sec,
         settings.max_download_limit_kb_per_sec,
+    )
+    .await?;
+    Ok(preset)
+}
+
+/// Switches between unlimited and the user's saved limits without forgetting them,
+/// applied live so active downloads aren't interrupted
+#[tauri::command]
+async fn toggle_bandwidth_limit(handle: tauri::AppHandle, enabled: bool) -> Result<(u64, u64), AppError> {
+    let mut settings = get_settings(&handle);
+    let current = SavedBandwidthLimit {
+        max_overall_download_limit_kb_per_sec: settings.max_overall_download_limit_kb_per_sec,
+        max_download_limit_kb_per_sec: settings.max_download_limit_kb_per_sec,
+    };
+
+    let applied = if enabled {
+        settings.saved_limit.unwrap_or(current)
+    } else {
+        if current.max_overall_download_limit_kb_per_sec > 0 || current.max_download_limit_kb_per_sec > 0 {
+            settings.saved_limit = Some(current);
+        }
+        SavedBandwidthLimit {
+            max_overall_download_limit_kb_per_sec: 0,
+            max_download_limit_kb_per_sec: 0,
+        }
+    };
+
+    let client = &*ARIA2_CLIENT;
+    client.set_bandwidth_limit(
+        applied.max_overall_download_limit_kb_per_sec,
+        applied.max_download_limit_kb_per_sec,
     );
-    client.get_bandwidth_limit()
+    if client.is_running().await {
+        client
+            .change_global_option(
+                "max-overall-download-limit",
+                &format!("{}K", applied.max_overall_download_limit_kb_per_sec),
+            )
+            .await?;
+        client
+            .change_global_option(
+                "max-download-limit",
+                &format!("{}K", applied.max_download_limit_kb_per_sec),
+            )
+            .await?;
+        client.apply_download_shares().await;
+    }
+
+    settings.max_overall_download_limit_kb_per_sec = applied.max_overall_download_limit_kb_per_sec;
+    settings.max_download_limit_kb_per_sec = applied.max_download_limit_kb_per_sec;
+    save_settings(&handle, &settings)?;
+    // A scheduled rule still wins while it's active, from the next check on
+    download::reset_bandwidth_schedule();
+
+    Ok((
+        applied.max_overall_download_limit_kb_per_sec,
+        applied.max_download_limit_kb_per_sec,
+    ))
 }
 
+/// The limits in effect, including one a schedule rule applied; the saved ones reach
+/// the client through `apply_settings` and the limit commands
 #[tauri::command]
-async fn get_app_settings(handle: tauri::AppHandle) -> Result<AppSettings, String> {
+fn get_bandwidth_limit() -> (u64, u64) {
+    ARIA2_CLIENT.get_bandwidth_limit()
+}
+
+#[tauri::command]
+async fn apply_connection_settings(handle: tauri::AppHandle) -> Result<(), AppError> {
+    let settings = get_settings(&handle);
+    let client = &*ARIA2_CLIENT;
+
+    client
+        .change_global_option("max-connection-per-server", &settings.max_connections.to_string())
+        .await?;
+    client
+        .change_global_option("split", &settings.split_count.to_string())
+        .await?;
+    client
+        .change_global_option("min-split-size", &settings.min_split_size)
+        .await?;
+    client
+        .change_global_option("connect-timeout", &settings.connect_timeout_secs.to_string())
+        .await?;
+    client
+        .change_global_option("lowest-speed-limit", &settings.lowest_speed_limit)
+        .await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn check_download_dir(path: String) -> download::DirCheck {
+    download::check_download_dir(std::path::Path::new(&path))
+}
+
+/// Hashes `path` with SHA-256 off the async runtime, emitting progress as it goes
+#[tauri::command]
+async fn verify_checksum(
+    handle: tauri::AppHandle,
+    path: String,
+    expected: String,
+) -> Result<download::ChecksumResult, AppError> {
+    let expected = expected.trim().to_lowercase();
+    if expected.len() != 64 || !expected.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AppError::invalid_input("Expected a SHA-256 checksum (64 hex characters)"));
+    }
+
+    let cancel = download::begin_checksum(&path);
+    let key = path.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut last_percent = None;
+        download::sha256_file(std::path::Path::new(&path), &cancel, |hashed, total| {
+            // One event per percent is plenty for a progress bar
+            let percent = (hashed * 100).checked_div(total).unwrap_or(100);
+            if last_percent != Some(percent) {
+                last_percent = Some(percent);
+                let progress = download::ChecksumProgress {
+                    path: path.clone(),
+                    hashed_bytes: hashed,
+                    total_bytes: total,
+                };
+                let _ = handle.emit(download::CHECKSUM_PROGRESS_EVENT, progress);
+            }
+        })
+    })
+    .await;
+    download::finish_checksum(&key);
+
+    let actual = result.map_err(|e| AppError::from(format!("Checksum task failed: {}", e)))??;
+    Ok(download::ChecksumResult {
+        matched: actual == expected,
+        actual,
+    })
+}
+
+#[tauri::command]
+fn cancel_checksum(path: String) -> bool {
+    download::cancel_checksum(&path)
+}
+
+/// Allocation strategies the UI should offer for downloads into `dir`
+#[tauri::command]
+fn get_file_allocation_options(dir: String) -> Vec<FileAllocation> {
+    FileAllocation::supported_for(std::path::Path::new(&dir))
+}
+
+/// The bandwidth schedule's rules, in the order they're checked
+#[tauri::command]
+async fn list_bandwidth_rules(handle: tauri::AppHandle) -> Vec<BandwidthRule> {
+    get_settings(&handle).bandwidth_schedule
+}
+
+/// Adds a rule after the existing ones, returning its new id
+#[tauri::command]
+async fn add_bandwidth_rule(handle: tauri::AppHandle, mut rule: BandwidthRule) -> Result<String, AppError> {
+    rule.validate().map_err(AppError::invalid_input)?;
+    rule.id = uuid::Uuid::new_v4().to_string();
+
+    let mut settings = get_settings(&handle);
+    settings.bandwidth_schedule.push(rule.clone());
+    save_schedule(&handle, &settings).await?;
+    Ok(rule.id)
+}
+
+#[tauri::command]
+async fn update_bandwidth_rule(handle: tauri::AppHandle, rule: BandwidthRule) -> Result<(), AppError> {
+    rule.validate().map_err(AppError::invalid_input)?;
+
+    let mut settings = get_settings(&handle);
+    let existing = settings
+        .bandwidth_schedule
+        .iter_mut()
+        .find(|r| r.id == rule.id)
+        .ok_or_else(|| AppError::not_found(format!("No bandwidth rule {}", rule.id)))?;
+    *existing = rule;
+    save_schedule(&handle, &settings).await
+}
+
+#[tauri::command]
+async fn delete_bandwidth_rule(handle: tauri::AppHandle, id: String) -> Result<(), AppError> {
+    let mut settings = get_settings(&handle);
+    let before = settings.bandwidth_schedule.len();
+    settings.bandwidth_schedule.retain(|r| r.id != id);
+    if settings.bandwidth_schedule.len() == before {
+        return Err(AppError::not_found(format!("No bandwidth rule {}", id)));
+    }
+    save_schedule(&handle, &settings).await
+}
+
+/// Saves the edited schedule and applies it right away instead of on the next check
+async fn save_schedule(handle: &tauri::AppHandle, settings: &AppSettings) -> Result<(), AppError> {
+    save_settings(handle, settings)?;
+    apply_bandwidth_schedule(handle).await;
+    Ok(())
+}
+
+/// Limits how many downloads run at once, live and for future daemon starts
+#[tauri::command]
+async fn set_max_concurrent(handle: tauri::AppHandle, n: u32) -> Result<(), AppError> {
+    if n == 0 {
+        return Err(AppError::invalid_input("Max concurrent downloads must be at least 1"));
+    }
+
+    let client = &*ARIA2_CLIENT;
+    client.set_max_concurrent_downloads(n);
+    if client.is_running().await {
+        client
+            .change_global_option("max-concurrent-downloads", &n.to_string())
+            .await?;
+    }
+
+    let mut settings = get_settings(&handle);
+    settings.max_concurrent_downloads = n;
+    save_settings(&handle, &settings)
+}
+
+/// Saves the current settings as profile `name`, replacing one with the same name
+#[tauri::command]
+async fn save_profile(handle: tauri::AppHandle, name: String) -> Result<(), AppError> {
+    let name = profile_name(&name)?;
+    let mut profiles = get_profiles(&handle);
+    profiles.insert(name.clone(), get_settings(&handle));
+    save_profiles(&handle, &profiles)?;
+    set_active_profile_name(&handle, Some(&name))
+}
+
+/// Switches to profile `name`. Limits the daemon can change at runtime apply right away;
+/// per-download options like connections and split apply to downloads added from now on.
+#[tauri::command]
+async fn load_profile(handle: tauri::AppHandle, name: String) -> Result<AppSettings, AppError> {
+    let name = profile_name(&name)?;
+    let profile = get_profiles(&handle)
+        .remove(&name)
+        .ok_or_else(|| AppError::not_found(format!("No profile named {}", name)))?;
+    profile.validate().map_err(AppError::invalid_input)?;
+
+    let client = &*ARIA2_CLIENT;
+    if client.get_rpc_url() != profile.rpc_url {
+        // Release a local daemon we spawned before pointing somewhere else
+        client.stop_daemon().await?;
+    }
+    apply_settings(client, &profile);
+
+    if client.is_running().await {
+        let live_options = [
+            (
+                "max-overall-download-limit",
+                format!("{}K", profile.max_overall_download_limit_kb_per_sec),
+            ),
+            (
+                "max-download-limit",
+                format!("{}K", profile.max_download_limit_kb_per_sec),
+            ),
+            (
+                "max-concurrent-downloads",
+                profile.max_concurrent_downloads.to_string(),
+            ),
+        ];
+        for (key, value) in live_options {
+            client.change_global_option(key, &value).await?;
+        }
+        client.apply_download_shares().await;
+    }
+
+    save_settings(&handle, &profile)?;
+    download::reset_bandwidth_schedule();
+    set_active_profile_name(&handle, Some(&name))?;
+    Ok(profile)
+}
+
+#[tauri::command]
+async fn list_profiles(handle: tauri::AppHandle) -> Vec<String> {
+    let mut names: Vec<String> = get_profiles(&handle).into_keys().collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    names
+}
+
+#[tauri::command]
+async fn delete_profile(handle: tauri::AppHandle, name: String) -> Result<(), AppError> {
+    let name = profile_name(&name)?;
+    let mut profiles = get_profiles(&handle);
+    if profiles.remove(&name).is_none() {
+        return Err(AppError::not_found(format!("No profile named {}", name)));
+    }
+    save_profiles(&handle, &profiles)?;
+    if get_active_profile_name(&handle).as_deref() == Some(name.as_str()) {
+        set_active_profile_name(&handle, None)?;
+    }
+    Ok(())
+}
+
+/// Profile last saved or loaded, restored on launch along with its settings
+#[tauri::command]
+async fn get_active_profile(handle: tauri::AppHandle) -> Option<String> {
+    get_active_profile_name(&handle)
+}
+
+#[tauri::command]
+async fn apply_option_to_download(gid: String, key: String, value: String) -> Result<String, AppError> {
+    let client = &*ARIA2_CLIENT;
+    client.change_option(&gid, &key, &value).await
+}
+
+#[tauri::command]
+async fn set_seed_ratio(gid: String, ratio: f64) -> Result<String, AppError> {
+    if !ratio.is_finite() || ratio < 0.0 {
+        return Err(AppError::invalid_input("Seed ratio must be 0 or greater"));
+    }
+    let client = &*ARIA2_CLIENT;
+    client.change_option(&gid, "seed-ratio", &ratio.to_string()).await
+}
+
+#[tauri::command]
+fn get_history_stats() -> HistoryStatsReport {
+    settings::api::get_history_stats()
+}
+
+/// For retention policies: forgets the downloads from more than `days` days ago and
+/// returns how many were dropped
+#[tauri::command]
+fn clear_history_older_than(days: u32) -> Result<usize, AppError> {
+    Ok(settings::api::clear_history_older_than(days)?)
+}
+
+#[tauri::command]
+async fn check_for_update(handle: tauri::AppHandle) -> Result<update::UpdateCheck, AppError> {
+    update::check_for_update(&handle).await
+}
+
+#[tauri::command]
+async fn install_update(handle: tauri::AppHandle) -> Result<(), AppError> {
+    update::install_update(&handle).await
+}
+
+/// Confirms the post-download hook after the user has seen `command`. Fails if the
+/// saved command has changed since, so what runs is always what was shown.
+#[tauri::command]
+async fn approve_post_download_command(handle: tauri::AppHandle, command: String) -> Result<(), AppError> {
+    let mut settings = get_settings(&handle);
+    if settings.post_download_command.as_deref() != Some(command.as_str()) {
+        return Err(AppError::invalid_input("Command doesn't match the saved post-download command"));
+    }
+    settings.approved_post_download_command = Some(command);
+    save_settings(&handle, &settings)
+}
+
+#[tauri::command]
+async fn set_theme(handle: tauri::AppHandle, window: tauri::Window, theme: String) -> Result<(), AppError> {
+    let mut settings = get_settings(&handle);
+    settings.theme = theme;
+    settings.validate().map_err(AppError::invalid_input)?;
+    save_settings(&handle, &settings)?;
+
+    window::apply_theme(&window, &settings.theme);
+    window::emit_theme_changed(&window, &settings.theme);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_app_settings(handle: tauri::AppHandle) -> Result<AppSettings, AppError> {
     Ok(get_settings(&handle))
 }
 
+/// Restores config files that no longer parse, such as after a crash mid-write, from
+/// their last good backup
 #[tauri::command]
-async fn save_app_settings(handle: tauri::AppHandle, settings: AppSettings) -> Result<(), String> {
+fn repair_config(handle: tauri::AppHandle) -> Result<Vec<ConfigRepair>, AppError> {
+    let mut repairs = settings::api::repair_config();
+
+    let store_repair = settings::api::repair_config_file(&settings_store_path(&handle)?);
+    if store_repair.state == ConfigFileState::Restored {
+        let store = settings_store(&handle);
+        store
+            .reload()
+            .map_err(|e| AppError::io(format!("Failed to reload restored settings: {}", e)))?;
+    }
+    repairs.push(store_repair);
+
+    Ok(repairs)
+}
+
+#[tauri::command]
+async fn save_app_settings(handle: tauri::AppHandle, mut settings: AppSettings) -> Result<(), AppError> {
+    settings.validate().map_err(AppError::invalid_input)?;
+    // Only `approve_post_download_command` may confirm a hook
+    settings.approved_post_download_command = get_settings(&handle).approved_post_download_command;
+    TERABOX_API.set_link_cache_ttl(Duration::from_secs(settings.link_cache_ttl_secs));
+    TERABOX_API.set_max_concurrency(settings.terabox_max_concurrency);
+    ARIA2_CLIENT.set_log_to_file(settings.log_to_file);
+    logging::set_level(&settings.log_level);
+    download::reset_bandwidth_schedule();
     save_settings(&handle, &settings)
 }
 
+/// The app log, for attaching to bug reports
+#[tauri::command]
+fn get_log_path() -> String {
+    logging::log_path().to_string_lossy().to_string()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -184,26 +2114,244 @@ pub fn run() {
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_notification::init())
+        .setup(|app| {
+            // Before the store first loads: the plugin would read a torn file as an empty
+            // store, and the next save would write the defaults over it
+            if let Ok(path) = settings_store_path(app.handle()) {
+                settings::api::repair_config_file(&path);
+            }
+            let settings = get_settings(app.handle());
+            logging::init(&settings.log_level);
+            apply_settings(&ARIA2_CLIENT, &settings);
+
+            if let Some(main_window) = app.get_webview_window("main").map(|w| w.as_ref().window()) {
+                window::restore_window_state(&main_window);
+                window::apply_theme(&main_window, &settings.theme);
+            }
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut queue_reconciled = false;
+                loop {
+                    tokio::time::sleep(download::COMPLETION_POLL_INTERVAL).await;
+                    let finished = {
+                        let client = &*ARIA2_CLIENT;
+                        if !queue_reconciled {
+                            // Once per launch, as soon as there's a daemon to compare with. Only
+                            // a full answer will do: missing from it means dropped from the queue.
+                            if let Ok(all) = client.get_all_downloads(Some(usize::MAX)).await {
+                                download::reconcile_queue(&all.into_iter().map(|d| d.gid).collect());
+                                queue_reconciled = true;
+                            }
+                        }
+                        download::record_throughput(client).await;
+                        let finished = download::record_finished_downloads(client).await;
+                        let stopped: Vec<String> = finished.iter().map(|info| info.gid.clone()).collect();
+                        advance_sequences(client, &stopped).await;
+                        finished
+                    };
+                    let completed: Vec<String> = finished
+                        .iter()
+                        .filter(|info| info.status == DownloadStatus::Complete)
+                        .map(|info| info.gid.clone())
+                        .collect();
+                    download::dequeue(&completed);
+                    auto_extract(&handle, &finished);
+                    run_post_download_hooks(&handle, finished);
+                }
+            });
+
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(download::PROGRESS_SAVE_INTERVAL).await;
+                    // All of them, so entries deep in the queue keep their progress too
+                    if let Ok(all) = ARIA2_CLIENT.get_all_downloads(Some(usize::MAX)).await {
+                        download::record_last_known(&all);
+                    }
+                }
+            });
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    apply_bandwidth_schedule(&handle).await;
+                    tokio::time::sleep(download::SCHEDULE_CHECK_INTERVAL).await;
+                }
+            });
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(download::IDLE_CHECK_INTERVAL).await;
+                    let Some(secs) = get_settings(&handle).auto_shutdown_idle_secs else {
+                        download::touch_activity();
+                        continue;
+                    };
+                    // Don't stop the daemon under a download that's being added
+                    let _adding = ADD_LOCK.lock().await;
+                    download::stop_if_idle(&ARIA2_CLIENT, Duration::from_secs(secs)).await;
+                }
+            });
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(download::STALL_CHECK_INTERVAL).await;
+                    let settings = get_settings(&handle);
+                    let Some(secs) = settings.stall_timeout_secs else {
+                        download::clear_stalls();
+                        continue;
+                    };
+                    let stalled = {
+                        let client = &*ARIA2_CLIENT;
+                        download::check_stalls(client, Duration::from_secs(secs)).await
+                    };
+                    for gid in stalled {
+                        log::warn!("Download {} stalled at 0 B/s for {}s", gid, secs);
+                        let _ = handle.emit(download::DOWNLOAD_STALLED_EVENT, &gid);
+                        // Re-resolving the link can't work without a connection
+                        if settings.restart_stalled_downloads && !download::is_offline() {
+                            if let Err(e) = retry_download(handle.clone(), gid.clone()).await {
+                                log::warn!("Failed to restart stalled download {}: {}", gid, e);
+                            }
+                        }
+                    }
+                }
+            });
+
+            Ok(())
+        })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::ThemeChanged(_) => {
+                let theme = get_settings(window.app_handle()).theme;
+                // A forced theme doesn't follow the OS, so only `system` has news to report
+                if theme == "system" {
+                    window::emit_theme_changed(window, &theme);
+                }
+            }
+            tauri::WindowEvent::CloseRequested { .. } => window::save_window_state(window),
+            _ => {}
+        })
         .invoke_handler(tauri::generate_handler![
             get_terabox_info,
             get_download_link,
+            login_terabox,
+            logout_terabox,
+            is_terabox_logged_in,
+            get_terabox_max_concurrency,
+            set_terabox_max_concurrency,
+            resolve_direct_url,
+            list_folder,
+            get_folder_size,
+            add_terabox_file,
+            cancel_folder_scan,
             extract_shorturl,
+            get_thumbnail,
+            check_link_health,
+            benchmark_link,
+            clear_link_cache,
+            estimate_batch_time,
             start_aria2,
             stop_aria2,
+            download_aria2_binary,
             is_aria2_running,
+            is_aria2_remote,
+            get_aria2_version,
+            get_aria2_logs,
+            ping_aria2,
+            validate_aria2_config,
+            set_rpc_endpoint,
             add_download,
+            add_download_async,
+            add_downloads_batch,
+            get_group_progress,
+            pause_group,
+            resume_group,
+            cancel_group,
+            redownload,
+            retry_download,
+            retry_errored,
+            notify_network_changed,
+            get_stalled_downloads,
+            copy_download_info,
+            copy_all_failed,
+            get_download_origin,
             get_download_status,
+            change_download_dir,
+            extract_download,
+            get_download_files,
+            get_download_servers,
+            select_files,
             pause_download,
             resume_download,
             cancel_download,
+            cancel_and_delete,
+            get_queue,
+            export_queue,
+            import_queue,
+            bootstrap,
+            set_priority,
+            rename_download,
+            remove_download_result,
+            clear_completed,
+            cancel_errored,
+            clear_all_results,
             get_all_downloads,
+            get_waiting,
+            get_stopped,
+            get_global_stats,
+            get_overall_progress,
+            get_downloads_by_status,
+            reveal_in_folder,
             pause_all_downloads,
             resume_all_downloads,
+            prepare_for_sleep,
+            resume_after_wake,
+            set_metered,
+            set_battery_level,
             set_bandwidth_limit,
+            set_download_share,
+            toggle_bandwidth_limit,
+            set_user_agent,
+            get_bandwidth_presets,
+            apply_bandwidth_preset,
             get_bandwidth_limit,
+            apply_connection_settings,
+            set_max_concurrent,
+            list_bandwidth_rules,
+            add_bandwidth_rule,
+            update_bandwidth_rule,
+            delete_bandwidth_rule,
+            save_profile,
+            load_profile,
+            list_profiles,
+            delete_profile,
+            get_active_profile,
+            get_file_allocation_options,
+            check_download_dir,
+            verify_checksum,
+            cancel_checksum,
+            apply_option_to_download,
+            set_seed_ratio,
+            get_history_stats,
+            clear_history_older_than,
             get_app_settings,
+            set_theme,
+            approve_post_download_command,
+            check_for_update,
+            install_update,
             save_app_settings,
+            get_log_path,
+            repair_config,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|handle, event| match event {
+            // Every change is already on disk; closed, the store misses the plugin's
+            // in-place save on exit
+            tauri::RunEvent::ExitRequested { .. } => settings_store(handle).close_resource(),
+            // Statics are never dropped, so the daemon is released here
+            tauri::RunEvent::Exit => ARIA2_CLIENT.release_on_exit(),
+            _ => {}
+        });
 }
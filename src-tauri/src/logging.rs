@@ -0,0 +1,120 @@
+use regex::Regex;
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+/// A log bigger than this is moved to `trauso.log.1` at startup
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Secrets that can end up in a log line: aria2 RPC tokens and `--rpc-secret`, and
+/// Terabox session cookies as a header, a JSON field or a `Debug` dump
+static SECRETS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    vec![
+        (Regex::new(r#"token:[^\s",\]]+"#).unwrap(), "token:***"),
+        (Regex::new(r"(?i)(--rpc-secret[= ])\S+").unwrap(), "${1}***"),
+        (
+            Regex::new(r#"(?i)(\bcookies?"?\s*[:=]\s*(?:Some\()?"?)[^"\r\n]+"#).unwrap(),
+            "${1}***",
+        ),
+        (Regex::new(r"(?i)(\bndus=)[^;\s]+").unwrap(), "${1}***"),
+    ]
+});
+
+struct FileLogger {
+    file: Mutex<File>,
+}
+
+impl log::Log for FileLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            redact(&message)
+        );
+        let _ = self.file.lock().unwrap().write_all(line.as_bytes());
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().flush();
+    }
+}
+
+pub fn log_path() -> PathBuf {
+    crate::settings::api::get_config_dir().join("logs").join("trauso.log")
+}
+
+/// Starts writing `log` and `tracing` output to `log_path()`. Only the first call does
+/// anything; use `set_level` to change the level afterwards.
+pub fn init(level: &str) {
+    let path = log_path();
+    let opened = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| {
+            if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_SIZE) {
+                fs::rename(&path, path.with_extension("log.1"))?;
+            }
+            OpenOptions::new().create(true).append(true).open(&path)
+        });
+
+    match opened {
+        Ok(file) => {
+            let logger = FileLogger {
+                file: Mutex::new(file),
+            };
+            if log::set_boxed_logger(Box::new(logger)).is_ok() {
+                set_level(level);
+            }
+        }
+        Err(e) => eprintln!("Failed to open log file {}: {}", path.display(), e),
+    }
+}
+
+/// One of `LOG_LEVELS`; anything else falls back to `info`
+pub fn set_level(level: &str) {
+    log::set_max_level(level.parse().unwrap_or(log::LevelFilter::Info));
+}
+
+pub fn redact(message: &str) -> Cow<'_, str> {
+    let mut redacted = Cow::Borrowed(message);
+    for (pattern, replacement) in SECRETS.iter() {
+        if let Cow::Owned(replaced) = pattern.replace_all(&redacted, *replacement) {
+            redacted = Cow::Owned(replaced);
+        }
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets() {
+        assert_eq!(
+            redact(r#"params: ["token:s3cret", "2089b05ecca3d829"]"#),
+            r#"params: ["token:***", "2089b05ecca3d829"]"#
+        );
+        assert_eq!(
+            redact("aria2c --rpc-secret=s3cret --continue=true"),
+            "aria2c --rpc-secret=*** --continue=true"
+        );
+        assert_eq!(redact("Cookie: ndus=abc; lang=en"), "Cookie: ***");
+        assert_eq!(redact(r#"{"cookies":"ndus=abc","ok":true}"#), r#"{"cookies":"***","ok":true}"#);
+        assert_eq!(redact(r#"cookies: Some("ndus=abc")"#), r#"cookies: Some("***")"#);
+        assert_eq!(redact("set ndus=abc;"), "set ndus=***;");
+        assert_eq!(redact("nothing secret"), "nothing secret");
+    }
+}
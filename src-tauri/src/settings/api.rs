@@ -1,103 +1,774 @@
 use crate::settings::types::*;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
-static SETTINGS: Mutex<Option<AppSettings>> = Mutex::new(None);
-static HISTORY: Mutex<Option<DownloadHistory>> = Mutex::new(None);
+static HISTORY: Mutex<Option<HistoryLog>> = Mutex::new(None);
+static STATS: Mutex<Option<HistoryStats>> = Mutex::new(None);
+static SETTINGS_STORE: Mutex<Option<Arc<dyn SettingsStore>>> = Mutex::new(None);
 
+/// Key of `AppSettings` in the settings store
+pub const SETTINGS_KEY: &str = "app_settings";
+
+/// Where the settings persist. The app's "settings" `tauri_plugin_store`, which also
+/// holds the profiles, is the only copy; nothing here caches them, so every reader
+/// sees the last write.
+pub trait SettingsStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<serde_json::Value>;
+    fn set(&self, key: &str, value: serde_json::Value);
+}
+
+/// Registers the settings store unless one already is; `store` is only called then
+pub fn init_settings_store(store: impl FnOnce() -> Arc<dyn SettingsStore>) {
+    SETTINGS_STORE.lock().unwrap().get_or_insert_with(store);
+}
+
+fn settings_store() -> Option<Arc<dyn SettingsStore>> {
+    SETTINGS_STORE.lock().unwrap().clone()
+}
+
+/// The stored settings, migrated; defaults before the store is registered
 pub fn load_settings() -> AppSettings {
-    let mut settings_guard = SETTINGS.lock().unwrap();
+    settings_store()
+        .and_then(|store| store.get(SETTINGS_KEY))
+        .map(migrate)
+        .unwrap_or_default()
+}
 
-    if let Some(ref settings) = *settings_guard {
-        return settings.clone();
-    }
+/// Upgrades a stored settings blob of any version to the current `AppSettings`.
+///
+/// Fields are applied one at a time on top of the defaults, so a single
+/// malformed or renamed field only resets itself instead of the whole config.
+/// Version 0 is the unversioned shape written before `version` existed.
+pub fn migrate(value: serde_json::Value) -> AppSettings {
+    let stored_version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
 
-    let config_dir = get_config_dir();
-    let path = config_dir.join("settings.json");
-    let settings = if path.exists() {
-        fs::read_to_string(&path)
-            .ok()
-            .and_then(|content| serde_json::from_str(&content).ok())
-            .unwrap_or_default()
-    } else {
-        AppSettings::default()
+    let mut merged = match serde_json::to_value(AppSettings::default()) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return AppSettings::default(),
     };
 
-    *settings_guard = Some(settings.clone());
+    if let serde_json::Value::Object(fields) = value {
+        for (key, field) in fields {
+            if key == "version" || !merged.contains_key(&key) {
+                continue;
+            }
+
+            let mut candidate = merged.clone();
+            candidate.insert(key.clone(), field);
+            if serde_json::from_value::<AppSettings>(serde_json::Value::Object(candidate.clone())).is_ok() {
+                merged = candidate;
+            } else {
+                log::warn!("Dropping invalid setting '{}' from v{} settings", key, stored_version);
+            }
+        }
+    }
+
+    let mut settings: AppSettings =
+        serde_json::from_value(serde_json::Value::Object(merged)).unwrap_or_default();
+    settings.version = SETTINGS_VERSION;
     settings
 }
 
 pub fn save_settings(settings: &AppSettings) -> Result<(), String> {
-    let config_dir = get_config_dir();
-    let path = config_dir.join("settings.json");
-    let content = serde_json::to_string_pretty(settings)
+    let store = settings_store().ok_or("Settings store isn't open yet")?;
+    let value = serde_json::to_value(settings)
         .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    store.set(SETTINGS_KEY, value);
+    Ok(())
+}
 
-    fs::write(&path, content)
-        .map_err(|e| format!("Failed to write settings: {}", e))?;
+/// Items kept in the history; older ones live on only in the lifetime stats
+const HISTORY_LIMIT: usize = 100;
+/// Lines the log may grow past `HISTORY_LIMIT` before it's rewritten with just those
+const HISTORY_COMPACT_SLACK: usize = 100;
 
-    let mut settings_guard = SETTINGS.lock().unwrap();
-    *settings_guard = Some(settings.clone());
+/// The history as loaded from history.jsonl, which holds one item per line, oldest
+/// first. Adding an item appends a line instead of rewriting the file, and a line torn
+/// by a crash only loses that one item.
+struct HistoryLog {
+    history: DownloadHistory,
+    /// Lines in the file, including ones past `HISTORY_LIMIT` not compacted away yet
+    lines: usize,
+}
 
-    Ok(())
+fn history_log_path() -> PathBuf {
+    get_config_dir().join("history.jsonl")
 }
 
-pub fn load_history() -> DownloadHistory {
-    let mut history_guard = HISTORY.lock().unwrap();
+/// What `read_history_log` found: items newest first, and how many lines there were
+struct HistoryFile {
+    items: Vec<DownloadHistoryItem>,
+    lines: usize,
+    /// Lines that didn't parse and were skipped
+    unreadable: usize,
+}
 
-    if let Some(ref history) = *history_guard {
-        return history.clone();
+fn read_history_log(path: &Path) -> Option<HistoryFile> {
+    let content = fs::read_to_string(path).ok()?;
+    let lines: Vec<&str> = content.lines().filter(|line| !line.trim().is_empty()).collect();
+    let mut items: Vec<DownloadHistoryItem> = lines
+        .iter()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let unreadable = lines.len() - items.len();
+    if unreadable > 0 {
+        log::warn!("Skipped {} unreadable history lines", unreadable);
     }
+    items.reverse();
+    items.truncate(HISTORY_LIMIT);
+    Some(HistoryFile { items, lines: lines.len(), unreadable })
+}
 
-    let config_dir = get_config_dir();
-    let path = config_dir.join("history.json");
-    let history = if path.exists() {
-        fs::read_to_string(&path)
-            .ok()
-            .and_then(|content| serde_json::from_str(&content).ok())
-            .unwrap_or_default()
-    } else {
-        DownloadHistory::default()
-    };
-
-    *history_guard = Some(history.clone());
-    history
+/// Rewrites the whole log from `items`, newest first
+fn write_history_log(path: &Path, items: &[DownloadHistoryItem]) -> Result<(), String> {
+    let mut content = String::new();
+    for item in items.iter().rev() {
+        let line = serde_json::to_string(item)
+            .map_err(|e| format!("Failed to serialize history: {}", e))?;
+        content.push_str(&line);
+        content.push('\n');
+    }
+    write_atomic(path, &content).map_err(|e| format!("Failed to write history: {}", e))
 }
 
-pub fn save_history(history: &DownloadHistory) -> Result<(), String> {
-    let config_dir = get_config_dir();
-    let path = config_dir.join("history.json");
-    let content = serde_json::to_string_pretty(history)
+/// Appends one line, compacting the log once it has grown well past `HISTORY_LIMIT`
+fn append_history_item(log: &mut HistoryLog, path: &Path, item: DownloadHistoryItem) -> Result<(), String> {
+    let mut line = serde_json::to_string(&item)
         .map_err(|e| format!("Failed to serialize history: {}", e))?;
-
-    fs::write(&path, content)
+    line.push('\n');
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
         .map_err(|e| format!("Failed to write history: {}", e))?;
+    log.lines += 1;
+
+    log.history.items.insert(0, item);
+    log.history.items.truncate(HISTORY_LIMIT);
+    if log.lines > HISTORY_LIMIT + HISTORY_COMPACT_SLACK {
+        write_history_log(path, &log.history.items)?;
+        log.lines = log.history.items.len();
+    }
+    Ok(())
+}
 
+/// Runs `f` on the history, loading it first. A history.json from before the log
+/// existed is moved over to it.
+fn with_history<T>(f: impl FnOnce(&mut HistoryLog) -> T) -> T {
     let mut history_guard = HISTORY.lock().unwrap();
-    *history_guard = Some(history.clone());
+    let log = history_guard.get_or_insert_with(|| {
+        let path = history_log_path();
+        if let Some(file) = read_history_log(&path) {
+            return HistoryLog {
+                history: DownloadHistory { items: file.items },
+                lines: file.lines,
+            };
+        }
 
-    Ok(())
+        let legacy = get_config_dir().join("history.json");
+        let mut history: DownloadHistory = fs::read_to_string(&legacy)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        history.items.truncate(HISTORY_LIMIT);
+        if legacy.exists() {
+            match write_history_log(&path, &history.items) {
+                Ok(()) => {
+                    let _ = fs::remove_file(&legacy);
+                }
+                Err(e) => log::warn!("Failed to move history.json over to history.jsonl: {}", e),
+            }
+        }
+        let lines = history.items.len();
+        HistoryLog { history, lines }
+    });
+    f(log)
 }
 
-pub fn add_history_item(item: DownloadHistoryItem) -> Result<(), String> {
-    let mut history = load_history();
-    history.items.insert(0, item);
+pub fn load_history() -> DownloadHistory {
+    with_history(|log| log.history.clone())
+}
+
+/// Records `item`, keeping as much of its URL as `history_privacy` allows
+pub fn add_history_item(mut item: DownloadHistoryItem) -> Result<(), String> {
+    item.url = load_settings().history_privacy.redact(item.url);
+    let mut stats = load_stats();
+    stats.record(&item);
+    save_stats(&stats)?;
+
+    with_history(|log| append_history_item(log, &history_log_path(), item))
+}
+
+/// Lifetime totals, kept in stats.json so they outlive the capped history
+pub fn load_stats() -> HistoryStats {
+    let mut stats_guard = STATS.lock().unwrap();
 
-    if history.items.len() > 100 {
-        history.items.truncate(100);
+    if let Some(ref stats) = *stats_guard {
+        return stats.clone();
     }
 
-    save_history(&history)
+    let path = get_config_dir().join("stats.json");
+    let stats: HistoryStats = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    *stats_guard = Some(stats.clone());
+    stats
+}
+
+pub fn save_stats(stats: &HistoryStats) -> Result<(), String> {
+    let path = get_config_dir().join("stats.json");
+    let content = serde_json::to_string_pretty(stats)
+        .map_err(|e| format!("Failed to serialize stats: {}", e))?;
+
+    write_atomic(&path, &content)
+        .map_err(|e| format!("Failed to write stats: {}", e))?;
+
+    *STATS.lock().unwrap() = Some(stats.clone());
+
+    Ok(())
+}
+
+pub fn get_history_stats() -> HistoryStatsReport {
+    HistoryStatsReport {
+        windowed: HistoryStats::from_items(&load_history().items),
+        lifetime: load_stats(),
+    }
 }
 
 pub fn clear_history() -> Result<(), String> {
-    save_history(&DownloadHistory::default())
+    with_history(|log| {
+        write_history_log(&history_log_path(), &[])?;
+        log.history = DownloadHistory::default();
+        log.lines = 0;
+        Ok(())
+    })
 }
 
-fn get_config_dir() -> PathBuf {
+/// Drops the items downloaded more than `days` days ago and returns how many. Items
+/// without a readable date are kept. Lifetime stats are unaffected.
+pub fn clear_history_older_than(days: u32) -> Result<usize, String> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
+    with_history(|log| retain_history_since(log, &history_log_path(), cutoff))
+}
+
+fn retain_history_since(
+    log: &mut HistoryLog,
+    path: &Path,
+    cutoff: chrono::DateTime<chrono::Utc>,
+) -> Result<usize, String> {
+    let before = log.history.items.len();
+    let kept: Vec<DownloadHistoryItem> = log
+        .history
+        .items
+        .iter()
+        .filter(|item| {
+            chrono::DateTime::parse_from_rfc3339(&item.downloaded_at)
+                .map_or(true, |at| at >= cutoff)
+        })
+        .cloned()
+        .collect();
+    let dropped = before - kept.len();
+    if dropped > 0 {
+        write_history_log(path, &kept)?;
+        log.lines = kept.len();
+        log.history.items = kept;
+    }
+    Ok(dropped)
+}
+
+pub fn get_config_dir() -> PathBuf {
     dirs::config_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("trauso")
 }
 
+/// `settings.json` -> `settings.json.<suffix>`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn is_readable_json(path: &Path) -> bool {
+    fs::read(path)
+        .ok()
+        .is_some_and(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).is_ok())
+}
+
+/// Writes through a temp file and a rename, so a crash mid-write leaves the old file or
+/// the new one but never half of either. The old file is kept as `.bak` if it still parsed.
+pub fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    write_atomic_keeping(path, content, is_readable_json)
+}
+
+/// `write_atomic` for the settings store. Its `.bak` is only refreshed from a store that
+/// holds the settings, so one saved before they were, or after a reset, can't replace it.
+pub fn write_settings_store(path: &Path, content: &str) -> std::io::Result<()> {
+    write_atomic_keeping(path, content, holds_settings)
+}
+
+fn holds_settings(path: &Path) -> bool {
+    fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
+        .is_some_and(|store| store.get(SETTINGS_KEY).is_some())
+}
+
+#[tracing::instrument(skip(content, keep_backup), fields(path = %path.display()))]
+fn write_atomic_keeping(path: &Path, content: &str, keep_backup: fn(&Path) -> bool) -> std::io::Result<()> {
+    let tmp = sibling(path, "tmp");
+    {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+    }
+    if keep_backup(path) {
+        fs::copy(path, sibling(path, "bak"))?;
+    }
+    fs::rename(&tmp, path)
+}
+
+/// Puts back the `.bak` of a config file that no longer parses
+pub fn repair_config_file(path: &Path) -> ConfigRepair {
+    let mut repair = ConfigRepair {
+        file: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        state: ConfigFileState::Healthy,
+        backup_saved_at: None,
+    };
+    if is_readable_json(path) {
+        return repair;
+    }
+
+    let backup = sibling(path, "bak");
+    repair.state = if !is_readable_json(&backup) {
+        if path.exists() {
+            ConfigFileState::Unrecoverable
+        } else {
+            ConfigFileState::Missing
+        }
+    } else {
+        match fs::copy(&backup, path) {
+            Ok(_) => {
+                tracing::info!("Restored {} from its backup", path.display());
+                repair.backup_saved_at = fs::metadata(&backup)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .map(|time| chrono::DateTime::<chrono::Local>::from(time).to_rfc3339());
+                ConfigFileState::Restored
+            }
+            Err(e) => {
+                log::warn!("Failed to restore {}: {}", path.display(), e);
+                ConfigFileState::Unrecoverable
+            }
+        }
+    };
+    repair
+}
+
+/// The history log has no backup; a line that doesn't parse is dropped, keeping the rest
+fn repair_history_log(path: &Path) -> ConfigRepair {
+    let mut repair = ConfigRepair {
+        file: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        state: ConfigFileState::Healthy,
+        backup_saved_at: None,
+    };
+    let Some(file) = read_history_log(path) else {
+        repair.state = ConfigFileState::Missing;
+        return repair;
+    };
+    if file.unreadable > 0 {
+        repair.state = match write_history_log(path, &file.items) {
+            Ok(()) => ConfigFileState::Restored,
+            Err(e) => {
+                log::warn!("Failed to repair {}: {}", path.display(), e);
+                ConfigFileState::Unrecoverable
+            }
+        };
+    }
+    repair
+}
+
+/// Repairs history and stats, then drops the cached copies so the next load reads
+/// what's on disk now. The settings live in the plugin store, which the caller repairs.
+#[tracing::instrument]
+pub fn repair_config() -> Vec<ConfigRepair> {
+    let repairs = vec![
+        repair_history_log(&history_log_path()),
+        repair_config_file(&get_config_dir().join("stats.json")),
+    ];
+
+    *HISTORY.lock().unwrap() = None;
+    *STATS.lock().unwrap() = None;
+    repairs
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terabox::FileCategory;
+
+    #[test]
+    fn test_migrate_v0_settings() {
+        let v0 = serde_json::json!({
+            "download_dir": "D:/Terabox",
+            "max_connections": 8,
+            "split_count": 4,
+            "min_split_size": "4M",
+            "user_agent": "custom-agent",
+            "auto_start_aria2": false,
+            "theme": "dark",
+            "max_overall_download_limit_kb_per_sec": 2048,
+            "max_download_limit_kb_per_sec": 512
+        });
+
+        let settings = migrate(v0);
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.download_dir, "D:/Terabox");
+        assert_eq!(settings.max_connections, 8);
+        assert_eq!(settings.split_count, 4);
+        assert_eq!(settings.min_split_size, "4M");
+        assert!(!settings.auto_start_aria2);
+        assert_eq!(settings.theme, "dark");
+        assert_eq!(settings.max_overall_download_limit_kb_per_sec, 2048);
+        assert_eq!(settings.rpc_url, AppSettings::default().rpc_url);
+    }
+
+    #[test]
+    fn test_migrate_keeps_valid_fields_when_one_is_malformed() {
+        let v0 = serde_json::json!({
+            "download_dir": "/mnt/data",
+            "max_connections": "sixteen",
+            "unknown_field": true
+        });
+
+        let settings = migrate(v0);
+        assert_eq!(settings.download_dir, "/mnt/data");
+        assert_eq!(settings.max_connections, AppSettings::default().max_connections);
+    }
+
+    #[test]
+    fn test_connections_scale_with_file_size() {
+        const MB: u64 = 1024 * 1024;
+        let mut settings = AppSettings {
+            max_connections: 16,
+            ..AppSettings::default()
+        };
+        assert_eq!(settings.connections_for_size(MB), None);
+
+        settings.adaptive_connections = true;
+        assert_eq!(settings.connections_for_size(MB), Some(1));
+        assert_eq!(settings.connections_for_size(5 * MB), Some(1));
+        assert_eq!(settings.connections_for_size(5 * MB + 1), Some(4));
+        assert_eq!(settings.connections_for_size(500 * MB), None);
+
+        settings.max_connections = 2;
+        assert_eq!(settings.connections_for_size(20 * MB), Some(2));
+    }
+
+    #[test]
+    fn test_dns_servers_must_be_ips() {
+        let with_dns = |servers: &str| AppSettings {
+            async_dns_server: Some(servers.to_string()),
+            ..AppSettings::default()
+        };
+        assert!(with_dns("1.1.1.1, 2606:4700:4700::1111").validate().is_ok());
+        assert!(with_dns("").validate().is_ok());
+        assert!(with_dns("dns.google").validate().is_err());
+        assert!(with_dns("8.8.8.8,").validate().is_err());
+    }
+
+    #[test]
+    fn test_user_agent_by_domain() {
+        let mut settings = AppSettings {
+            user_agent: "default-agent".to_string(),
+            user_agent_by_domain: [("terabox.com".to_string(), "terabox-agent".to_string())].into(),
+            ..AppSettings::default()
+        };
+        assert_eq!(settings.user_agent_for("https://d3.terabox.com/file?x=1"), "terabox-agent");
+        assert_eq!(settings.user_agent_for("https://TERABOX.com/s/1abc"), "terabox-agent");
+        assert_eq!(settings.user_agent_for("https://notterabox.com/file"), "default-agent");
+        assert_eq!(settings.user_agent_for("not a url"), "default-agent");
+
+        settings
+            .user_agent_by_domain
+            .insert("d3.terabox.com".to_string(), "cdn-agent".to_string());
+        assert_eq!(settings.user_agent_for("https://d3.terabox.com/file"), "cdn-agent");
+    }
+
+    #[test]
+    fn test_lowest_speed_limit_syntax() {
+        let with_limit = |limit: &str| AppSettings {
+            lowest_speed_limit: limit.to_string(),
+            ..AppSettings::default()
+        };
+        for limit in ["0", "10K", "1M", "2048"] {
+            assert!(with_limit(limit).validate().is_ok(), "{}", limit);
+        }
+        for limit in ["", "K", "10KB", "1.5M", "-1"] {
+            assert!(with_limit(limit).validate().is_err(), "{}", limit);
+        }
+    }
+
+    #[test]
+    fn test_repair_restores_last_good_version() {
+        let dir = std::env::temp_dir().join(format!("trauso-repair-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.json");
+
+        write_atomic(&path, r#"{"items":[1]}"#).unwrap();
+        write_atomic(&path, r#"{"items":[1,2]}"#).unwrap();
+        assert_eq!(repair_config_file(&path).state, ConfigFileState::Healthy);
+
+        // Truncated by a crash outside `write_atomic`
+        fs::write(&path, r#"{"items":[1,"#).unwrap();
+        let repair = repair_config_file(&path);
+        assert_eq!(repair.state, ConfigFileState::Restored);
+        assert!(repair.backup_saved_at.is_some());
+        assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"items":[1]}"#);
+
+        assert_eq!(repair_config_file(&dir.join("stats.json")).state, ConfigFileState::Missing);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_settings_store_backup_needs_the_settings() {
+        let dir = std::env::temp_dir().join(format!("trauso-store-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings");
+        let backup = sibling(&path, "bak");
+
+        write_settings_store(&path, r#"{"app_settings":{"max_connections":3}}"#).unwrap();
+        write_settings_store(&path, r#"{"app_settings":{"max_connections":4}}"#).unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), r#"{"app_settings":{"max_connections":3}}"#);
+
+        // A store without the settings, like one left by a reset, keeps the last good copy
+        write_settings_store(&path, r#"{"window_state":{}}"#).unwrap();
+        write_settings_store(&path, r#"{"window_state":{"x":1}}"#).unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), r#"{"app_settings":{"max_connections":4}}"#);
+
+        // A torn store is put back from it
+        fs::write(&path, r#"{"app_settings":{"max_conn"#).unwrap();
+        assert_eq!(repair_config_file(&path).state, ConfigFileState::Restored);
+        assert!(holds_settings(&path));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn history_item(id: usize) -> DownloadHistoryItem {
+        DownloadHistoryItem {
+            id: id.to_string(),
+            filename: format!("file{}.mkv", id),
+            url: None,
+            size: 1024,
+            status: "completed".to_string(),
+            downloaded_at: String::new(),
+            path: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_history_log_appends_and_compacts() {
+        let dir = std::env::temp_dir().join(format!("trauso-history-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.jsonl");
+        let mut log = HistoryLog { history: DownloadHistory::default(), lines: 0 };
+
+        for id in 0..HISTORY_LIMIT + HISTORY_COMPACT_SLACK {
+            append_history_item(&mut log, &path, history_item(id)).unwrap();
+        }
+        let file = read_history_log(&path).unwrap();
+        assert_eq!(file.lines, HISTORY_LIMIT + HISTORY_COMPACT_SLACK);
+        assert_eq!(file.items.len(), HISTORY_LIMIT);
+        assert_eq!(file.items[0].id, (HISTORY_LIMIT + HISTORY_COMPACT_SLACK - 1).to_string());
+
+        // One more line and the log is rewritten with just the items kept
+        append_history_item(&mut log, &path, history_item(1000)).unwrap();
+        let file = read_history_log(&path).unwrap();
+        assert_eq!((file.lines, log.lines), (HISTORY_LIMIT, HISTORY_LIMIT));
+        assert_eq!(file.items[0].id, "1000");
+
+        // A line torn by a crash costs only that item
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(br#"{"id":"1001","filena"#)
+            .unwrap();
+        let file = read_history_log(&path).unwrap();
+        assert_eq!((file.items[0].id.as_str(), file.unreadable), ("1000", 1));
+        assert_eq!(repair_history_log(&path).state, ConfigFileState::Restored);
+        assert_eq!(read_history_log(&path).unwrap().unreadable, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Stands in for the plugin store
+    #[derive(Default)]
+    struct MemoryStore(Mutex<std::collections::HashMap<String, serde_json::Value>>);
+
+    impl SettingsStore for MemoryStore {
+        fn get(&self, key: &str) -> Option<serde_json::Value> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        fn set(&self, key: &str, value: serde_json::Value) {
+            self.0.lock().unwrap().insert(key.to_string(), value);
+        }
+    }
+
+    #[test]
+    fn test_settings_have_one_source_of_truth() {
+        init_settings_store(|| Arc::new(MemoryStore::default()));
+        let store = settings_store().unwrap();
+
+        // Saved here, seen by whoever reads the store directly
+        let settings = AppSettings {
+            history_privacy: HistoryPrivacy::Hashed,
+            max_connections: 3,
+            ..AppSettings::default()
+        };
+        save_settings(&settings).unwrap();
+        let stored = migrate(store.get(SETTINGS_KEY).unwrap());
+        assert_eq!(stored.history_privacy, HistoryPrivacy::Hashed);
+        assert_eq!(stored.max_connections, 3);
+
+        // Written straight to the store, e.g. when a profile is switched to, and seen here
+        let mut value = serde_json::to_value(&settings).unwrap();
+        value["max_connections"] = serde_json::json!(5);
+        value["history_privacy"] = serde_json::json!("omitted");
+        store.set(SETTINGS_KEY, value);
+        let loaded = load_settings();
+        assert_eq!(loaded.max_connections, 5);
+        assert_eq!(loaded.history_privacy, HistoryPrivacy::Omitted);
+    }
+
+    #[test]
+    fn test_history_privacy_modes() {
+        let url = Some("https://terabox.com/s/1abc".to_string());
+        assert_eq!(HistoryPrivacy::Full.redact(url.clone()), url);
+        assert_eq!(HistoryPrivacy::Omitted.redact(url.clone()), None);
+
+        let hashed = HistoryPrivacy::Hashed.redact(url.clone()).unwrap();
+        assert!(hashed.starts_with("sha256:") && hashed.len() == "sha256:".len() + 64);
+        assert!(!hashed.contains("terabox"));
+        // The same share always hashes the same
+        assert_eq!(HistoryPrivacy::Hashed.redact(url), Some(hashed));
+
+        for privacy in [HistoryPrivacy::Full, HistoryPrivacy::Hashed, HistoryPrivacy::Omitted] {
+            assert_eq!(privacy.redact(None), None);
+            assert_eq!(privacy.redact(Some(String::new())), None);
+        }
+
+        // Items recorded before `url` was optional, and ones without it, still load
+        let item: DownloadHistoryItem = serde_json::from_str(
+            r#"{"id":"a","filename":"a.mkv","url":"","size":1,"status":"completed","downloaded_at":"","path":""}"#,
+        )
+        .unwrap();
+        assert_eq!(item.url.as_deref(), Some(""));
+        let item: DownloadHistoryItem = serde_json::from_str(
+            r#"{"id":"a","filename":"a.mkv","size":1,"status":"completed","downloaded_at":"","path":""}"#,
+        )
+        .unwrap();
+        assert!(item.url.is_none());
+    }
+
+    #[test]
+    fn test_clear_history_older_than() {
+        let dir = std::env::temp_dir().join(format!("trauso-history-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.jsonl");
+        let mut log = HistoryLog { history: DownloadHistory::default(), lines: 0 };
+
+        let now = chrono::Utc::now();
+        for (id, age_days) in [(0, 40), (1, 10), (2, 1)] {
+            let item = DownloadHistoryItem {
+                downloaded_at: (now - chrono::Duration::days(age_days)).to_rfc3339(),
+                ..history_item(id)
+            };
+            append_history_item(&mut log, &path, item).unwrap();
+        }
+        // Undated items are kept
+        append_history_item(&mut log, &path, history_item(3)).unwrap();
+
+        let cutoff = now - chrono::Duration::days(30);
+        assert_eq!(retain_history_since(&mut log, &path, cutoff).unwrap(), 1);
+        let ids: Vec<_> = read_history_log(&path).unwrap().items.into_iter().map(|item| item.id).collect();
+        assert_eq!(ids, ["3", "2", "1"]);
+        assert_eq!(log.lines, 3);
+
+        let cutoff = now - chrono::Duration::days(5);
+        assert_eq!(retain_history_since(&mut log, &path, cutoff).unwrap(), 1);
+        assert_eq!(retain_history_since(&mut log, &path, cutoff).unwrap(), 0);
+        assert_eq!(read_history_log(&path).unwrap().items.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// `cargo test --release -- --ignored --nocapture bench_history_inserts`
+    #[test]
+    #[ignore]
+    fn bench_history_inserts() {
+        const INSERTS: usize = 10_000;
+        let dir = std::env::temp_dir().join(format!("trauso-history-bench-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("history.jsonl");
+        let mut log = HistoryLog { history: DownloadHistory::default(), lines: 0 };
+        let start = std::time::Instant::now();
+        for id in 0..INSERTS {
+            append_history_item(&mut log, &path, history_item(id)).unwrap();
+        }
+        let appended = start.elapsed();
+
+        // What `add_history_item` did before: rewrite the whole file every time
+        let path = dir.join("history.json");
+        let mut history = DownloadHistory::default();
+        let start = std::time::Instant::now();
+        for id in 0..INSERTS {
+            history.items.insert(0, history_item(id));
+            history.items.truncate(HISTORY_LIMIT);
+            write_atomic(&path, &serde_json::to_string_pretty(&history).unwrap()).unwrap();
+        }
+        let rewritten = start.elapsed();
+        fs::remove_dir_all(&dir).unwrap();
+
+        println!(
+            "{} inserts: appended in {:.0?} ({:.1} µs each), rewritten in {:.0?} ({:.1} µs each)",
+            INSERTS,
+            appended,
+            appended.as_secs_f64() * 1e6 / INSERTS as f64,
+            rewritten,
+            rewritten.as_secs_f64() * 1e6 / INSERTS as f64
+        );
+    }
+
+    #[test]
+    fn test_history_stats_from_items() {
+        let item = |filename: &str, size: u64, status: &str| DownloadHistoryItem {
+            id: filename.to_string(),
+            filename: filename.to_string(),
+            url: None,
+            size,
+            status: status.to_string(),
+            downloaded_at: String::new(),
+            path: String::new(),
+        };
+
+        let stats = HistoryStats::from_items(&[
+            item("a.mp4", 100, "completed"),
+            item("b.mkv", 50, "completed"),
+            item("c.zip", 70, "failed"),
+        ]);
+
+        assert_eq!(stats.total_downloads, 3);
+        assert_eq!(stats.total_bytes, 150);
+        assert_eq!(stats.success_count, 2);
+        assert_eq!(stats.failure_count, 1);
+        assert_eq!(stats.by_category.get(&FileCategory::Video), Some(&2));
+        assert_eq!(stats.by_category.get(&FileCategory::Archive), Some(&1));
+    }
+}
@@ -1,3 +1,3 @@
-mod api;
+pub mod api;
 pub mod types;
 
@@ -1,7 +1,54 @@
+use crate::aria2::{FileAllocation, OverwritePolicy};
+use crate::terabox::FileCategory;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Current on-disk schema version of `AppSettings`
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// aria2c flags the app manages itself and that `extra_aria2_args` may not override
+pub const RESERVED_ARIA2_ARGS: &[&str] = &[
+    "--enable-rpc",
+    "--rpc-listen-all",
+    "--rpc-listen-port",
+    "--rpc-secret",
+    "--max-overall-download-limit",
+    "--max-download-limit",
+    "--seed-ratio",
+    "--max-concurrent-downloads",
+    "--file-allocation",
+    "--allow-overwrite",
+    "--auto-file-renaming",
+    "--connect-timeout",
+    "--lowest-speed-limit",
+    "--disable-ipv6",
+    "--async-dns-server",
+];
+
+/// Accepted values of `AppSettings::theme`
+pub const THEMES: &[&str] = &["light", "dark", "system"];
+
+/// Presets every install has, as (name, KB/s); 0 is unlimited
+pub const BUILTIN_BANDWIDTH_PRESETS: &[(&str, u64)] = &[
+    ("512 KB/s", 512),
+    ("1 MB/s", 1024),
+    ("5 MB/s", 5 * 1024),
+    ("Unlimited", 0),
+];
+
+/// aria2's syntax for speeds in bytes/s: digits with an optional `K` or `M` suffix
+fn is_speed_value(value: &str) -> bool {
+    let digits = value.strip_suffix(['K', 'k', 'M', 'm']).unwrap_or(value);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Accepted values of `AppSettings::log_level`, least to most verbose
+pub const LOG_LEVELS: &[&str] = &["error", "warn", "info", "debug", "trace"];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
+    pub version: u32,
     pub download_dir: String,
     pub max_connections: u32,
     pub split_count: u32,
@@ -11,11 +58,149 @@ pub struct AppSettings {
     pub theme: String,
     pub max_overall_download_limit_kb_per_sec: u64,
     pub max_download_limit_kb_per_sec: u64,
+    pub rpc_url: String,
+    pub rpc_secret: Option<String>,
+    pub extra_aria2_args: Vec<String>,
+    pub seed_ratio: f64,
+    pub link_cache_ttl_secs: u64,
+    pub pause_on_metered: bool,
+    pub aria2_startup_timeout_secs: u64,
+    pub log_to_file: bool,
+    pub max_concurrent_downloads: u32,
+    pub pause_on_low_battery: bool,
+    /// Battery percentage below which downloads pause while unplugged
+    pub low_battery_threshold: u8,
+    /// Last non-zero limits, restored when the "unlimited" toggle is switched off again
+    pub saved_limit: Option<SavedBandwidthLimit>,
+    pub file_allocation: FileAllocation,
+    /// Stop the local daemon after this long without active or waiting downloads
+    pub auto_shutdown_idle_secs: Option<u64>,
+    /// Shell command run when a download completes; `{path}` becomes the file's path
+    pub post_download_command: Option<String>,
+    pub enable_post_download_hooks: bool,
+    /// The command the user confirmed after seeing it. A hook only runs while this
+    /// matches `post_download_command`, so editing the command asks again.
+    pub approved_post_download_command: Option<String>,
+    /// Unpack zip, rar and 7z downloads into a sibling folder once they complete
+    pub auto_extract: bool,
+    pub delete_after_extract: bool,
+    pub overwrite_policy: OverwritePolicy,
+    /// Recurring overall limits by time of day; the first matching rule wins, and
+    /// `max_overall_download_limit_kb_per_sec` applies when none does
+    pub bandwidth_schedule: Vec<BandwidthRule>,
+    /// Folder per file category, relative to `download_dir` unless absolute
+    pub category_dirs: HashMap<FileCategory, String>,
+    /// How much goes into the app log, one of `LOG_LEVELS`
+    pub log_level: String,
+    /// Use `connection_tiers` for files whose size is known up front
+    pub adaptive_connections: bool,
+    /// Smallest matching tier wins; bigger files use `max_connections`
+    pub connection_tiers: Vec<ConnectionTier>,
+    /// User-defined overall limits, listed after `BUILTIN_BANDWIDTH_PRESETS`
+    pub custom_bandwidth_presets: Vec<BandwidthPreset>,
+    /// Flag an active download after this long at 0 B/s; None turns the check off
+    pub stall_timeout_secs: Option<u64>,
+    /// Replace a stalled download with a freshly resolved one, like `retry_download`
+    pub restart_stalled_downloads: bool,
+    /// Keep aria2 on IPv4, for networks that advertise IPv6 but can't route it
+    pub disable_ipv6: bool,
+    /// Comma-separated nameserver IPs for aria2's own resolver; None uses the system's
+    pub async_dns_server: Option<String>,
+    /// Give up on connecting to a server after this long
+    pub connect_timeout_secs: u32,
+    /// aria2 aborts a download slower than this, e.g. `10K`; `0` never does. The
+    /// download then fails and `retry_download` can start it over on a fresh link.
+    pub lowest_speed_limit: String,
+    /// User agent by host, instead of `user_agent`; a domain also covers its subdomains
+    pub user_agent_by_domain: HashMap<String, String>,
+    /// Leave the local daemon downloading after the app quits
+    pub keep_daemon_on_exit: bool,
+    /// Most Terabox API requests in flight at once; too many and Terabox may block the IP
+    pub terabox_max_concurrency: usize,
+    /// Once the network is back, retry the downloads that failed while it was down
+    pub retry_on_reconnect: bool,
+    /// How much of each download's URL the history keeps
+    pub history_privacy: HistoryPrivacy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BandwidthPreset {
+    pub name: String,
+    /// Overall limit; 0 is unlimited
+    pub limit_kb_per_sec: u64,
+    /// Set on the built-in presets, which can't be changed
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+/// "Files up to `max_size_mb` get `connections` connections"
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConnectionTier {
+    pub max_size_mb: u64,
+    pub connections: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SavedBandwidthLimit {
+    pub max_overall_download_limit_kb_per_sec: u64,
+    pub max_download_limit_kb_per_sec: u64,
+}
+
+/// "Limit to `limit_kb_per_sec` between `start` and `end` on these weekdays"
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BandwidthRule {
+    /// Assigned when the rule is added
+    #[serde(default)]
+    pub id: String,
+    /// Bit 0 is Monday, bit 6 Sunday
+    pub weekdays: u8,
+    /// Local time as `HH:MM`
+    pub start: String,
+    /// Local time as `HH:MM`; earlier than `start` means the rule runs past midnight
+    pub end: String,
+    pub limit_kb_per_sec: u64,
+}
+
+impl BandwidthRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.weekdays == 0 || self.weekdays > 0x7F {
+            return Err("Pick at least one weekday".to_string());
+        }
+        let start = parse_time_of_day(&self.start).ok_or("Start must be a time like 08:00")?;
+        let end = parse_time_of_day(&self.end).ok_or("End must be a time like 18:00")?;
+        if start == end {
+            return Err("Start and end can't be the same time".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the rule covers `minute` (since midnight) on `weekday` (0 = Monday).
+    /// After midnight, an overnight rule counts as part of the day it started on.
+    pub fn matches(&self, weekday: u32, minute: u32) -> bool {
+        let (Some(start), Some(end)) = (parse_time_of_day(&self.start), parse_time_of_day(&self.end)) else {
+            return false;
+        };
+        let on = |day: u32| self.weekdays & (1 << (day % 7)) != 0;
+
+        if start < end {
+            on(weekday) && (start..end).contains(&minute)
+        } else {
+            (on(weekday) && minute >= start) || (on(weekday + 6) && minute < end)
+        }
+    }
+}
+
+/// Minutes since midnight for `HH:MM`
+fn parse_time_of_day(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
+            version: SETTINGS_VERSION,
             download_dir: "downloads".to_string(),
             max_connections: 16,
             split_count: 16,
@@ -25,11 +210,210 @@ impl Default for AppSettings {
             theme: "system".to_string(),
             max_overall_download_limit_kb_per_sec: 0,
             max_download_limit_kb_per_sec: 0,
+            rpc_url: "http://localhost:6800/jsonrpc".to_string(),
+            rpc_secret: None,
+            extra_aria2_args: Vec::new(),
+            seed_ratio: 1.0,
+            link_cache_ttl_secs: 600,
+            pause_on_metered: false,
+            aria2_startup_timeout_secs: 5,
+            log_to_file: false,
+            max_concurrent_downloads: 5,
+            pause_on_low_battery: false,
+            low_battery_threshold: 15,
+            saved_limit: None,
+            file_allocation: FileAllocation::None,
+            auto_shutdown_idle_secs: None,
+            post_download_command: None,
+            enable_post_download_hooks: false,
+            approved_post_download_command: None,
+            auto_extract: false,
+            delete_after_extract: false,
+            overwrite_policy: OverwritePolicy::Rename,
+            bandwidth_schedule: Vec::new(),
+            category_dirs: HashMap::new(),
+            log_level: "info".to_string(),
+            adaptive_connections: false,
+            connection_tiers: vec![
+                ConnectionTier { max_size_mb: 5, connections: 1 },
+                ConnectionTier { max_size_mb: 50, connections: 4 },
+            ],
+            custom_bandwidth_presets: Vec::new(),
+            stall_timeout_secs: Some(180),
+            restart_stalled_downloads: false,
+            disable_ipv6: false,
+            async_dns_server: None,
+            connect_timeout_secs: 60,
+            lowest_speed_limit: "0".to_string(),
+            user_agent_by_domain: ["terabox.com", "1024tera.com", "terabox.app"]
+                .into_iter()
+                .map(|domain| (domain.to_string(), crate::terabox::USER_AGENT.to_string()))
+                .collect(),
+            keep_daemon_on_exit: false,
+            terabox_max_concurrency: 4,
+            retry_on_reconnect: true,
+            history_privacy: HistoryPrivacy::Full,
         }
     }
 }
 
 impl AppSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=16).contains(&self.max_connections) {
+            return Err("Max connections must be between 1 and 16".to_string());
+        }
+        if !THEMES.contains(&self.theme.as_str()) {
+            return Err(format!("Theme must be one of {}", THEMES.join(", ")));
+        }
+        if !LOG_LEVELS.contains(&self.log_level.as_str()) {
+            return Err(format!("Log level must be one of {}", LOG_LEVELS.join(", ")));
+        }
+        if self.split_count == 0 {
+            return Err("Split count must be at least 1".to_string());
+        }
+        if self.max_concurrent_downloads == 0 {
+            return Err("Max concurrent downloads must be at least 1".to_string());
+        }
+        if !self.seed_ratio.is_finite() || self.seed_ratio < 0.0 {
+            return Err("Seed ratio must be 0 or greater".to_string());
+        }
+        if !(1..=100).contains(&self.low_battery_threshold) {
+            return Err("Low battery threshold must be between 1 and 100".to_string());
+        }
+        if !(1..=120).contains(&self.aria2_startup_timeout_secs) {
+            return Err("aria2 startup timeout must be between 1 and 120 seconds".to_string());
+        }
+        if self.auto_shutdown_idle_secs.is_some_and(|secs| secs < 10) {
+            return Err("Idle shutdown must be at least 10 seconds".to_string());
+        }
+        if let Some(servers) = self.async_dns_server.as_deref().filter(|s| !s.trim().is_empty()) {
+            if let Some(bad) = servers
+                .split(',')
+                .map(str::trim)
+                .find(|server| server.parse::<std::net::IpAddr>().is_err())
+            {
+                return Err(format!("DNS server must be an IP address, not \"{}\"", bad));
+            }
+        }
+        if self.user_agent.trim().is_empty() {
+            return Err("User agent can't be empty".to_string());
+        }
+        if self
+            .user_agent_by_domain
+            .iter()
+            .any(|(domain, agent)| domain.trim().is_empty() || agent.trim().is_empty())
+        {
+            return Err("Per-domain user agents need both a domain and a user agent".to_string());
+        }
+        if !(1..=600).contains(&self.connect_timeout_secs) {
+            return Err("Connect timeout must be between 1 and 600 seconds".to_string());
+        }
+        if !(1..=32).contains(&self.terabox_max_concurrency) {
+            return Err("Terabox request concurrency must be between 1 and 32".to_string());
+        }
+        if !is_speed_value(&self.lowest_speed_limit) {
+            return Err("Lowest speed limit must be a number with an optional K or M, like 10K".to_string());
+        }
+        if self.stall_timeout_secs.is_some_and(|secs| secs < 30) {
+            return Err("Stall timeout must be at least 30 seconds".to_string());
+        }
+        for rule in &self.bandwidth_schedule {
+            rule.validate()?;
+        }
+        for tier in &self.connection_tiers {
+            if tier.max_size_mb == 0 {
+                return Err("Connection tier sizes must be at least 1 MB".to_string());
+            }
+            if !(1..=16).contains(&tier.connections) {
+                return Err("Connection tiers must use between 1 and 16 connections".to_string());
+            }
+        }
+        let mut preset_names: Vec<String> = BUILTIN_BANDWIDTH_PRESETS
+            .iter()
+            .map(|(name, _)| name.to_lowercase())
+            .collect();
+        for preset in &self.custom_bandwidth_presets {
+            let name = preset.name.trim().to_lowercase();
+            if name.is_empty() {
+                return Err("Bandwidth presets need a name".to_string());
+            }
+            if preset_names.contains(&name) {
+                return Err(format!("There's already a bandwidth preset named {}", preset.name.trim()));
+            }
+            preset_names.push(name);
+        }
+        if self.category_dirs.values().any(|dir| dir.trim().is_empty()) {
+            return Err("Category folders can't be empty".to_string());
+        }
+        for arg in &self.extra_aria2_args {
+            if !arg.starts_with("--") {
+                return Err(format!("Invalid aria2 argument '{}': must start with --", arg));
+            }
+            let flag = arg.split('=').next().unwrap_or(arg);
+            if RESERVED_ARIA2_ARGS.contains(&flag) {
+                return Err(format!("aria2 argument '{}' is managed by the app and can't be overridden", flag));
+            }
+        }
+        Ok(())
+    }
+
+    /// Connections for a file of `size` bytes, when `adaptive_connections` scales it down.
+    /// Never more than `max_connections`.
+    pub fn connections_for_size(&self, size: u64) -> Option<u32> {
+        if !self.adaptive_connections {
+            return None;
+        }
+        self.connection_tiers
+            .iter()
+            .filter(|tier| size <= tier.max_size_mb.saturating_mul(1024 * 1024))
+            .min_by_key(|tier| tier.max_size_mb)
+            .map(|tier| tier.connections.min(self.max_connections))
+    }
+
+    /// The user agent to fetch `url` with: the most specific `user_agent_by_domain`
+    /// entry matching its host, else `user_agent`
+    pub fn user_agent_for(&self, url: &str) -> &str {
+        let Some(host) = reqwest::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_lowercase)) else {
+            return &self.user_agent;
+        };
+        self.user_agent_by_domain
+            .iter()
+            .filter(|(domain, _)| {
+                let domain = domain.trim().trim_start_matches('.').to_lowercase();
+                host == domain || host.ends_with(&format!(".{}", domain))
+            })
+            .max_by_key(|(domain, _)| domain.len())
+            .map_or(&self.user_agent, |(_, agent)| agent)
+    }
+
+    /// Built-in presets first, then the user's own
+    pub fn bandwidth_presets(&self) -> Vec<BandwidthPreset> {
+        let builtin = BUILTIN_BANDWIDTH_PRESETS.iter().map(|(name, limit)| BandwidthPreset {
+            name: name.to_string(),
+            limit_kb_per_sec: *limit,
+            builtin: true,
+        });
+        let custom = self.custom_bandwidth_presets.iter().map(|preset| BandwidthPreset {
+            builtin: false,
+            ..preset.clone()
+        });
+        builtin.chain(custom).collect()
+    }
+
+    /// Where downloads of `category` go by default, if routed anywhere special
+    pub fn category_dir(&self, category: FileCategory) -> Option<String> {
+        let dir = self.category_dirs.get(&category)?;
+        let path = std::path::Path::new(&self.download_dir).join(dir.trim());
+        Some(path.to_string_lossy().to_string())
+    }
+
+    /// The hook to run on completion, if hooks are on and the command was confirmed
+    pub fn post_download_hook(&self) -> Option<&str> {
+        let command = self.post_download_command.as_deref().filter(|c| !c.trim().is_empty())?;
+        let approved = self.approved_post_download_command.as_deref() == Some(command);
+        (self.enable_post_download_hooks && approved).then_some(command)
+    }
+
     pub fn format_bandwidth(kb_per_sec: u64) -> String {
         if kb_per_sec == 0 {
             "Unlimited".to_string()
@@ -41,11 +425,38 @@ impl AppSettings {
     }
 }
 
+/// What `add_history_item` keeps of a download's URL
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryPrivacy {
+    #[default]
+    Full,
+    /// `sha256:` and the hex digest, so repeats of a share can still be matched up
+    Hashed,
+    Omitted,
+}
+
+impl HistoryPrivacy {
+    pub fn redact(self, url: Option<String>) -> Option<String> {
+        let url = url.filter(|url| !url.is_empty())?;
+        match self {
+            HistoryPrivacy::Full => Some(url),
+            HistoryPrivacy::Hashed => {
+                use sha2::{Digest, Sha256};
+                Some(format!("sha256:{}", hex::encode(Sha256::digest(url.as_bytes()))))
+            }
+            HistoryPrivacy::Omitted => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadHistoryItem {
     pub id: String,
     pub filename: String,
-    pub url: String,
+    /// Missing when not known or not kept, see `HistoryPrivacy`
+    #[serde(default)]
+    pub url: Option<String>,
     pub size: u64,
     pub status: String,
     pub downloaded_at: String,
@@ -56,3 +467,68 @@ pub struct DownloadHistoryItem {
 pub struct DownloadHistory {
     pub items: Vec<DownloadHistoryItem>,
 }
+
+/// Aggregate download counts, either over the current history or over the app's lifetime
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HistoryStats {
+    pub total_downloads: u64,
+    /// Bytes of successfully completed downloads
+    pub total_bytes: u64,
+    pub success_count: u64,
+    pub failure_count: u64,
+    pub by_category: HashMap<FileCategory, u64>,
+}
+
+impl HistoryStats {
+    pub fn record(&mut self, item: &DownloadHistoryItem) {
+        self.total_downloads += 1;
+        if item.status == "completed" {
+            self.success_count += 1;
+            self.total_bytes += item.size;
+        } else {
+            self.failure_count += 1;
+        }
+        *self
+            .by_category
+            .entry(FileCategory::from_filename(&item.filename))
+            .or_default() += 1;
+    }
+
+    pub fn from_items(items: &[DownloadHistoryItem]) -> Self {
+        let mut stats = Self::default();
+        for item in items {
+            stats.record(item);
+        }
+        stats
+    }
+}
+
+/// `windowed` covers only the capped history list; `lifetime` survives truncation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryStatsReport {
+    pub windowed: HistoryStats,
+    pub lifetime: HistoryStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigFileState {
+    Healthy,
+    /// Never written yet, and no backup either
+    Missing,
+    /// Didn't parse and was replaced by its backup; for the history log, lines that
+    /// didn't parse were dropped
+    Restored,
+    /// Didn't parse and had no usable backup; it loads as defaults
+    Unrecoverable,
+}
+
+/// What `repair_config` found for one config file
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigRepair {
+    pub file: String,
+    pub state: ConfigFileState,
+    /// When the restored backup was written, RFC 3339
+    pub backup_saved_at: Option<String>,
+}
@@ -1,31 +1,91 @@
+use crate::error::AppError;
 use crate::terabox::types::*;
+use crate::terabox::session::load_session_cookie;
+use base64::prelude::*;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
 use regex::Regex;
 use reqwest::Client;
-use std::sync::LazyLock;
-use std::time::Duration;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 const BASE_URL: &str = "https://terabox.hnn.workers.dev";
-const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/143.0.0.0 Safari/537.36";
-
-static SHORTURL_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
-    vec![
-        Regex::new(r"terabox\.com/s/([^/?\u{0026}]+)").unwrap(),
-        Regex::new(r"1024tera\.com/s/([^/?\u{0026}]+)").unwrap(),
-        Regex::new(r"1024terabox\.com/s/([^/?\u{0026}]+)").unwrap(),
-        Regex::new(r"4funbox\.com/s/([^/?\u{0026}]+)").unwrap(),
-        Regex::new(r"mirrobox\.com/s/([^/?\u{0026}]+)").unwrap(),
-        Regex::new(r"teraboxapp\.com/s/([^/?\u{0026}]+)").unwrap(),
-        Regex::new(r"terabox\.app/s/([^/?\u{0026}]+)").unwrap(),
-        Regex::new(r"surl=([^\u{0026}]+)").unwrap(),
-        Regex::new(r"/s/([^/?\u{0026}]+)").unwrap(),
-    ]
-});
+const DEFAULT_LINK_CACHE_TTL: Duration = Duration::from_secs(600);
+/// Matches `AppSettings::terabox_max_concurrency`'s default
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+/// Known to be accepted by Terabox's API and CDNs
+pub const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/143.0.0.0 Safari/537.36";
+/// Terabox's errno for a file over the free account's download size limit
+const FILE_TOO_LARGE_ERRNO: i64 = 9019;
+/// The free account's download size limit, for responses that don't give it: 4 GiB
+const FREE_ACCOUNT_SIZE_LIMIT: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Event emitted with a `FolderScanProgress` after each page of a folder listing
+pub const FOLDER_SCAN_PROGRESS_EVENT: &str = "folder-scan-progress";
+
+/// Entries requested per page while enumerating a folder
+const FOLDER_PAGE_SIZE: usize = 100;
+/// How often a rate-limited page is retried before the scan gives up
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+const RATE_LIMIT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RATE_LIMIT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Terabox's own domains plus the regional and mobile mirrors that serve the same shares.
+/// Subdomains (`www.`, `m.`, ...) are accepted for all of them.
+const TERABOX_DOMAINS: &[&str] = &[
+    "terabox.com",
+    "terabox.app",
+    "terabox.fun",
+    "teraboxapp.com",
+    "teraboxlink.com",
+    "teraboxshare.com",
+    "terasharelink.com",
+    "terafileshare.com",
+    "freeterabox.com",
+    "1024tera.com",
+    "1024terabox.com",
+    "4funbox.com",
+    "4funbox.co",
+    "mirrobox.com",
+    "momerybox.com",
+    "nephobox.com",
+    "tibibox.com",
+    "gibibox.com",
+];
+
+/// CDN hosts that serve the same signed direct links interchangeably
+const DIRECT_LINK_HOSTS: &[&str] = &["d.terabox.com", "data.terabox.com"];
+
+static SHORTURL_ID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9_-]+$").unwrap());
 
 static SHORTURL_DIRECT: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9_-]{10,25}$").unwrap());
 
+/// Resolved links keyed by (shareid, fs_id), with the time they were resolved
+type LinkCache = HashMap<(i64, String), (DownloadLink, Instant)>;
+
+type InfoRequest = Shared<BoxFuture<'static, Result<TeraboxInfo, AppError>>>;
+
 pub struct TeraboxApi {
     client: Client,
+    base_url: String,
+    /// `get_info` requests in flight, keyed by shorturl
+    info_requests: Mutex<HashMap<String, InfoRequest>>,
+    link_cache: Mutex<LinkCache>,
+    link_cache_ttl: Mutex<Duration>,
+    /// Complete folder listings keyed by shorturl, kept for the session
+    folder_cache: Mutex<HashMap<String, FolderListing>>,
+    /// Cancel flags of the folder scans in progress, keyed by shorturl
+    folder_scans: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    /// `ndus=...` of the logged-in account, sent with every request
+    session_cookie: Mutex<Option<String>>,
+    /// Bounds the API requests in flight; replaced by `set_max_concurrency`
+    request_limit: Mutex<Arc<Semaphore>>,
+    max_concurrency: Mutex<usize>,
 }
 
 impl Default for TeraboxApi {
@@ -36,6 +96,12 @@ impl Default for TeraboxApi {
 
 impl TeraboxApi {
     pub fn new() -> Self {
+        let api = Self::with_base_url(BASE_URL);
+        api.set_session_cookie(load_session_cookie());
+        api
+    }
+
+    fn with_base_url(base_url: &str) -> Self {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
             .user_agent(USER_AGENT)
@@ -44,27 +110,125 @@ impl TeraboxApi {
             .build()
             .expect("Failed to create HTTP client");
 
-        Self { client }
+        Self {
+            client,
+            base_url: base_url.to_string(),
+            info_requests: Mutex::new(HashMap::new()),
+            link_cache: Mutex::new(HashMap::new()),
+            link_cache_ttl: Mutex::new(DEFAULT_LINK_CACHE_TTL),
+            folder_cache: Mutex::new(HashMap::new()),
+            folder_scans: Mutex::new(HashMap::new()),
+            session_cookie: Mutex::new(None),
+            request_limit: Mutex::new(Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENCY))),
+            max_concurrency: Mutex::new(DEFAULT_MAX_CONCURRENCY),
+        }
     }
 
-    pub fn extract_shorturl(url: &str) -> Option<String> {
-        for pattern in SHORTURL_PATTERNS.iter() {
-            if let Some(caps) = pattern.captures(url) {
-                if let Some(m) = caps.get(1) {
-                    return Some(m.as_str().to_string());
-                }
-            }
+    /// Requests already in flight finish under the old limit
+    pub fn set_max_concurrency(&self, limit: usize) {
+        let limit = limit.max(1);
+        let mut current = self.max_concurrency.lock().unwrap();
+        if *current != limit {
+            *current = limit;
+            *self.request_limit.lock().unwrap() = Arc::new(Semaphore::new(limit));
+        }
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        *self.max_concurrency.lock().unwrap()
+    }
+
+    fn request_limit(&self) -> Arc<Semaphore> {
+        self.request_limit.lock().unwrap().clone()
+    }
+
+    /// Waits for a free slot under `set_max_concurrency`
+    async fn acquire(limit: Arc<Semaphore>) -> OwnedSemaphorePermit {
+        limit.acquire_owned().await.expect("request semaphore is never closed")
+    }
+
+    /// Links and listings fetched under the previous login are dropped
+    pub fn set_session_cookie(&self, cookie: Option<String>) {
+        *self.session_cookie.lock().unwrap() = cookie;
+        self.clear_link_cache();
+        self.folder_cache.lock().unwrap().clear();
+    }
+
+    pub fn is_logged_in(&self) -> bool {
+        self.session_cookie.lock().unwrap().is_some()
+    }
+
+    fn session_cookie(&self) -> Option<String> {
+        self.session_cookie.lock().unwrap().clone()
+    }
+
+    /// A rejected request while logged in means the saved session is no good anymore
+    fn session_error(&self, error: AppError) -> AppError {
+        match error {
+            AppError::TeraboxAuth { message } if self.is_logged_in() => AppError::SessionExpired {
+                message: format!("Terabox login expired, log in again ({})", message),
+            },
+            other => other,
         }
+    }
+
+    /// A TTL of zero disables link caching
+    pub fn set_link_cache_ttl(&self, ttl: Duration) {
+        *self.link_cache_ttl.lock().unwrap() = ttl;
+    }
+
+    pub fn clear_link_cache(&self) {
+        self.link_cache.lock().unwrap().clear();
+    }
+
+    /// Drops any cached entry that resolved to `download_link`, forcing a fresh resolution next time
+    pub fn invalidate_link(&self, download_link: &str) {
+        self.link_cache
+            .lock()
+            .unwrap()
+            .retain(|_, (link, _)| link.download_link.as_deref() != Some(download_link));
+    }
+
+    /// Pulls the shorturl out of a share link, or accepts a bare shorturl.
+    ///
+    /// Handles `/s/<shorturl>` paths as well as `?surl=<id>` links (`/wap/share/filelist`,
+    /// `/sharing/link`, ...). A `surl` is the shorturl without its leading `1`, so it's
+    /// prefixed to match what `/s/` links carry.
+    pub fn extract_shorturl(url: &str) -> Option<String> {
+        let url = url.trim();
 
         if SHORTURL_DIRECT.is_match(url) {
             return Some(url.to_string());
         }
 
-        None
+        let with_scheme = if url.contains("://") {
+            url.to_string()
+        } else {
+            format!("https://{}", url)
+        };
+        let parsed = reqwest::Url::parse(&with_scheme).ok()?;
+        let host = parsed.host_str()?.to_lowercase();
+        let known_host = TERABOX_DOMAINS
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+        if !known_host {
+            return None;
+        }
+
+        if let Some((_, surl)) = parsed.query_pairs().find(|(k, _)| k == "surl") {
+            return SHORTURL_ID.is_match(&surl).then(|| format!("1{}", surl));
+        }
+
+        let mut segments = parsed.path_segments()?;
+        segments.find(|segment| *segment == "s")?;
+        segments
+            .next()
+            .filter(|id| SHORTURL_ID.is_match(id))
+            .map(|id| id.to_string())
     }
 
-    fn get_headers() -> Vec<(&'static str, String)> {
-        vec![
+    fn get_headers(session_cookie: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
             ("Accept", "*/*".to_string()),
             ("Accept-Language", "id-ID,id;q=0.9,en-US;q=0.8,en;q=0.7".to_string()),
             ("Cache-Control", "no-cache".to_string()),
@@ -81,25 +245,62 @@ impl TeraboxApi {
             ("Priority", "u=1, i".to_string()),
             ("Referer", format!("{}/", BASE_URL)),
             ("Origin", BASE_URL.to_string()),
-        ]
+        ];
+        if let Some(cookie) = session_cookie {
+            headers.push(("Cookie", cookie.to_string()));
+        }
+        headers
     }
 
-    pub async fn get_info(&self, url: &str) -> Result<TeraboxInfo, String> {
-        let shorturl = Self::extract_shorturl(url).ok_or("Invalid TeraBox URL")?;
-        println!("Extracted shorturl: {}", shorturl);
-        
-        let headers = Self::get_headers();
+    /// Concurrent calls for the same share wait on a single request and share its result
+    pub async fn get_info(&self, url: &str) -> Result<TeraboxInfo, AppError> {
+        let shorturl = Self::extract_shorturl(url)
+            .ok_or_else(|| AppError::invalid_input("Invalid TeraBox URL"))?;
+        tracing::debug!("Extracted shorturl: {}", shorturl);
+
+        let request = self
+            .info_requests
+            .lock()
+            .unwrap()
+            .entry(shorturl.clone())
+            .or_insert_with(|| {
+                let (client, base_url) = (self.client.clone(), self.base_url.clone());
+                let limit = self.request_limit();
+                Self::fetch_info(client, base_url, shorturl.clone(), self.session_cookie(), limit)
+                    .boxed()
+                    .shared()
+            })
+            .clone();
+        let result = request.clone().await;
+
+        // Whoever gets here first retires it; a later call then asks again
+        let mut in_flight = self.info_requests.lock().unwrap();
+        if in_flight.get(&shorturl).is_some_and(|r| r.ptr_eq(&request)) {
+            in_flight.remove(&shorturl);
+        }
+        result.map_err(|e| self.session_error(e))
+    }
+
+    #[tracing::instrument(skip(client, base_url, session_cookie, limit))]
+    async fn fetch_info(
+        client: Client,
+        base_url: String,
+        shorturl: String,
+        session_cookie: Option<String>,
+        limit: Arc<Semaphore>,
+    ) -> Result<TeraboxInfo, AppError> {
+        let _permit = Self::acquire(limit).await;
+        let headers = Self::get_headers(session_cookie.as_deref());
         
         // Try get-info-new first, then fallback to get-info
         let endpoints = ["/api/get-info-new", "/api/get-info"];
         
-        let mut last_error = String::from("Unknown error");
+        let mut last_error = AppError::from("Unknown error");
 
         for api_endpoint in &endpoints {
-            let request_url = format!("{}{}", BASE_URL, api_endpoint);
+            let request_url = format!("{}{}", base_url, api_endpoint);
             
-            let mut request = self
-                .client
+            let mut request = client
                 .get(&request_url)
                 .query(&[("shorturl", &shorturl), ("pwd", &"".to_string())]);
 
@@ -111,7 +312,7 @@ impl TeraboxApi {
                 Ok(response) => {
                     let status = response.status();
                     if !status.is_success() {
-                        last_error = format!("Server returned error status: {}", status);
+                        last_error = Self::status_error(status);
                         continue;
                     }
 
@@ -119,51 +320,357 @@ impl TeraboxApi {
                     let text = match response.text().await {
                         Ok(t) => t,
                         Err(e) => {
-                            last_error = format!("Failed to read response: {}", e);
+                            last_error = AppError::network(format!("Failed to read response: {}", e));
                             continue;
                         }
                     };
                     
-                    println!("Info API Response ({}): {}", api_endpoint, text);
+                    tracing::debug!("Info API Response ({}): {}", api_endpoint, text);
 
                     match serde_json::from_str::<WorkerInfoResponse>(&text) {
-                        Ok(data) => {
-                            if data.ok {
-                                let list = data
-                                    .list
-                                    .unwrap_or_default()
-                                    .into_iter()
-                                    .map(|item| Self::convert_file_item(item))
-                                    .collect();
-
-                                return Ok(TeraboxInfo {
-                                    ok: true,
-                                    shareid: data.shareid.unwrap_or(0),
-                                    uk: data.uk.unwrap_or(0),
-                                    sign: data.sign.unwrap_or_default(),
-                                    timestamp: data.timestamp.unwrap_or(0),
-                                    list,
-                                    error_message: None,
-                                });
-                            } else {
-                                last_error = data.message.unwrap_or("API returned ok=false".to_string());
-                            }
-                        }
+                        Ok(data) => match Self::info_from_response(data) {
+                            Ok(info) => return Ok(info),
+                            Err(e) => last_error = e,
+                        },
                         Err(e) => {
-                            last_error = format!("Failed to parse JSON: {}. Response len: {}", e, text.len());
+                            last_error = AppError::invalid_response(format!(
+                                "Failed to parse JSON: {}. Response len: {}",
+                                e,
+                                text.len()
+                            ));
                         }
                     }
                 }
                 Err(e) => {
-                    last_error = format!("Request failed: {}", e);
+                    last_error = AppError::network(format!("Request failed: {}", e));
+                }
+            }
+        }
+
+        Err(last_error.context("Failed to get info"))
+    }
+
+    /// Enumerates every file and folder in a share, page by page and folder by folder.
+    /// Rate-limited pages are retried with jittered backoff, `on_progress` is called after
+    /// each page, and `cancel_folder_scan` stops the scan with what was found so far.
+    /// Complete listings are cached for the session, so reopening a share is instant.
+    #[tracing::instrument(skip(self, on_progress))]
+    pub async fn list_folder(
+        &self,
+        url: &str,
+        mut on_progress: impl FnMut(&FolderScanProgress),
+    ) -> Result<FolderListing, AppError> {
+        let shorturl = Self::extract_shorturl(url)
+            .ok_or_else(|| AppError::invalid_input("Invalid TeraBox URL"))?;
+        if let Some(listing) = self.folder_cache.lock().unwrap().get(&shorturl) {
+            return Ok(listing.clone());
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.folder_scans
+            .lock()
+            .unwrap()
+            .insert(shorturl.clone(), cancel.clone());
+        let result = self.scan_folder(&shorturl, &cancel, &mut on_progress).await;
+        self.folder_scans.lock().unwrap().remove(&shorturl);
+
+        let listing = result.map_err(|e| self.session_error(e).context("Failed to list folder"))?;
+        if listing.complete {
+            self.folder_cache
+                .lock()
+                .unwrap()
+                .insert(shorturl, listing.clone());
+        }
+        Ok(listing)
+    }
+
+    /// Finds `fs_id` anywhere in a share, along with what `get_download_link` needs to
+    /// resolve it. Files at the top come from `get_info`; deeper ones from `list_folder`.
+    pub async fn find_file(
+        &self,
+        url: &str,
+        fs_id: &str,
+    ) -> Result<(DownloadParams, TeraboxFileInfo), AppError> {
+        let params = |shareid, uk, sign: &str, timestamp| DownloadParams {
+            shareid,
+            uk,
+            sign: sign.to_string(),
+            timestamp,
+            fs_id: fs_id.to_string(),
+            mode: default_mode(),
+        };
+
+        let info = self.get_info(url).await?;
+        if let Some(file) = info.list.iter().find(|f| f.fs_id == fs_id) {
+            return Ok((params(info.shareid, info.uk, &info.sign, info.timestamp), file.clone()));
+        }
+        if !info.list.iter().any(|f| f.is_dir) {
+            return Err(AppError::not_found(format!("Share has no file with id {}", fs_id)));
+        }
+
+        let listing = self.list_folder(url, |_| {}).await?;
+        let entry = listing
+            .entries
+            .into_iter()
+            .find(|entry| entry.file.fs_id == fs_id)
+            .ok_or_else(|| AppError::not_found(format!("Share has no file with id {}", fs_id)))?;
+        Ok((
+            params(listing.shareid, listing.uk, &listing.sign, listing.timestamp),
+            entry.file,
+        ))
+    }
+
+    /// Stops a running `list_folder` for this share; false if none is running
+    pub fn cancel_folder_scan(&self, url: &str) -> bool {
+        let Some(shorturl) = Self::extract_shorturl(url) else {
+            return false;
+        };
+        match self.folder_scans.lock().unwrap().get(&shorturl) {
+            Some(cancel) => {
+                cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn scan_folder(
+        &self,
+        shorturl: &str,
+        cancel: &AtomicBool,
+        on_progress: &mut impl FnMut(&FolderScanProgress),
+    ) -> Result<FolderListing, AppError> {
+        let mut listing = FolderListing {
+            shorturl: shorturl.to_string(),
+            shareid: 0,
+            uk: 0,
+            sign: String::new(),
+            timestamp: 0,
+            entries: Vec::new(),
+            complete: false,
+        };
+
+        // Breadth-first from the share root, which is the empty path
+        let mut pending = VecDeque::from([String::new()]);
+        let mut pages = 0;
+        let mut bytes = 0u64;
+
+        while let Some(dir) = pending.pop_front() {
+            for page in 1.. {
+                if cancel.load(Ordering::Relaxed) {
+                    return Ok(listing);
+                }
+                let Some(data) = self.fetch_folder_page(shorturl, &dir, page, cancel).await? else {
+                    return Ok(listing);
+                };
+
+                if pages == 0 {
+                    listing.shareid = data.shareid.unwrap_or(0);
+                    listing.uk = data.uk.unwrap_or(0);
+                    listing.sign = data.sign.unwrap_or_default();
+                    listing.timestamp = data.timestamp.unwrap_or(0);
+                }
+                pages += 1;
+
+                let items = data.list.unwrap_or_default();
+                let count = items.len();
+                for item in items {
+                    let file = Self::convert_file_item(item);
+                    let path = if dir.is_empty() {
+                        file.name.clone()
+                    } else {
+                        format!("{}/{}", dir, file.name)
+                    };
+                    if file.is_dir {
+                        pending.push_back(path.clone());
+                    } else {
+                        bytes += file.size.and_then(|size| u64::try_from(size).ok()).unwrap_or(0);
+                    }
+                    listing.entries.push(FolderEntry { path, file });
+                }
+
+                on_progress(&FolderScanProgress {
+                    shorturl: shorturl.to_string(),
+                    dir: dir.clone(),
+                    pages,
+                    entries: listing.entries.len(),
+                    bytes,
+                });
+
+                if count < FOLDER_PAGE_SIZE {
+                    break;
+                }
+            }
+        }
+
+        listing.complete = true;
+        Ok(listing)
+    }
+
+    /// One page of `dir` (relative to the share root). `None` if the scan was cancelled
+    /// while backing off from a rate limit.
+    async fn fetch_folder_page(
+        &self,
+        shorturl: &str,
+        dir: &str,
+        page: u32,
+        cancel: &AtomicBool,
+    ) -> Result<Option<WorkerInfoResponse>, AppError> {
+        let request_url = format!("{}/api/get-info-new", self.base_url);
+        let page = page.to_string();
+        let num = FOLDER_PAGE_SIZE.to_string();
+
+        for attempt in 0.. {
+            // Per page, so a long scan shares the slots with link requests
+            let permit = Self::acquire(self.request_limit()).await;
+            let mut request = self.client.get(&request_url).query(&[
+                ("shorturl", shorturl),
+                ("pwd", ""),
+                ("page", &page),
+                ("num", &num),
+            ]);
+            if !dir.is_empty() {
+                request = request.query(&[("dir", format!("/{}", dir))]);
+            }
+            for (key, value) in Self::get_headers(self.session_cookie().as_deref()) {
+                request = request.header(key, value);
+            }
+
+            let response = request
+                .send()
+                .await
+                .map_err(|e| AppError::network(format!("Request failed: {}", e)))?;
+            let status = response.status();
+
+            let rate_limited = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::SERVICE_UNAVAILABLE;
+            if rate_limited && attempt < MAX_RATE_LIMIT_RETRIES {
+                let retry_after = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or_default();
+                let delay = Self::rate_limit_delay(attempt).max(retry_after.min(RATE_LIMIT_MAX_DELAY));
+                // Jitter keeps parallel scans from retrying in lockstep
+                let jitter = rand::thread_rng().gen_range(0..=delay.as_millis() as u64 / 2);
+                log::warn!(
+                    "Rate limited listing {} (page {}), retrying in {:?}",
+                    shorturl,
+                    page,
+                    delay
+                );
+
+                // Backing off shouldn't hold a slot others could use
+                drop(permit);
+                if Self::sleep_unless_cancelled(delay + Duration::from_millis(jitter), cancel).await {
+                    return Ok(None);
                 }
+                continue;
+            }
+            if !status.is_success() {
+                return Err(Self::status_error(status));
+            }
+
+            let data: WorkerInfoResponse = response
+                .json()
+                .await
+                .map_err(|e| AppError::invalid_response(format!("Failed to parse JSON: {}", e)))?;
+            if !data.ok {
+                return Err(data.message.unwrap_or("API returned ok=false".to_string()).into());
+            }
+            return Ok(Some(data));
+        }
+
+        unreachable!("the retry loop only exits by returning")
+    }
+
+    /// Exponential backoff before retry `attempt`, capped at `RATE_LIMIT_MAX_DELAY`
+    fn rate_limit_delay(attempt: u32) -> Duration {
+        RATE_LIMIT_BASE_DELAY
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(RATE_LIMIT_MAX_DELAY)
+    }
+
+    /// Sleeps in short steps so a cancel doesn't wait out a long backoff. True if cancelled.
+    async fn sleep_unless_cancelled(delay: Duration, cancel: &AtomicBool) -> bool {
+        let step = Duration::from_millis(100);
+        let deadline = Instant::now() + delay;
+        while Instant::now() < deadline {
+            if cancel.load(Ordering::Relaxed) {
+                return true;
             }
+            tokio::time::sleep(step.min(deadline - Instant::now())).await;
+        }
+        cancel.load(Ordering::Relaxed)
+    }
+
+    pub async fn get_download_link(&self, params: DownloadParams) -> Result<DownloadLink, AppError> {
+        let key = (params.shareid, params.fs_id.clone());
+        let ttl = *self.link_cache_ttl.lock().unwrap();
+
+        if !ttl.is_zero() {
+            let mut cache = self.link_cache.lock().unwrap();
+            cache.retain(|_, (_, resolved_at)| resolved_at.elapsed() < ttl);
+            if let Some((link, _)) = cache.get(&key) {
+                return Ok(link.clone());
+            }
+        }
+
+        let link = self
+            .resolve_download_link(params)
+            .await
+            .map_err(|e| self.session_error(e))?;
+
+        if !ttl.is_zero() {
+            self.link_cache
+                .lock()
+                .unwrap()
+                .insert(key, (link.clone(), Instant::now()));
         }
 
-        Err(format!("Failed to get info: {}", last_error))
+        Ok(link)
     }
 
-    pub async fn get_download_link(&self, params: DownloadParams) -> Result<DownloadLink, String> {
+    /// Resolves the direct link for use outside the app. The link only works with the
+    /// session cookies and, for some CDNs, the same user agent aria2 would send.
+    pub async fn resolve_direct_url(
+        &self,
+        params: DownloadParams,
+        user_agent: &str,
+    ) -> Result<DirectUrl, AppError> {
+        let link = self.get_download_link(params).await?;
+        let url = link.download_link.ok_or_else(|| {
+            AppError::not_found(link.error_message.unwrap_or("No download link returned".to_string()))
+        })?;
+
+        let mut headers = BTreeMap::new();
+        headers.insert("User-Agent".to_string(), user_agent.to_string());
+        if let Some(cookies) = link.cookies.filter(|c| !c.is_empty()) {
+            headers.insert("Cookie".to_string(), cookies);
+        }
+
+        let curl = Self::curl_command(&url, &headers);
+        Ok(DirectUrl { url, headers, curl })
+    }
+
+    /// POSIX-shell quoted; `-OJ` keeps the server's file name, `-C -` resumes
+    fn curl_command(url: &str, headers: &BTreeMap<String, String>) -> String {
+        let quote = |value: &str| format!("'{}'", value.replace('\'', r"'\''"));
+        let mut command = String::from("curl -L -C - -OJ");
+        for (name, value) in headers {
+            command.push_str(" -H ");
+            command.push_str(&quote(&format!("{}: {}", name, value)));
+        }
+        command.push(' ');
+        command.push_str(&quote(url));
+        command
+    }
+
+    #[tracing::instrument(skip(self, params), fields(shareid = params.shareid, fs_id = %params.fs_id))]
+    async fn resolve_download_link(&self, params: DownloadParams) -> Result<DownloadLink, AppError> {
+        let _permit = Self::acquire(self.request_limit()).await;
         let request_body = serde_json::json!({
             "shareid": params.shareid,
             "uk": params.uk,
@@ -172,7 +679,7 @@ impl TeraboxApi {
             "fs_id": params.fs_id,
         });
 
-        let headers = Self::get_headers();
+        let headers = Self::get_headers(self.session_cookie().as_deref());
         
         // Determine primary and fallback endpoints based on selected mode
         let (primary, fallback) = if params.mode == 1 {
@@ -182,10 +689,10 @@ impl TeraboxApi {
         };
 
         let endpoints = [primary, fallback];
-        let mut last_error = String::from("Unknown error");
+        let mut last_error = AppError::from("Unknown error");
 
         for endpoint in endpoints {
-            let request_url = format!("{}{}", BASE_URL, endpoint);
+            let request_url = format!("{}{}", self.base_url, endpoint);
 
             let mut request = self
                 .client
@@ -197,52 +704,320 @@ impl TeraboxApi {
                 request = request.header(*key, value);
             }
 
-            println!("Trying Download Endpoint: {}", endpoint);
+            tracing::debug!("Trying Download Endpoint: {}", endpoint);
 
             match request.send().await {
                 Ok(response) => {
+                    let set_cookies: Vec<String> = response
+                        .headers()
+                        .get_all(reqwest::header::SET_COOKIE)
+                        .iter()
+                        .filter_map(|v| v.to_str().ok())
+                        .filter_map(|v| v.split(';').next())
+                        .map(|v| v.trim().to_string())
+                        .collect();
+
                     let text = match response.text().await {
                         Ok(t) => t,
                         Err(e) => {
-                            last_error = format!("Failed to read response from {}: {}", endpoint, e);
+                            last_error = AppError::network(format!(
+                                "Failed to read response from {}: {}",
+                                endpoint, e
+                            ));
                             log::warn!("{}", last_error);
                             continue;
                         }
                     };
                     
-                    println!("Download API Response ({}): {}", endpoint, text);
+                    tracing::debug!("Download API Response ({}): {}", endpoint, text);
 
                     match serde_json::from_str::<WorkerDownloadResponse>(&text) {
                         Ok(data) => {
                             if data.ok && data.download_link.is_some() {
+                                let cookies = data
+                                    .cookie
+                                    .into_iter()
+                                    .chain(set_cookies)
+                                    .filter(|c| !c.is_empty())
+                                    .collect::<Vec<_>>()
+                                    .join("; ");
+
+                                let mut urls: Vec<String> = data.download_link.iter().cloned().collect();
+                                for url in data.download_links {
+                                    if !url.is_empty() && !urls.contains(&url) {
+                                        urls.push(url);
+                                    }
+                                }
+                                // Each CDN host throttles per connection, so spread aria2 across them
+                                for url in urls.clone() {
+                                    for variant in Self::host_variants(&url) {
+                                        if !urls.contains(&variant) {
+                                            urls.push(variant);
+                                        }
+                                    }
+                                }
+
                                 return Ok(DownloadLink {
                                     ok: true,
                                     download_link: data.download_link,
+                                    urls,
                                     error_message: None,
+                                    cookies: (!cookies.is_empty()).then_some(cookies),
                                 });
                             }
                             
+                            // The other server won't let the file through either
+                            if let Some(too_large) = Self::file_too_large(&data) {
+                                return Err(too_large);
+                            }
+
                             // If API returns specific message, update error but try next server
                             if let Some(msg) = data.message {
-                                last_error = format!("Server {} error: {}", endpoint, msg);
+                                last_error = format!("Server {} error: {}", endpoint, msg).into();
                             } else {
-                                last_error = format!("Server {} returned ok=false", endpoint);
+                                last_error = format!("Server {} returned ok=false", endpoint).into();
                             }
                         }
                         Err(e) => {
-                            last_error = format!("Failed to parse JSON from {}: {}", endpoint, e);
+                            last_error = AppError::invalid_response(format!(
+                                "Failed to parse JSON from {}: {}",
+                                endpoint, e
+                            ));
                         }
                     }
                 }
                 Err(e) => {
-                    last_error = format!("Request failed for {}: {}", request_url, e);
+                    last_error = AppError::network(format!("Request failed for {}: {}", request_url, e));
                 }
             }
             
-            println!("Retry: Switching to fallback server due to: {}", last_error);
+            tracing::warn!("Retry: Switching to fallback server due to: {}", last_error);
+        }
+
+        Err(last_error.context("All download servers failed. Last error"))
+    }
+
+    /// `FileTooLarge` if Terabox refused the link for the free account's size limit,
+    /// going by its errno or, from servers that drop it, the message
+    fn file_too_large(data: &WorkerDownloadResponse) -> Option<AppError> {
+        let by_message = data.message.as_deref().is_some_and(|message| {
+            let message = message.to_lowercase();
+            (message.contains("too large") || message.contains("exceeds"))
+                && (message.contains("free") || message.contains("premium") || message.contains("vip"))
+        });
+        if data.errno != Some(FILE_TOO_LARGE_ERRNO) && !by_message {
+            return None;
+        }
+        Some(AppError::FileTooLarge {
+            size: data.size,
+            limit: data.limit.unwrap_or(FREE_ACCOUNT_SIZE_LIMIT),
+        })
+    }
+
+    /// The same direct link on the other known CDN hosts, or nothing if `url` isn't on one
+    fn host_variants(url: &str) -> Vec<String> {
+        let parsed = match reqwest::Url::parse(url) {
+            Ok(parsed) => parsed,
+            Err(_) => return Vec::new(),
+        };
+        let host = parsed.host_str().unwrap_or_default().to_lowercase();
+        if !DIRECT_LINK_HOSTS.contains(&host.as_str()) {
+            return Vec::new();
+        }
+
+        DIRECT_LINK_HOSTS
+            .iter()
+            .filter(|variant| **variant != host)
+            .filter_map(|variant| {
+                let mut mirror = parsed.clone();
+                mirror.set_host(Some(variant)).ok()?;
+                Some(mirror.to_string())
+            })
+            .collect()
+    }
+
+    /// Fetches the share's thumbnail as base64, or `None` if the share has none
+    pub async fn get_thumbnail(&self, url: &str) -> Result<Option<String>, AppError> {
+        let info = self.get_info(url).await?;
+        let thumbnail_url = match info.thumbnail_url {
+            Some(thumbnail_url) => thumbnail_url,
+            None => return Ok(None),
+        };
+
+        let response = self
+            .client
+            .get(&thumbnail_url)
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("Thumbnail request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(Self::status_error(response.status()));
+        }
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| AppError::network(format!("Failed to read thumbnail: {}", e)))?;
+
+        Ok(Some(BASE64_STANDARD.encode(bytes)))
+    }
+
+    pub async fn check_link_health(&self, url: &str) -> Result<LinkHealth, AppError> {
+        let direct_link = match self.resolve_first_file(url).await {
+            Ok((direct_link, _)) => direct_link,
+            Err(e) => return Ok(LinkHealth::dead(e.to_string())),
+        };
+
+        let response = match self.client.head(&direct_link).send().await {
+            Ok(response) => response,
+            Err(e) => return Ok(LinkHealth::dead(format!("HEAD request failed: {}", e))),
+        };
+
+        let status = response.status();
+        let size: Option<u64> = response
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok());
+
+        // Direct links usually carry their lifetime as an `expires` query parameter
+        let expires_hint = reqwest::Url::parse(&direct_link)
+            .ok()
+            .and_then(|u| {
+                u.query_pairs()
+                    .find(|(k, _)| k == "expires")
+                    .map(|(_, v)| v.to_string())
+            })
+            .or_else(|| {
+                response
+                    .headers()
+                    .get(reqwest::header::EXPIRES)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_string())
+            });
+
+        if status != reqwest::StatusCode::OK || size.is_none() {
+            return Ok(LinkHealth {
+                alive: false,
+                size,
+                expires_hint,
+                error_message: Some(format!("Direct link returned status {}", status)),
+            });
+        }
+
+        Ok(LinkHealth {
+            alive: true,
+            size,
+            expires_hint,
+            error_message: None,
+        })
+    }
+
+    /// Direct link (and its cookies) of the first file in the share
+    async fn resolve_first_file(&self, url: &str) -> Result<(String, Option<String>), AppError> {
+        let info = self.get_info(url).await?;
+        let file = info
+            .list
+            .iter()
+            .find(|f| !f.is_dir)
+            .ok_or_else(|| AppError::not_found("Share has no downloadable files"))?;
+
+        let params = DownloadParams {
+            shareid: info.shareid,
+            uk: info.uk,
+            sign: info.sign.clone(),
+            timestamp: info.timestamp,
+            fs_id: file.fs_id.clone(),
+            mode: default_mode(),
+        };
+
+        match self.get_download_link(params).await? {
+            DownloadLink { download_link: Some(link), cookies, .. } => Ok((link, cookies)),
+            link => Err(AppError::not_found(
+                link.error_message.unwrap_or("No download link returned".to_string()),
+            )),
+        }
+    }
+
+    /// Downloads from the share's first file for `duration` and throws the bytes away,
+    /// measuring what Terabox delivers on a single connection without involving aria2
+    pub async fn benchmark_link(&self, url: &str, duration: Duration) -> Result<LinkBenchmark, AppError> {
+        let (direct_link, cookies) = self.resolve_first_file(url).await?;
+
+        let mut request = self
+            .client
+            .get(&direct_link)
+            // The client's 30s timeout covers the whole body, so leave room for the run
+            .timeout(duration + Duration::from_secs(30));
+        if let Some(cookies) = cookies.filter(|c| !c.is_empty()) {
+            request = request.header(reqwest::header::COOKIE, cookies);
+        }
+
+        let started = Instant::now();
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("Request failed: {}", e)))?;
+        let latency = started.elapsed();
+        if !response.status().is_success() {
+            return Err(Self::status_error(response.status()));
+        }
+
+        let body_started = Instant::now();
+        let mut bytes = 0u64;
+        while body_started.elapsed() < duration {
+            match response.chunk().await {
+                Ok(Some(chunk)) => bytes += chunk.len() as u64,
+                Ok(None) => break,
+                Err(e) => return Err(AppError::network(format!("Download failed: {}", e))),
+            }
         }
+        let elapsed = body_started.elapsed();
+
+        Ok(LinkBenchmark {
+            bytes_downloaded: bytes,
+            duration_ms: elapsed.as_millis() as u64,
+            average_mb_per_sec: bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64().max(0.001),
+            latency_ms: latency.as_millis() as u64,
+        })
+    }
 
-        Err(format!("All download servers failed. Last error: {}", last_error))
+    fn status_error(status: reqwest::StatusCode) -> AppError {
+        let message = format!("Server returned error status: {}", status);
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                AppError::TeraboxAuth { message }
+            }
+            reqwest::StatusCode::NOT_FOUND => AppError::NotFound { message },
+            _ => AppError::Network { message },
+        }
+    }
+
+    /// Every top-level file and folder of the share, in the order Terabox lists them
+    fn info_from_response(data: WorkerInfoResponse) -> Result<TeraboxInfo, AppError> {
+        if !data.ok {
+            return Err(data.message.unwrap_or("API returned ok=false".to_string()).into());
+        }
+
+        let list: Vec<TeraboxFileInfo> = data
+            .list
+            .unwrap_or_default()
+            .into_iter()
+            .map(Self::convert_file_item)
+            .collect();
+        let thumbnail_url = list.iter().find_map(|f| f.thumbnail_url.clone());
+
+        Ok(TeraboxInfo {
+            ok: true,
+            shareid: data.shareid.unwrap_or(0),
+            uk: data.uk.unwrap_or(0),
+            sign: data.sign.unwrap_or_default(),
+            timestamp: data.timestamp.unwrap_or(0),
+            list,
+            thumbnail_url,
+            error_message: None,
+        })
     }
 
     fn convert_file_item(item: WorkerFileItem) -> TeraboxFileInfo {
@@ -260,15 +1035,23 @@ impl TeraboxApi {
         } else {
             Self::check_file_type(&item.filename)
         };
+        let file_category = if is_dir {
+            FileCategory::Folder
+        } else {
+            FileCategory::detect(&item.filename, item.mime_type.as_deref(), item.category.as_deref())
+        };
 
         TeraboxFileInfo {
             is_dir,
             fs_id: item.fs_id,
             name: item.filename,
             file_type,
+            file_category,
             size,
             category: item.category,
+            mime_type: item.mime_type,
             create_time,
+            thumbnail_url: item.thumbs.and_then(WorkerThumbs::largest),
         }
     }
 
@@ -296,6 +1079,219 @@ impl TeraboxApi {
 mod tests {
     use super::*;
 
+    /// Serves worker requests over HTTP, answering each with the JSON body `handler`'s
+    /// future resolves to. Returns the base url.
+    async fn mock_worker<F, Fut>(handler: F) -> String
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = String> + Send + 'static,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let handler = Arc::new(handler);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let mut request = [0u8; 4096];
+                    let n = socket.read(&mut request).await.unwrap_or(0);
+                    let body = handler(String::from_utf8_lossy(&request[..n]).into_owned()).await;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        base_url
+    }
+
+    #[test]
+    fn test_file_too_large_response() {
+        let parse = |json: &str| serde_json::from_str::<WorkerDownloadResponse>(json).unwrap();
+
+        let data = parse(
+            r#"{"ok":false,"errno":9019,"message":"file size exceeds limit","size":6442450944,"limit":4294967296}"#,
+        );
+        assert!(matches!(
+            TeraboxApi::file_too_large(&data),
+            Some(AppError::FileTooLarge { size: Some(6442450944), limit: 4294967296 })
+        ));
+
+        // Without the errno or the numbers, the message still gives it away
+        let data = parse(r#"{"ok":false,"message":"File too large for free users, upgrade to Premium"}"#);
+        assert!(matches!(
+            TeraboxApi::file_too_large(&data),
+            Some(AppError::FileTooLarge { size: None, limit: FREE_ACCOUNT_SIZE_LIMIT })
+        ));
+
+        // Other failures, and the fields missing from older servers, are left alone
+        let data = parse(r#"{"ok":false,"errno":-9,"message":"file does not exist"}"#);
+        assert!(TeraboxApi::file_too_large(&data).is_none());
+        let data = parse(r#"{"ok":false,"retry":true}"#);
+        assert!(TeraboxApi::file_too_large(&data).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_info_shares_concurrent_requests() {
+        use std::sync::atomic::AtomicUsize;
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let (release, released) = tokio::sync::watch::channel(false);
+        let server_hits = hits.clone();
+        let base_url = mock_worker(move |_| {
+            server_hits.fetch_add(1, Ordering::SeqCst);
+            let mut released = released.clone();
+            async move {
+                // Held until every caller has joined the request in flight
+                let _ = released.wait_for(|released| *released).await;
+                r#"{"ok":true,"shareid":1,"uk":2,"sign":"s","timestamp":3,"list":[]}"#.to_string()
+            }
+        })
+        .await;
+
+        let api = TeraboxApi::with_base_url(&base_url);
+        let calls = futures_util::future::join_all(
+            (0..10).map(|_| api.get_info("https://terabox.com/s/1abcdefghij")),
+        );
+        tokio::pin!(calls);
+        // The first poll starts every caller, before any answer can arrive
+        assert!(futures_util::poll!(&mut calls).is_pending());
+        release.send(true).unwrap();
+        for result in calls.await {
+            assert_eq!(result.unwrap().shareid, 1);
+        }
+
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(api.info_requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency_bounds_requests_in_flight() {
+        use std::sync::atomic::AtomicUsize;
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most_in_flight = Arc::new(AtomicUsize::new(0));
+        let (server_in_flight, server_most) = (in_flight.clone(), most_in_flight.clone());
+        let base_url = mock_worker(move |_| {
+            let (in_flight, most) = (server_in_flight.clone(), server_most.clone());
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                r#"{"ok":true,"shareid":1,"uk":2,"sign":"s","timestamp":3,"list":[]}"#.to_string()
+            }
+        })
+        .await;
+
+        let api = Arc::new(TeraboxApi::with_base_url(&base_url));
+        api.set_max_concurrency(2);
+        let mut calls = Vec::new();
+        for i in 0..6 {
+            let api = api.clone();
+            calls.push(tokio::spawn(async move {
+                let _ = api.get_info(&format!("https://terabox.com/s/1share{}", i)).await;
+            }));
+        }
+        for i in 0..4 {
+            let api = api.clone();
+            let params = DownloadParams {
+                shareid: 1,
+                uk: 2,
+                sign: "s".to_string(),
+                timestamp: 3,
+                fs_id: i.to_string(),
+                mode: default_mode(),
+            };
+            calls.push(tokio::spawn(async move {
+                let _ = api.get_download_link(params).await;
+            }));
+        }
+        for call in calls {
+            call.await.unwrap();
+        }
+
+        assert_eq!(most_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_find_file_reaches_nested_folders() {
+        let base_url = mock_worker(|request| async move {
+            let list = if request.contains("dir=%2Fextras%2Fmore") {
+                r#"[{"fs_id":"444","is_dir":"0","size":"2048","filename":"deep.mkv"}]"#
+            } else if request.contains("dir=%2Fextras") {
+                r#"[{"fs_id":"333","is_dir":"1","size":"0","filename":"more"}]"#
+            } else {
+                r#"[{"fs_id":"111","is_dir":"0","size":"10","filename":"top.txt"},{"fs_id":"222","is_dir":"1","size":"0","filename":"extras"}]"#
+            };
+            format!(
+                r#"{{"ok":true,"shareid":9,"uk":8,"sign":"s","timestamp":7,"list":{}}}"#,
+                list
+            )
+        })
+        .await;
+
+        let api = TeraboxApi::with_base_url(&base_url);
+        let url = "https://terabox.com/s/1abcdefghij";
+        let (params, file) = api.find_file(url, "111").await.unwrap();
+        assert_eq!((params.shareid, params.fs_id.as_str(), file.name.as_str()), (9, "111", "top.txt"));
+
+        let (params, file) = api.find_file(url, "444").await.unwrap();
+        assert_eq!((params.shareid, params.fs_id.as_str()), (9, "444"));
+        assert_eq!((file.name.as_str(), file.size), ("deep.mkv", Some(2048)));
+        assert!(matches!(api.find_file(url, "555").await, Err(AppError::NotFound { .. })));
+    }
+
+    #[test]
+    fn test_info_lists_every_file_of_a_multi_file_share() {
+        let data: WorkerInfoResponse = serde_json::from_value(serde_json::json!({
+            "ok": true,
+            "shareid": 4242,
+            "uk": 7,
+            "sign": "abc",
+            "timestamp": 1700000000,
+            "list": [
+                {"fs_id": 111, "is_dir": "0", "size": "1048576", "filename": "part1.mkv", "category": "1"},
+                {"fs_id": "222", "is_dir": 0, "size": 2048, "filename": "notes.pdf"},
+                {"fs_id": "333", "is_dir": "1", "size": "0", "filename": "extras"}
+            ]
+        }))
+        .unwrap();
+
+        let info = TeraboxApi::info_from_response(data).unwrap();
+        assert_eq!(info.shareid, 4242);
+        let files: Vec<_> = info
+            .list
+            .iter()
+            .map(|f| (f.fs_id.as_str(), f.name.as_str(), f.size, f.is_dir))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("111", "part1.mkv", Some(1048576), false),
+                ("222", "notes.pdf", Some(2048), false),
+                ("333", "extras", None, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_curl_command_quotes_headers() {
+        let mut headers = BTreeMap::new();
+        headers.insert("User-Agent".to_string(), "Mozilla/5.0".to_string());
+        headers.insert("Cookie".to_string(), "ndus=a'b".to_string());
+        assert_eq!(
+            TeraboxApi::curl_command("https://d.terabox.com/file?x=1&y=2", &headers),
+            r"curl -L -C - -OJ -H 'Cookie: ndus=a'\''b' -H 'User-Agent: Mozilla/5.0' 'https://d.terabox.com/file?x=1&y=2'"
+        );
+    }
+
     #[test]
     fn test_extract_shorturl() {
         assert_eq!(
@@ -303,4 +1299,52 @@ mod tests {
             Some("1DcGWQPuMVDgkXrFhP7AlcQ".to_string())
         );
     }
+
+    #[test]
+    fn test_rate_limit_delay_doubles_up_to_cap() {
+        assert_eq!(TeraboxApi::rate_limit_delay(0), Duration::from_secs(1));
+        assert_eq!(TeraboxApi::rate_limit_delay(3), Duration::from_secs(8));
+        assert_eq!(TeraboxApi::rate_limit_delay(10), RATE_LIMIT_MAX_DELAY);
+        assert_eq!(TeraboxApi::rate_limit_delay(u32::MAX), RATE_LIMIT_MAX_DELAY);
+    }
+
+    #[test]
+    fn test_host_variants() {
+        assert_eq!(
+            TeraboxApi::host_variants("https://d.terabox.com/file/abc?fid=1&sign=x"),
+            vec!["https://data.terabox.com/file/abc?fid=1&sign=x".to_string()]
+        );
+        assert!(TeraboxApi::host_variants("https://example.com/file/abc").is_empty());
+    }
+
+    #[test]
+    fn test_extract_shorturl_variants() {
+        let cases = [
+            ("https://www.terabox.com/s/1DcGWQPuMVDgkXrFhP7AlcQ", Some("1DcGWQPuMVDgkXrFhP7AlcQ")),
+            ("https://terabox.com/s/1DcGWQPuMVDgkXrFhP7AlcQ?pwd=abcd", Some("1DcGWQPuMVDgkXrFhP7AlcQ")),
+            ("https://www.terabox.com/wap/share/filelist?surl=DcGWQPuMVDgkXrFhP7AlcQ", Some("1DcGWQPuMVDgkXrFhP7AlcQ")),
+            ("https://www.terabox.com/sharing/link?surl=DcGWQPuMVDgkXrFhP7AlcQ", Some("1DcGWQPuMVDgkXrFhP7AlcQ")),
+            ("https://m.terabox.com/wap/share/filelist?surl=DcGWQPuMVDgkXrFhP7AlcQ&from=app", Some("1DcGWQPuMVDgkXrFhP7AlcQ")),
+            ("https://www.1024terabox.com/s/1AbCdEfGhIjKlMnOp", Some("1AbCdEfGhIjKlMnOp")),
+            ("https://1024tera.com/s/1AbCdEfGhIjKlMnOp", Some("1AbCdEfGhIjKlMnOp")),
+            ("https://terafileshare.com/s/1AbCdEfGhIjKlMnOp", Some("1AbCdEfGhIjKlMnOp")),
+            ("https://www.4funbox.com/s/1AbC_dEf-GhIjKl", Some("1AbC_dEf-GhIjKl")),
+            ("https://teraboxapp.com/s/1AbCdEfGhIjKlMnOp/", Some("1AbCdEfGhIjKlMnOp")),
+            ("www.terabox.app/s/1AbCdEfGhIjKlMnOp", Some("1AbCdEfGhIjKlMnOp")),
+            ("  https://mirrobox.com/s/1AbCdEfGhIjKlMnOp  ", Some("1AbCdEfGhIjKlMnOp")),
+            ("1AbCdEfGhIjKlMnOp", Some("1AbCdEfGhIjKlMnOp")),
+            ("https://example.com/s/1AbCdEfGhIjKlMnOp", None),
+            ("https://www.terabox.com/main?category=all", None),
+            ("not a url", None),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(
+                TeraboxApi::extract_shorturl(url),
+                expected.map(str::to_string),
+                "{}",
+                url
+            );
+        }
+    }
 }
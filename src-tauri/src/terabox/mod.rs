@@ -1,5 +1,7 @@
 mod api;
+mod session;
 mod types;
 
 pub use api::*;
+pub use session::*;
 pub use types::*;
@@ -0,0 +1,85 @@
+use crate::error::AppError;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+fn session_path() -> PathBuf {
+    crate::settings::api::get_config_dir().join("terabox_session")
+}
+
+/// Accepts what users tend to paste: the bare `ndus` token, `ndus=<token>`, or a whole
+/// `Cookie:` header. Returns it as `ndus=<token>`.
+pub fn normalize_session_cookie(input: &str) -> Result<String, AppError> {
+    let input = input.trim();
+    let input = input
+        .strip_prefix("Cookie:")
+        .or_else(|| input.strip_prefix("cookie:"))
+        .unwrap_or(input);
+    let token = if input.contains('=') {
+        input
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| name.trim() == "ndus")
+            .map(|(_, value)| value.trim())
+            .ok_or_else(|| AppError::invalid_input("The cookie has no ndus value"))?
+    } else {
+        input.trim()
+    };
+
+    if token.is_empty() || token.contains(|c: char| c.is_whitespace() || c == ';' || c == ',') {
+        return Err(AppError::invalid_input("That doesn't look like a Terabox ndus token"));
+    }
+    Ok(format!("ndus={}", token))
+}
+
+pub fn load_session_cookie() -> Option<String> {
+    let cookie = fs::read_to_string(session_path()).ok()?;
+    Some(cookie.trim().to_string()).filter(|c| !c.is_empty())
+}
+
+/// Saves `cookie` where only the current user can read it
+pub fn save_session_cookie(cookie: &str) -> Result<(), AppError> {
+    let path = session_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&path)
+        .map_err(|e| AppError::io(format!("Failed to save Terabox session: {}", e)))?;
+    file.write_all(cookie.as_bytes())?;
+    Ok(())
+}
+
+pub fn clear_session_cookie() -> Result<(), AppError> {
+    match fs::remove_file(session_path()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(AppError::io(format!("Failed to remove Terabox session: {}", e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_session_cookie() {
+        assert_eq!(normalize_session_cookie(" Y2FmZQ-abc ").unwrap(), "ndus=Y2FmZQ-abc");
+        assert_eq!(normalize_session_cookie("ndus=Y2FmZQ-abc").unwrap(), "ndus=Y2FmZQ-abc");
+        assert_eq!(
+            normalize_session_cookie("Cookie: lang=en; ndus=Y2FmZQ-abc; csrfToken=x").unwrap(),
+            "ndus=Y2FmZQ-abc"
+        );
+        assert!(normalize_session_cookie("lang=en").is_err());
+        assert!(normalize_session_cookie("two words").is_err());
+        assert!(normalize_session_cookie("").is_err());
+    }
+}
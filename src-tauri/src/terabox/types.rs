@@ -1,4 +1,85 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Coarse file kind used for icons and category routing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum FileCategory {
+    Folder,
+    Video,
+    Audio,
+    Image,
+    Archive,
+    Document,
+    Other,
+}
+
+impl FileCategory {
+    pub fn from_filename(name: &str) -> Self {
+        let ext = Path::new(name)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match ext.as_str() {
+            "mp4" | "mov" | "m4v" | "mkv" | "asf" | "avi" | "wmv" | "m2ts" | "3g2" | "3gp"
+            | "webm" | "flv" | "ts" | "mpg" | "mpeg" => FileCategory::Video,
+            "mp3" | "flac" | "wav" | "aac" | "m4a" | "ogg" | "opus" | "wma" => FileCategory::Audio,
+            "jpg" | "jpeg" | "png" | "gif" | "webp" | "svg" | "bmp" | "heic" | "tif" | "tiff" => {
+                FileCategory::Image
+            }
+            "zip" | "rar" | "7z" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "iso" => FileCategory::Archive,
+            "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "txt" | "csv" | "rtf"
+            | "odt" | "epub" | "md" => FileCategory::Document,
+            _ => FileCategory::Other,
+        }
+    }
+
+    /// `video/mp4` -> Video. None for types that don't say what the file is, like
+    /// `application/octet-stream`.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.split(';').next().unwrap_or("").trim().to_lowercase();
+        let (kind, subtype) = mime.split_once('/')?;
+        match (kind, subtype) {
+            ("video", _) => Some(FileCategory::Video),
+            ("audio", _) => Some(FileCategory::Audio),
+            ("image", _) => Some(FileCategory::Image),
+            ("text", _) => Some(FileCategory::Document),
+            (
+                "application",
+                "zip" | "x-zip-compressed" | "vnd.rar" | "x-rar-compressed" | "x-7z-compressed"
+                | "x-tar" | "gzip" | "x-gzip" | "x-bzip2" | "x-xz" | "x-iso9660-image",
+            ) => Some(FileCategory::Archive),
+            ("application", "pdf" | "msword" | "rtf" | "epub+zip") => Some(FileCategory::Document),
+            ("application", subtype) if subtype.starts_with("vnd.openxmlformats-officedocument") => {
+                Some(FileCategory::Document)
+            }
+            _ => None,
+        }
+    }
+
+    /// Terabox's numeric `category`: 1 video, 2 audio, 3 image, 4 document. The rest
+    /// (apps, torrents, "other") don't narrow it down.
+    pub fn from_server_category(category: &str) -> Option<Self> {
+        match category.trim() {
+            "1" => Some(FileCategory::Video),
+            "2" => Some(FileCategory::Audio),
+            "3" => Some(FileCategory::Image),
+            "4" => Some(FileCategory::Document),
+            _ => None,
+        }
+    }
+
+    /// What the server says wins over the extension, which is often missing or wrong:
+    /// MIME type first, then Terabox's category, then the file name
+    pub fn detect(name: &str, mime_type: Option<&str>, server_category: Option<&str>) -> Self {
+        mime_type
+            .and_then(Self::from_mime)
+            .or_else(|| server_category.and_then(Self::from_server_category))
+            .unwrap_or_else(|| Self::from_filename(name))
+    }
+}
 
 /// File info returned to frontend (computed fields)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -7,9 +88,14 @@ pub struct TeraboxFileInfo {
     pub fs_id: String,
     pub name: String,
     pub file_type: String,
+    pub file_category: FileCategory,
     pub size: Option<i64>,
+    /// Terabox's numeric category
     pub category: Option<String>,
+    /// MIME type, when the server reports one
+    pub mime_type: Option<String>,
     pub create_time: Option<i64>,
+    pub thumbnail_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,12 +105,96 @@ pub struct TeraboxInfo {
     pub uk: i64,
     pub sign: String,
     pub timestamp: i64,
+    /// Every top-level file and folder; any file resolves with its `fs_id` in `DownloadParams`
     #[serde(default)]
     pub list: Vec<TeraboxFileInfo>,
+    /// Preview of the first media file in the share, if Terabox has one
+    #[serde(default)]
+    pub thumbnail_url: Option<String>,
     #[serde(default)]
     pub error_message: Option<String>,
 }
 
+/// A file or folder found while enumerating a share, with its path inside the share
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderEntry {
+    /// Slash-separated, relative to the folder that was listed
+    pub path: String,
+    #[serde(flatten)]
+    pub file: TeraboxFileInfo,
+}
+
+/// Every entry below a share's root. `complete` is false when the scan was cancelled
+/// and `entries` holds only what was found until then.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderListing {
+    pub shorturl: String,
+    pub shareid: i64,
+    pub uk: i64,
+    pub sign: String,
+    pub timestamp: i64,
+    pub entries: Vec<FolderEntry>,
+    pub complete: bool,
+}
+
+/// Totals of a `FolderListing`, for checking it fits before downloading it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderSize {
+    pub shorturl: String,
+    pub total_bytes: u64,
+    pub file_count: usize,
+    pub folder_count: usize,
+    /// False when the scan was cancelled and the totals cover only part of the share
+    pub complete: bool,
+    /// Space left in the download folder, when the platform can tell
+    pub free_bytes: Option<u64>,
+    /// Whether `total_bytes` fits in `free_bytes`; None when that's unknown
+    pub fits: Option<bool>,
+}
+
+impl FolderSize {
+    pub fn from_listing(listing: &FolderListing) -> Self {
+        let files = listing.entries.iter().filter(|entry| !entry.file.is_dir);
+        Self {
+            shorturl: listing.shorturl.clone(),
+            total_bytes: listing.total_bytes(),
+            file_count: files.count(),
+            folder_count: listing.entries.iter().filter(|entry| entry.file.is_dir).count(),
+            complete: listing.complete,
+            free_bytes: None,
+            fits: None,
+        }
+    }
+
+    pub fn with_free_space(mut self, free_bytes: Option<u64>) -> Self {
+        self.free_bytes = free_bytes;
+        self.fits = free_bytes.map(|free| self.total_bytes <= free);
+        self
+    }
+}
+
+impl FolderListing {
+    pub fn total_bytes(&self) -> u64 {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.file.is_dir)
+            .filter_map(|entry| entry.file.size)
+            .map(|size| u64::try_from(size).unwrap_or(0))
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderScanProgress {
+    pub shorturl: String,
+    /// Folder whose page was just fetched
+    pub dir: String,
+    pub pages: u32,
+    pub entries: usize,
+    /// Size of the files found so far
+    pub bytes: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadParams {
     pub shareid: i64,
@@ -36,7 +206,7 @@ pub struct DownloadParams {
     pub mode: i32, // 1 for Server 1, 2 for Server 2
 }
 
-fn default_mode() -> i32 {
+pub(crate) fn default_mode() -> i32 {
     2 // Default to Server 2 (get-downloadp) as it seems more stable/common
 }
 
@@ -44,9 +214,57 @@ fn default_mode() -> i32 {
 pub struct DownloadLink {
     pub ok: bool,
     pub download_link: Option<String>,
+    /// Every candidate direct link, `download_link` first; aria2 uses the rest as mirrors
+    #[serde(default)]
+    pub urls: Vec<String>,
+    pub error_message: Option<String>,
+    /// Session cookies the direct link must be fetched with, as a `Cookie:` header value
+    #[serde(default)]
+    pub cookies: Option<String>,
+}
+
+/// A resolved direct link with what another downloader needs to fetch it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectUrl {
+    pub url: String,
+    /// Headers to send along, by name
+    pub headers: BTreeMap<String, String>,
+    /// Ready-to-paste `curl` invocation for the same request
+    pub curl: String,
+}
+
+/// Result of probing a share's direct link without enqueuing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkHealth {
+    pub alive: bool,
+    pub size: Option<u64>,
+    pub expires_hint: Option<String>,
+    #[serde(default)]
     pub error_message: Option<String>,
 }
 
+impl LinkHealth {
+    pub fn dead(message: String) -> Self {
+        Self {
+            alive: false,
+            size: None,
+            expires_hint: None,
+            error_message: Some(message),
+        }
+    }
+}
+
+/// Result of `benchmark_link`: how fast Terabox served the file on one connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkBenchmark {
+    pub bytes_downloaded: u64,
+    pub duration_ms: u64,
+    /// MiB per second over the measured window
+    pub average_mb_per_sec: f64,
+    /// Time until the response headers arrived
+    pub latency_ms: u64,
+}
+
 // ============ API Response Types (raw from worker) ============
 
 /// Raw response from /api/get-info-new
@@ -65,6 +283,8 @@ pub struct WorkerInfoResponse {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkerFileItem {
     pub category: Option<String>,
+    #[serde(default, alias = "mimetype", alias = "mime")]
+    pub mime_type: Option<String>,
     
     #[serde(deserialize_with = "deserialize_string_or_number")]
     pub fs_id: String,
@@ -77,6 +297,25 @@ pub struct WorkerFileItem {
     
     pub filename: String,
     pub create_time: Option<String>,
+    #[serde(default)]
+    pub thumbs: Option<WorkerThumbs>,
+}
+
+/// Thumbnail URLs from smallest (`url1`) to largest (`url3`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerThumbs {
+    pub url1: Option<String>,
+    pub url2: Option<String>,
+    pub url3: Option<String>,
+}
+
+impl WorkerThumbs {
+    pub fn largest(self) -> Option<String> {
+        [self.url3, self.url2, self.url1]
+            .into_iter()
+            .flatten()
+            .find(|u| !u.is_empty())
+    }
 }
 
 /// Raw response from /api/get-download or /api/get-downloadp
@@ -86,7 +325,20 @@ pub struct WorkerDownloadResponse {
     pub retry: Option<bool>,
     #[serde(rename = "downloadLink")]
     pub download_link: Option<String>,
+    /// Alternate CDN nodes for the same file, when the worker returns them
+    #[serde(rename = "downloadLinks", default)]
+    pub download_links: Vec<String>,
     pub message: Option<String>,
+    #[serde(default)]
+    pub cookie: Option<String>,
+    /// Terabox's own error code, passed through on failures
+    #[serde(default)]
+    pub errno: Option<i64>,
+    /// The file's size and the account's download limit, on size-limit failures
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub limit: Option<u64>,
 }
 
 fn deserialize_string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -100,3 +352,77 @@ where
         _ => Err(serde::de::Error::custom("Expected string or number")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_category_from_filename() {
+        assert_eq!(FileCategory::from_filename("Movie.Part1.MKV"), FileCategory::Video);
+        assert_eq!(FileCategory::from_filename("song.flac"), FileCategory::Audio);
+        assert_eq!(FileCategory::from_filename("photo.jpeg"), FileCategory::Image);
+        assert_eq!(FileCategory::from_filename("backup.tar.gz"), FileCategory::Archive);
+        assert_eq!(FileCategory::from_filename("notes.pdf"), FileCategory::Document);
+        assert_eq!(FileCategory::from_filename("README"), FileCategory::Other);
+    }
+
+    #[test]
+    fn test_file_category_prefers_server_type() {
+        assert_eq!(FileCategory::detect("clip.bin", Some("video/mp4"), None), FileCategory::Video);
+        assert_eq!(FileCategory::detect("clip.bin", None, Some("1")), FileCategory::Video);
+        // MIME beats both the category and the extension when they disagree
+        assert_eq!(
+            FileCategory::detect("song.mp3", Some("application/zip"), Some("2")),
+            FileCategory::Archive
+        );
+        assert_eq!(
+            FileCategory::detect("VIDEO", Some("Video/MP4; codecs=avc1"), None),
+            FileCategory::Video
+        );
+        // Uninformative server types fall back to the extension
+        assert_eq!(
+            FileCategory::detect("notes.pdf", Some("application/octet-stream"), Some("6")),
+            FileCategory::Document
+        );
+    }
+
+    #[test]
+    fn test_folder_size_sums_files_at_every_depth() {
+        let entry = |path: &str, is_dir: bool, size: Option<i64>| FolderEntry {
+            path: path.to_string(),
+            file: TeraboxFileInfo {
+                is_dir,
+                fs_id: path.to_string(),
+                name: path.rsplit('/').next().unwrap().to_string(),
+                file_type: "other".to_string(),
+                file_category: FileCategory::Other,
+                size,
+                category: None,
+                mime_type: None,
+                create_time: None,
+                thumbnail_url: None,
+            },
+        };
+        let listing = FolderListing {
+            shorturl: "1abc".to_string(),
+            shareid: 1,
+            uk: 2,
+            sign: String::new(),
+            timestamp: 0,
+            entries: vec![
+                entry("a.mkv", false, Some(1000)),
+                entry("extras", true, Some(4096)),
+                entry("extras/b.mkv", false, Some(500)),
+                entry("extras/unknown.bin", false, None),
+            ],
+            complete: true,
+        };
+
+        let size = FolderSize::from_listing(&listing);
+        assert_eq!((size.total_bytes, size.file_count, size.folder_count), (1500, 3, 1));
+        assert_eq!(size.fits, None);
+        assert_eq!(size.clone().with_free_space(Some(1499)).fits, Some(false));
+        assert_eq!(size.with_free_space(Some(1500)).fits, Some(true));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::error::AppError;
+use serde::Serialize;
+use tauri::Emitter;
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+/// Event emitted with an `UpdateProgress` for every chunk of the update download
+pub const UPDATE_PROGRESS_EVENT: &str = "update-progress";
+
+/// Result of a successful check. `available` is false when the app is up to date;
+/// a check that couldn't reach the server is an error instead.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateCheck {
+    pub available: bool,
+    pub version: Option<String>,
+    /// Release notes from the update manifest
+    pub notes: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateProgress {
+    pub downloaded: u64,
+    /// Missing when the server doesn't send a content length
+    pub total: Option<u64>,
+}
+
+pub async fn check_for_update(handle: &tauri::AppHandle) -> Result<UpdateCheck, AppError> {
+    let check = match fetch_update(handle).await? {
+        Some(update) => UpdateCheck {
+            available: true,
+            version: Some(update.version.clone()),
+            notes: update.body.clone(),
+            date: update
+                .date
+                .and_then(|date| chrono::DateTime::from_timestamp(date.unix_timestamp(), 0))
+                .map(|date| date.to_rfc3339()),
+        },
+        None => UpdateCheck {
+            available: false,
+            version: None,
+            notes: None,
+            date: None,
+        },
+    };
+    Ok(check)
+}
+
+/// Downloads and applies the available update; the app has to restart to run it
+pub async fn install_update(handle: &tauri::AppHandle) -> Result<(), AppError> {
+    let update = fetch_update(handle)
+        .await?
+        .ok_or_else(|| AppError::not_found("No update available"))?;
+
+    let mut downloaded = 0u64;
+    update
+        .download_and_install(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = handle.emit(UPDATE_PROGRESS_EVENT, UpdateProgress { downloaded, total });
+            },
+            || log::info!("Downloaded update {}", update.version),
+        )
+        .await
+        .map_err(|e| updater_error(e, "Failed to install update"))
+}
+
+async fn fetch_update(handle: &tauri::AppHandle) -> Result<Option<Update>, AppError> {
+    let updater = handle
+        .updater()
+        .map_err(|e| updater_error(e, "Couldn't check for updates"))?;
+    updater
+        .check()
+        .await
+        .map_err(|e| updater_error(e, "Couldn't check for updates"))
+}
+
+/// Connection problems become `Network` so the UI can offer a retry
+fn updater_error(error: tauri_plugin_updater::Error, prefix: &str) -> AppError {
+    use tauri_plugin_updater::Error;
+
+    let message = format!("{}: {}", prefix, error);
+    match error {
+        Error::Reqwest(_) | Error::Network(_) | Error::ReleaseNotFound => AppError::network(message),
+        _ => AppError::from(message),
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, PhysicalPosition, PhysicalSize, Theme};
+
+/// Event emitted with a `ThemeChange` when the chosen or the OS theme changes
+pub const THEME_CHANGED_EVENT: &str = "theme-changed";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThemeChange {
+    /// The setting: `light`, `dark` or `system`
+    pub theme: String,
+    /// What the window actually shows, `light` or `dark`
+    pub resolved: String,
+}
+
+/// Main window geometry, saved on close and restored on launch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+}
+
+fn theme_name(theme: Theme) -> &'static str {
+    match theme {
+        Theme::Dark => "dark",
+        _ => "light",
+    }
+}
+
+/// Applies the `theme` setting to the window, leaving `system` to follow the OS
+pub fn apply_theme(window: &tauri::Window, theme: &str) {
+    let forced = match theme {
+        "light" => Some(Theme::Light),
+        "dark" => Some(Theme::Dark),
+        _ => None,
+    };
+    if let Err(e) = window.set_theme(forced) {
+        log::warn!("Failed to set window theme: {}", e);
+    }
+}
+
+pub fn emit_theme_changed(window: &tauri::Window, theme: &str) {
+    let resolved = window.theme().map(theme_name).unwrap_or("light");
+    let change = ThemeChange {
+        theme: theme.to_string(),
+        resolved: resolved.to_string(),
+    };
+    let _ = window.emit(THEME_CHANGED_EVENT, change);
+}
+
+pub fn save_window_state(window: &tauri::Window) {
+    let maximized = window.is_maximized().unwrap_or(false);
+    let (Ok(position), Ok(size)) = (window.outer_position(), window.inner_size()) else {
+        return;
+    };
+
+    let mut state = WindowState {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized,
+    };
+    if maximized {
+        // The maximized geometry says nothing about where to put the window once restored
+        if let Some(previous) = load_window_state(window.app_handle()) {
+            state = WindowState { maximized, ..previous };
+        }
+    }
+
+    let result = serde_json::to_value(&state)
+        .map_err(|e| e.to_string())
+        .and_then(|value| {
+            crate::settings_store(window.app_handle()).set("window_state", value);
+            crate::persist_settings_store(window.app_handle()).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to save window state: {}", e);
+    }
+}
+
+fn load_window_state(handle: &tauri::AppHandle) -> Option<WindowState> {
+    crate::settings_store(handle)
+        .get("window_state")
+        .and_then(|value| serde_json::from_value(value).ok())
+}
+
+pub fn restore_window_state(window: &tauri::Window) {
+    let Some(state) = load_window_state(window.app_handle()) else {
+        return;
+    };
+
+    let _ = window.set_size(PhysicalSize::new(state.width, state.height));
+    // Skip a position left on a monitor that's no longer connected
+    let on_screen = window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .any(|monitor| {
+            let origin = monitor.position();
+            let size = monitor.size();
+            (origin.x..origin.x + size.width as i32).contains(&state.x)
+                && (origin.y..origin.y + size.height as i32).contains(&state.y)
+        });
+    if on_screen {
+        let _ = window.set_position(PhysicalPosition::new(state.x, state.y));
+    }
+    if state.maximized {
+        let _ = window.maximize();
+    }
+}
@@ -94,7 +94,7 @@ export function useDownloadQueue() {
 
       updateItem(id, { status: "downloading" });
       await startAria2();
-      const gid = await addDownload(
+      const { gid } = await addDownload(
         linkResult.download_link,
         item.downloadDir,
         item.file.name
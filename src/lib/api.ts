@@ -1,16 +1,94 @@
 import { invoke } from "@tauri-apps/api/core";
 import type {
   TeraboxInfo,
+  FolderListing,
   DownloadParams,
   DownloadLink,
+  LinkHealth,
+  LinkBenchmark,
+  DirectUrl,
   DownloadInfo,
+  DownloadStatus,
+  AddDownloadOptions,
   AppSettings,
+  Aria2Version,
+  Aria2Ping,
+  HistoryStatsReport,
+  DownloadOrigin,
+  FileAllocation,
+  DirCheck,
+  ChecksumResult,
+  GlobalStats,
+  DownloadFile,
+  UpdateCheck,
+  Theme,
+  RetryReport,
+  DownloadPage,
+  BandwidthRule,
+  BatchDownload,
+  BatchReport,
+  GroupProgress,
+  ConfigRepair,
+  BatchEstimate,
+  QueuedItem,
+  BandwidthPreset,
+  BatchMode,
+  OverallProgress,
+  AddedDownload,
+  Priority,
+  DownloadServer,
+  Aria2ConfigCheck,
+  FolderSize,
+  QueueImportReport,
+  Bootstrap,
 } from "./types";
 
 export async function getTeraboxInfo(url: string): Promise<TeraboxInfo> {
   return invoke<TeraboxInfo>("get_terabox_info", { url });
 }
 
+/** `cookie`: the account's `ndus` token, `ndus=...`, or a whole Cookie header */
+export async function loginTerabox(cookie: string): Promise<void> {
+  return invoke("login_terabox", { cookie });
+}
+
+export async function logoutTerabox(): Promise<void> {
+  return invoke("logout_terabox");
+}
+
+export async function isTeraboxLoggedIn(): Promise<boolean> {
+  return invoke<boolean>("is_terabox_logged_in");
+}
+
+export async function getTeraboxMaxConcurrency(): Promise<number> {
+  return invoke<number>("get_terabox_max_concurrency");
+}
+
+export async function setTeraboxMaxConcurrency(limit: number): Promise<void> {
+  return invoke("set_terabox_max_concurrency", { limit });
+}
+
+export async function listFolder(url: string): Promise<FolderListing> {
+  return invoke<FolderListing>("list_folder", { url });
+}
+
+/** One file from a share, e.g. picked from a `listFolder` result; `fsId` may be nested */
+export async function addTeraboxFile(
+  url: string,
+  fsId: string,
+  dir?: string
+): Promise<AddedDownload> {
+  return invoke<AddedDownload>("add_terabox_file", { url, fsId, dir });
+}
+
+export async function getFolderSize(url: string, dir?: string): Promise<FolderSize> {
+  return invoke<FolderSize>("get_folder_size", { url, dir });
+}
+
+export async function cancelFolderScan(url: string): Promise<boolean> {
+  return invoke<boolean>("cancel_folder_scan", { url });
+}
+
 export async function getDownloadLink(
   params: DownloadParams
 ): Promise<DownloadLink> {
@@ -21,6 +99,33 @@ export async function extractShorturl(url: string): Promise<string | null> {
   return invoke<string | null>("extract_shorturl", { url });
 }
 
+export async function resolveDirectUrl(params: DownloadParams): Promise<DirectUrl> {
+  return invoke<DirectUrl>("resolve_direct_url", { params });
+}
+
+export async function getThumbnail(url: string): Promise<string | null> {
+  return invoke<string | null>("get_thumbnail", { url });
+}
+
+export async function checkLinkHealth(url: string): Promise<LinkHealth> {
+  return invoke<LinkHealth>("check_link_health", { url });
+}
+
+export async function benchmarkLink(url: string, durationSecs: number): Promise<LinkBenchmark> {
+  return invoke<LinkBenchmark>("benchmark_link", { url, durationSecs });
+}
+
+export async function estimateBatchTime(
+  totalBytes: number,
+  concurrency?: number
+): Promise<BatchEstimate> {
+  return invoke<BatchEstimate>("estimate_batch_time", { totalBytes, concurrency });
+}
+
+export async function clearLinkCache(): Promise<void> {
+  return invoke<void>("clear_link_cache");
+}
+
 export async function wrapDownloadUrl(url: string): Promise<string> {
   return invoke<string>("wrap_download_url", { url });
 }
@@ -29,20 +134,132 @@ export async function startAria2(): Promise<void> {
   return invoke<void>("start_aria2");
 }
 
+/** Call once at launch instead of `startAria2` followed by `getAllDownloads` */
+export async function bootstrap(): Promise<Bootstrap> {
+  return invoke<Bootstrap>("bootstrap");
+}
+
 export async function stopAria2(): Promise<void> {
   return invoke<void>("stop_aria2");
 }
 
+export async function downloadAria2Binary(): Promise<string> {
+  return invoke<string>("download_aria2_binary");
+}
+
 export async function isAria2Running(): Promise<boolean> {
   return invoke<boolean>("is_aria2_running");
 }
 
+export async function isAria2Remote(): Promise<boolean> {
+  return invoke<boolean>("is_aria2_remote");
+}
+
+export async function pingAria2(): Promise<Aria2Ping> {
+  return invoke<Aria2Ping>("ping_aria2");
+}
+
+export async function validateAria2Config(): Promise<Aria2ConfigCheck> {
+  return invoke<Aria2ConfigCheck>("validate_aria2_config");
+}
+
+export async function getAria2Logs(maxLines?: number): Promise<string[]> {
+  return invoke<string[]>("get_aria2_logs", { maxLines });
+}
+
+export async function getAria2Version(): Promise<Aria2Version> {
+  return invoke<Aria2Version>("get_aria2_version");
+}
+
+export async function setRpcEndpoint(
+  url: string,
+  secret?: string
+): Promise<void> {
+  return invoke<void>("set_rpc_endpoint", { url, secret });
+}
+
 export async function addDownload(
   url: string,
   dir?: string,
-  filename?: string
+  filename?: string,
+  options?: AddDownloadOptions
+): Promise<AddedDownload> {
+  return invoke<AddedDownload>("add_download", { url, dir, filename, options });
+}
+
+/** Returns a tracking id right away; listen for `download-added` / `download-add-failed` */
+export async function addDownloadAsync(
+  url: string,
+  dir?: string,
+  filename?: string,
+  options?: AddDownloadOptions
 ): Promise<string> {
-  return invoke<string>("add_download", { url, dir, filename });
+  return invoke<string>("add_download_async", { url, dir, filename, options });
+}
+
+export async function addDownloadsBatch(
+  downloads: BatchDownload[],
+  dir?: string,
+  mode?: BatchMode
+): Promise<BatchReport> {
+  return invoke<BatchReport>("add_downloads_batch", { downloads, dir, mode });
+}
+
+export async function getGroupProgress(groupId: string): Promise<GroupProgress> {
+  return invoke<GroupProgress>("get_group_progress", { groupId });
+}
+
+export async function pauseGroup(groupId: string): Promise<number> {
+  return invoke<number>("pause_group", { groupId });
+}
+
+export async function resumeGroup(groupId: string): Promise<number> {
+  return invoke<number>("resume_group", { groupId });
+}
+
+export async function cancelGroup(groupId: string): Promise<number> {
+  return invoke<number>("cancel_group", { groupId });
+}
+
+export async function redownload(historyId: string): Promise<string> {
+  return invoke<string>("redownload", { historyId });
+}
+
+export async function retryDownload(gid: string): Promise<string> {
+  return invoke<string>("retry_download", { gid });
+}
+
+export async function retryErrored(): Promise<RetryReport> {
+  return invoke<RetryReport>("retry_errored");
+}
+
+/** Call from the browser's online/offline events */
+export async function notifyNetworkChanged(online: boolean): Promise<RetryReport> {
+  return invoke<RetryReport>("notify_network_changed", { online });
+}
+
+/** Copies a download's details to the clipboard and returns the text */
+export async function copyDownloadInfo(gid: string): Promise<string> {
+  const text = await invoke<string>("copy_download_info", { gid });
+  await navigator.clipboard.writeText(text);
+  return text;
+}
+
+/** Copies every failed download's details to the clipboard and returns the text */
+export async function copyAllFailed(): Promise<string> {
+  const text = await invoke<string>("copy_all_failed");
+  await navigator.clipboard.writeText(text);
+  return text;
+}
+
+export async function getStalledDownloads(): Promise<string[]> {
+  return invoke<string[]>("get_stalled_downloads");
+}
+
+export async function getDownloadOrigin(
+  gid: string
+): Promise<DownloadOrigin | null> {
+  return invoke<DownloadOrigin | null>("get_download_origin", { gid });
 }
 
 export async function getDownloadStatus(gid: string): Promise<DownloadInfo> {
@@ -61,18 +278,127 @@ export async function cancelDownload(gid: string): Promise<string> {
   return invoke<string>("cancel_download", { gid });
 }
 
-export async function getAllDownloads(): Promise<DownloadInfo[]> {
-  return invoke<DownloadInfo[]>("get_all_downloads");
+/** Returns the paths that were deleted */
+export async function cancelAndDelete(gid: string): Promise<string[]> {
+  return invoke<string[]>("cancel_and_delete", { gid });
+}
+
+/** Only for downloads that haven't started; returns the name as sanitized for disk */
+export async function renameDownload(gid: string, newName: string): Promise<string> {
+  return invoke<string>("rename_download", { gid, newName });
+}
+
+export async function setPriority(gid: string, priority: Priority): Promise<void> {
+  return invoke("set_priority", { gid, priority });
+}
+
+export async function getQueue(): Promise<QueuedItem[]> {
+  return invoke<QueuedItem[]>("get_queue");
+}
+
+/** Resolves to how many downloads were written */
+export async function exportQueue(path: string): Promise<number> {
+  return invoke<number>("export_queue", { path });
+}
+
+export async function importQueue(path: string, dir?: string): Promise<QueueImportReport> {
+  return invoke<QueueImportReport>("import_queue", { path, dir });
+}
+
+export async function changeDownloadDir(gid: string, newDir: string): Promise<string> {
+  return invoke<string>("change_download_dir", { gid, newDir });
+}
+
+export async function extractDownload(gid: string, password?: string): Promise<string> {
+  return invoke<string>("extract_download", { gid, password });
+}
+
+export async function getDownloadFiles(gid: string): Promise<DownloadFile[]> {
+  return invoke<DownloadFile[]>("get_download_files", { gid });
+}
+
+export async function getDownloadServers(gid: string): Promise<DownloadServer[]> {
+  return invoke<DownloadServer[]>("get_download_servers", { gid });
+}
+
+export async function selectFiles(gid: string, indices: number[]): Promise<string> {
+  return invoke<string>("select_files", { gid, indices });
+}
+
+export async function getGlobalStats(): Promise<GlobalStats> {
+  return invoke<GlobalStats>("get_global_stats");
+}
+
+export async function getOverallProgress(): Promise<OverallProgress> {
+  return invoke<OverallProgress>("get_overall_progress");
+}
+
+export async function removeDownloadResult(gid: string): Promise<string> {
+  return invoke<string>("remove_download_result", { gid });
+}
+
+export async function clearCompleted(): Promise<number> {
+  return invoke<number>("clear_completed");
+}
+
+export async function cancelErrored(): Promise<number> {
+  return invoke<number>("cancel_errored");
+}
+
+export async function clearAllResults(): Promise<string> {
+  return invoke<string>("clear_all_results");
+}
+
+export async function getAllDownloads(limit?: number): Promise<DownloadInfo[]> {
+  return invoke<DownloadInfo[]>("get_all_downloads", { limit });
+}
+
+export async function getWaiting(offset: number, num: number): Promise<DownloadPage> {
+  return invoke<DownloadPage>("get_waiting", { offset, num });
+}
+
+export async function getStopped(offset: number, num: number): Promise<DownloadPage> {
+  return invoke<DownloadPage>("get_stopped", { offset, num });
+}
+
+export async function getDownloadsByStatus(
+  status: DownloadStatus
+): Promise<DownloadInfo[]> {
+  return invoke<DownloadInfo[]>("get_downloads_by_status", { status });
 }
 
 export async function pauseAllDownloads(): Promise<string> {
   return invoke<string>("pause_all_downloads");
 }
 
+export async function revealInFolder(gid: string): Promise<void> {
+  return invoke("reveal_in_folder", { gid });
+}
+
 export async function resumeAllDownloads(): Promise<string> {
   return invoke<string>("resume_all_downloads");
 }
 
+/** Call from a power-event listener before the OS suspends */
+export async function prepareForSleep(): Promise<void> {
+  return invoke("prepare_for_sleep");
+}
+
+export async function resumeAfterWake(): Promise<void> {
+  return invoke("resume_after_wake");
+}
+
+export async function setMetered(metered: boolean): Promise<void> {
+  return invoke("set_metered", { metered });
+}
+
+export async function setBatteryLevel(
+  percent: number,
+  charging: boolean
+): Promise<void> {
+  return invoke("set_battery_level", { percent, charging });
+}
+
 export function formatBytes(bytes: number): string {
   if (bytes === 0) return "0 B";
   const k = 1024;
@@ -105,10 +431,132 @@ export async function setBandwidthLimit(
   });
 }
 
+/** `percent` of the overall limit, or null to remove the share; resolves to the KB/s applied, 0 being unlimited */
+export async function setDownloadShare(gid: string, percent: number | null): Promise<number> {
+  return invoke<number>("set_download_share", { gid, percent });
+}
+
+export async function toggleBandwidthLimit(
+  enabled: boolean
+): Promise<[number, number]> {
+  return invoke<[number, number]>("toggle_bandwidth_limit", { enabled });
+}
+
 export async function getBandwidthLimit(): Promise<[number, number]> {
   return invoke<[number, number]>("get_bandwidth_limit");
 }
 
+export async function setUserAgent(userAgent: string): Promise<void> {
+  return invoke("set_user_agent", { userAgent });
+}
+
+export async function getBandwidthPresets(): Promise<BandwidthPreset[]> {
+  return invoke<BandwidthPreset[]>("get_bandwidth_presets");
+}
+
+export async function applyBandwidthPreset(name: string): Promise<BandwidthPreset> {
+  return invoke<BandwidthPreset>("apply_bandwidth_preset", { name });
+}
+
+export async function applyConnectionSettings(): Promise<void> {
+  return invoke<void>("apply_connection_settings");
+}
+
+export async function checkDownloadDir(path: string): Promise<DirCheck> {
+  return invoke<DirCheck>("check_download_dir", { path });
+}
+
+export async function verifyChecksum(path: string, expected: string): Promise<ChecksumResult> {
+  return invoke<ChecksumResult>("verify_checksum", { path, expected });
+}
+
+export async function cancelChecksum(path: string): Promise<boolean> {
+  return invoke<boolean>("cancel_checksum", { path });
+}
+
+export async function getFileAllocationOptions(
+  dir: string
+): Promise<FileAllocation[]> {
+  return invoke<FileAllocation[]>("get_file_allocation_options", { dir });
+}
+
+export async function setMaxConcurrent(n: number): Promise<void> {
+  return invoke("set_max_concurrent", { n });
+}
+
+export async function listBandwidthRules(): Promise<BandwidthRule[]> {
+  return invoke<BandwidthRule[]>("list_bandwidth_rules");
+}
+
+export async function addBandwidthRule(rule: Omit<BandwidthRule, "id">): Promise<string> {
+  return invoke<string>("add_bandwidth_rule", { rule });
+}
+
+export async function updateBandwidthRule(rule: BandwidthRule): Promise<void> {
+  return invoke("update_bandwidth_rule", { rule });
+}
+
+export async function deleteBandwidthRule(id: string): Promise<void> {
+  return invoke("delete_bandwidth_rule", { id });
+}
+
+export async function saveProfile(name: string): Promise<void> {
+  return invoke("save_profile", { name });
+}
+
+export async function loadProfile(name: string): Promise<AppSettings> {
+  return invoke<AppSettings>("load_profile", { name });
+}
+
+export async function listProfiles(): Promise<string[]> {
+  return invoke<string[]>("list_profiles");
+}
+
+export async function deleteProfile(name: string): Promise<void> {
+  return invoke("delete_profile", { name });
+}
+
+export async function getActiveProfile(): Promise<string | null> {
+  return invoke<string | null>("get_active_profile");
+}
+
+export async function applyOptionToDownload(
+  gid: string,
+  key: string,
+  value: string
+): Promise<string> {
+  return invoke<string>("apply_option_to_download", { gid, key, value });
+}
+
+export async function setSeedRatio(gid: string, ratio: number): Promise<string> {
+  return invoke<string>("set_seed_ratio", { gid, ratio });
+}
+
+export async function getHistoryStats(): Promise<HistoryStatsReport> {
+  return invoke<HistoryStatsReport>("get_history_stats");
+}
+
+/** Resolves to how many history items were dropped */
+export async function clearHistoryOlderThan(days: number): Promise<number> {
+  return invoke<number>("clear_history_older_than", { days });
+}
+
+export async function approvePostDownloadCommand(command: string): Promise<void> {
+  return invoke("approve_post_download_command", { command });
+}
+
+export async function setTheme(theme: Theme): Promise<void> {
+  return invoke("set_theme", { theme });
+}
+
+export async function checkForUpdate(): Promise<UpdateCheck> {
+  return invoke<UpdateCheck>("check_for_update");
+}
+
+export async function installUpdate(): Promise<void> {
+  return invoke("install_update");
+}
+
 export async function getAppSettings(): Promise<AppSettings> {
   return invoke<AppSettings>("get_app_settings");
 }
@@ -116,3 +564,11 @@ export async function getAppSettings(): Promise<AppSettings> {
 export async function saveAppSettings(settings: AppSettings): Promise<void> {
   return invoke<void>("save_app_settings", { settings });
 }
+
+export async function getLogPath(): Promise<string> {
+  return invoke<string>("get_log_path");
+}
+
+export async function repairConfig(): Promise<ConfigRepair[]> {
+  return invoke<ConfigRepair[]>("repair_config");
+}
@@ -1,11 +1,23 @@
+export type FileCategory =
+  | "folder"
+  | "video"
+  | "audio"
+  | "image"
+  | "archive"
+  | "document"
+  | "other";
+
 export interface TeraboxFileInfo {
   is_dir: boolean;
   fs_id: string;
   name: string;
   file_type: "video" | "image" | "file" | "folder" | "other";
+  file_category: FileCategory;
   size: number | null;
   category: string | null;
+  mime_type: string | null;
   create_time: number | null;
+  thumbnail_url: string | null;
 }
 
 export interface TeraboxInfo {
@@ -15,9 +27,43 @@ export interface TeraboxInfo {
   sign: string;
   timestamp: number;
   list: TeraboxFileInfo[];
+  thumbnail_url?: string | null;
   error_message?: string;
 }
 
+export interface FolderEntry extends TeraboxFileInfo {
+  path: string;
+}
+
+export interface FolderListing {
+  shorturl: string;
+  shareid: number;
+  uk: number;
+  sign: string;
+  timestamp: number;
+  entries: FolderEntry[];
+  complete: boolean;
+}
+
+export interface FolderScanProgress {
+  shorturl: string;
+  dir: string;
+  pages: number;
+  entries: number;
+  bytes: number;
+}
+
+/** `fits` is null when the free space in the download folder is unknown */
+export interface FolderSize {
+  shorturl: string;
+  total_bytes: number;
+  file_count: number;
+  folder_count: number;
+  complete: boolean;
+  free_bytes: number | null;
+  fits: boolean | null;
+}
+
 export interface DownloadParams {
   shareid: number;
   uk: number;
@@ -30,32 +76,412 @@ export interface DownloadParams {
 export interface DownloadLink {
   ok: boolean;
   download_link: string | null;
+  urls: string[];
+  error_message?: string;
+  cookies?: string | null;
+}
+
+export interface DirectUrl {
+  url: string;
+  headers: Record<string, string>;
+  curl: string;
+}
+
+export interface LinkBenchmark {
+  bytes_downloaded: number;
+  duration_ms: number;
+  average_mb_per_sec: number;
+  latency_ms: number;
+}
+
+export interface LinkHealth {
+  alive: boolean;
+  size: number | null;
+  expires_hint: string | null;
   error_message?: string;
 }
 
+export type AppError =
+  | { kind: "network"; message: string }
+  | { kind: "aria2_rpc"; code: number; message: string }
+  | { kind: "terabox_auth"; message: string }
+  | { kind: "session_expired"; message: string }
+  | { kind: "not_found"; message: string }
+  | { kind: "io"; message: string }
+  | { kind: "invalid_response"; message: string }
+  | { kind: "invalid_input"; message: string }
+  | { kind: "cancelled"; message: string }
+  | { kind: "password_required"; message: string }
+  | { kind: "file_too_large"; size: number | null; limit: number }
+  | { kind: "download_exists"; gid: string }
+  | { kind: "already_downloaded"; path: string }
+  | { kind: "binary_not_found"; searched_paths: string[] }
+  | { kind: "other"; message: string };
+
+export interface Aria2Version {
+  version: string;
+  enabled_features: string[];
+}
+
+/** `args` has the RPC secret masked; `problems` is what aria2c printed or rejected */
+export interface Aria2ConfigCheck {
+  valid: boolean;
+  args: string[];
+  problems: string[];
+  version: string | null;
+}
+
+export interface Aria2Ping {
+  reachable: boolean;
+  latency_ms: number;
+  version: string | null;
+}
+
+export interface DownloadOrigin {
+  share_url: string | null;
+  fs_id: string | null;
+  requested_name: string | null;
+  group_id: string | null;
+  added_at: string | null;
+  completed_at: string | null;
+  /** Already in the history, or once was */
+  recorded: boolean;
+}
+
+export interface RetryFailure {
+  gid: string;
+  message: string;
+}
+
+export interface RetryReport {
+  retried: string[];
+  failed: RetryFailure[];
+}
+
+/** `requeued`: queued downloads aria2 came back without, added again from their shares */
+export interface Bootstrap {
+  aria2_running: boolean;
+  downloads: DownloadInfo[];
+  requeued: RetryReport;
+}
+
+export interface ExtractProgress {
+  gid: string;
+  percent: number;
+}
+
+export interface ExtractFailure {
+  gid: string;
+  error: AppError;
+}
+
+export interface HookResult {
+  gid: string;
+  command: string;
+  success: boolean;
+  exit_code: number | null;
+  timed_out: boolean;
+  error: string | null;
+}
+
+export interface DownloadPage {
+  downloads: DownloadInfo[];
+  offset: number;
+  total: number;
+}
+
+export interface BatchEstimate {
+  low_secs: number;
+  high_secs: number;
+  slow_bytes_per_sec: number;
+  fast_bytes_per_sec: number;
+  samples: number;
+}
+
+export type Priority = "low" | "normal" | "high";
+
+export interface QueueEntry {
+  gid: string;
+  share_url: string | null;
+  category: FileCategory | null;
+  group_id: string | null;
+  added_at: string;
+  priority: Priority;
+  /** Progress as last saved; render it until `download` arrives after a relaunch */
+  last_known: DownloadInfo | null;
+}
+
+export interface QueuedItem extends QueueEntry {
+  download: DownloadInfo | null;
+}
+
+export interface QueueImportReport {
+  gids: string[];
+  failed: BatchFailure[];
+}
+
+export interface GroupProgress {
+  group_id: string;
+  downloaded: number;
+  total_size: number;
+  progress: number;
+  downloads: DownloadInfo[];
+  current_gid: string | null;
+}
+
+export interface DownloadFile {
+  index: number;
+  path: string;
+  length: number;
+  completed_length: number;
+  selected: boolean;
+}
+
+export interface DownloadServer {
+  file_index: number;
+  uri: string;
+  current_uri: string;
+  host: string | null;
+  speed: number;
+}
+
+export type Theme = "light" | "dark" | "system";
+
+export interface ThemeChange {
+  theme: Theme;
+  resolved: "light" | "dark";
+}
+
+export interface UpdateCheck {
+  available: boolean;
+  version: string | null;
+  notes: string | null;
+  date: string | null;
+}
+
+export interface UpdateProgress {
+  downloaded: number;
+  total: number | null;
+}
+
+export interface GlobalStats {
+  download_speed: number;
+  upload_speed: number;
+  num_active: number;
+  num_waiting: number;
+  num_stopped: number;
+}
+
+export interface OverallProgress {
+  total_bytes: number;
+  downloaded_bytes: number;
+  overall_speed: number;
+  eta_seconds: number | null;
+}
+
+export interface ChecksumProgress {
+  path: string;
+  hashed_bytes: number;
+  total_bytes: number;
+}
+
+export interface ChecksumResult {
+  matched: boolean;
+  actual: string;
+}
+
+export interface DirCheck {
+  writable: boolean;
+  created: boolean;
+  free_bytes: number | null;
+}
+
+export interface AddDownloadOptions {
+  max_connections?: number;
+  split?: number;
+  share_url?: string;
+  fs_id?: string;
+  cookies?: string;
+  force?: boolean;
+  urls?: string[];
+  paused?: boolean;
+  expected_size?: number;
+  record_existing?: boolean;
+  overwrite_policy?: OverwritePolicy;
+  group_id?: string;
+  file_category?: FileCategory;
+  user_agent?: string;
+  priority?: Priority;
+}
+
+/** `resumed_from`: percent already on disk when a partial download is picked up again */
+export interface AddedDownload {
+  gid: string;
+  resumed_from: number | null;
+}
+
+export interface DownloadAdded extends AddedDownload {
+  tracking_id: string;
+}
+
+export interface DownloadAddFailed {
+  tracking_id: string;
+  error: AppError;
+}
+
+export interface BatchDownload {
+  url: string;
+  filename?: string;
+  options?: AddDownloadOptions;
+}
+
+export interface BatchFailure {
+  url: string;
+  message: string;
+}
+
+/** `sequential` runs one download at a time, in order */
+export type BatchMode = "parallel" | "sequential";
+
+export interface BatchReport {
+  group_id: string;
+  mode: BatchMode;
+  gids: string[];
+  failed: BatchFailure[];
+}
+
 export interface DownloadInfo {
   gid: string;
   filename: string;
+  path: string | null;
   total_size: number;
   downloaded: number;
   speed: number;
+  average_speed: number;
   progress: number;
   status: "active" | "waiting" | "paused" | "complete" | "error" | "removed";
   error_message: string | null;
+  eta_seconds: number | null;
+  connections: number;
+  uploaded: number;
+  upload_speed: number;
+  seeders: number;
+  source_uri: string | null;
+  created_time: string | null;
+  completed_time: string | null;
 }
 
 export type DownloadStatus = DownloadInfo["status"];
 
 export interface AppSettings {
+  version: number;
   download_dir: string;
   max_connections: number;
   split_count: number;
   min_split_size: string;
   user_agent: string;
   auto_start_aria2: boolean;
-  theme: string;
+  theme: Theme;
   max_overall_download_limit_kb_per_sec: number;
   max_download_limit_kb_per_sec: number;
+  rpc_url: string;
+  rpc_secret: string | null;
+  extra_aria2_args: string[];
+  seed_ratio: number;
+  link_cache_ttl_secs: number;
+  pause_on_metered: boolean;
+  aria2_startup_timeout_secs: number;
+  log_to_file: boolean;
+  max_concurrent_downloads: number;
+  pause_on_low_battery: boolean;
+  low_battery_threshold: number;
+  saved_limit: SavedBandwidthLimit | null;
+  file_allocation: FileAllocation;
+  auto_shutdown_idle_secs: number | null;
+  post_download_command: string | null;
+  enable_post_download_hooks: boolean;
+  approved_post_download_command: string | null;
+  auto_extract: boolean;
+  delete_after_extract: boolean;
+  overwrite_policy: OverwritePolicy;
+  bandwidth_schedule: BandwidthRule[];
+  category_dirs: Partial<Record<FileCategory, string>>;
+  log_level: LogLevel;
+  adaptive_connections: boolean;
+  connection_tiers: ConnectionTier[];
+  custom_bandwidth_presets: BandwidthPreset[];
+  stall_timeout_secs: number | null;
+  restart_stalled_downloads: boolean;
+  disable_ipv6: boolean;
+  async_dns_server: string | null;
+  connect_timeout_secs: number;
+  lowest_speed_limit: string;
+  user_agent_by_domain: Record<string, string>;
+  keep_daemon_on_exit: boolean;
+  terabox_max_concurrency: number;
+  retry_on_reconnect: boolean;
+  history_privacy: HistoryPrivacy;
+}
+
+/** `hashed` keeps `sha256:` and the hex digest of the URL */
+export type HistoryPrivacy = "full" | "hashed" | "omitted";
+
+export type FileAllocation = "none" | "prealloc" | "trunc" | "falloc";
+
+/** `weekdays`: bit 0 is Monday, bit 6 Sunday. Times are local `HH:MM`. */
+export interface BandwidthRule {
+  id: string;
+  weekdays: number;
+  start: string;
+  end: string;
+  limit_kb_per_sec: number;
+}
+
+/** Overall limit; `limit_kb_per_sec` 0 is unlimited */
+export interface BandwidthPreset {
+  name: string;
+  limit_kb_per_sec: number;
+  builtin: boolean;
+}
+
+export interface ConnectionTier {
+  max_size_mb: number;
+  connections: number;
+}
+
+export interface ActiveBandwidthLimit {
+  limit_kb_per_sec: number;
+  rule_id: string | null;
+}
+
+export type OverwritePolicy = "rename" | "overwrite" | "skip";
+
+export interface SavedBandwidthLimit {
+  max_overall_download_limit_kb_per_sec: number;
+  max_download_limit_kb_per_sec: number;
+}
+
+export interface HistoryStats {
+  total_downloads: number;
+  total_bytes: number;
+  success_count: number;
+  failure_count: number;
+  by_category: Partial<Record<FileCategory, number>>;
+}
+
+export interface HistoryStatsReport {
+  windowed: HistoryStats;
+  lifetime: HistoryStats;
+}
+
+export type LogLevel = "error" | "warn" | "info" | "debug" | "trace";
+
+export type ConfigFileState = "healthy" | "missing" | "restored" | "unrecoverable";
+
+export interface ConfigRepair {
+  file: string;
+  state: ConfigFileState;
+  backup_saved_at: string | null;
 }
 
 export function formatBandwidth(kbPerSec: number): string {